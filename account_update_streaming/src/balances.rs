@@ -0,0 +1,218 @@
+use crate::snapshot::AccountSnapshot;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+pub type SharedBalanceBook = Arc<Mutex<BalanceBook>>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Balance {
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "wb")]
+    pub wallet_balance: Decimal,
+    #[serde(rename = "cw")]
+    pub cross_wallet_balance: Decimal,
+    /// Change excluding realized PnL and commission (transfers, funding, ...).
+    #[serde(rename = "bc")]
+    pub balance_change: Decimal,
+}
+
+/// One asset of a spot `outboundAccountPosition` event.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpotBalance {
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "f")]
+    pub free: Decimal,
+    #[serde(rename = "l")]
+    pub locked: Decimal,
+}
+
+/// Running per-asset state. `wallet_balance` is the latest `wb` reported by
+/// Binance (or the REST snapshot); `balance_change_total` accumulates the `bc`
+/// deltas seen since start.
+#[derive(Debug, Clone)]
+pub struct AssetBalance {
+    pub wallet_balance: Decimal,
+    pub cross_wallet_balance: Decimal,
+    pub balance_change_total: Decimal,
+    /// Spot only: the part of `wallet_balance` held by open orders.
+    pub locked: Option<Decimal>,
+    pub updates: u64,
+}
+
+/// Balances keyed by asset, kept current from the `ACCOUNT_UPDATE` balances
+/// (futures) or `outboundAccountPosition` balances (spot).
+#[derive(Debug, Default)]
+pub struct BalanceBook {
+    balances: HashMap<String, AssetBalance>,
+}
+
+impl BalanceBook {
+    pub fn shared() -> SharedBalanceBook {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Rebuilds the book from a REST snapshot. Empty assets are skipped unless
+    /// already tracked; tracked ones keep their change total and update count.
+    pub fn replace_from(&mut self, snapshot: &AccountSnapshot) {
+        let mut previous = std::mem::take(&mut self.balances);
+        for asset in &snapshot.assets {
+            let tracked = previous.remove(&asset.asset);
+            if asset.wallet_balance.is_zero() && tracked.is_none() {
+                continue;
+            }
+            let (balance_change_total, updates) = tracked.map_or((Decimal::ZERO, 0), |balance| {
+                (balance.balance_change_total, balance.updates)
+            });
+            self.balances.insert(
+                asset.asset.clone(),
+                AssetBalance {
+                    wallet_balance: asset.wallet_balance,
+                    cross_wallet_balance: asset.cross_wallet_balance,
+                    balance_change_total,
+                    locked: None,
+                    updates,
+                },
+            );
+        }
+    }
+
+    pub fn apply(&mut self, balance: Balance) {
+        let entry = self.entry(balance.asset);
+        entry.wallet_balance = balance.wallet_balance;
+        entry.cross_wallet_balance = balance.cross_wallet_balance;
+        entry.balance_change_total += balance.balance_change;
+        entry.updates += 1;
+    }
+
+    /// Spot events carry absolute balances without a delta, so the change is
+    /// measured against the previous event for the asset (none for the first).
+    pub fn apply_spot(&mut self, balance: SpotBalance) {
+        let entry = self.entry(balance.asset);
+        let total = balance.free + balance.locked;
+        if entry.updates > 0 {
+            entry.balance_change_total += total - entry.wallet_balance;
+        }
+        entry.wallet_balance = total;
+        entry.locked = Some(balance.locked);
+        entry.updates += 1;
+    }
+
+    fn entry(&mut self, asset: String) -> &mut AssetBalance {
+        self.balances.entry(asset).or_insert_with(|| AssetBalance {
+            wallet_balance: Decimal::ZERO,
+            cross_wallet_balance: Decimal::ZERO,
+            balance_change_total: Decimal::ZERO,
+            locked: None,
+            updates: 0,
+        })
+    }
+
+    /// Tracked assets ordered by name.
+    pub fn dump(&self) -> Vec<(&str, &AssetBalance)> {
+        let mut balances = self
+            .balances
+            .iter()
+            .map(|(asset, balance)| (asset.as_str(), balance))
+            .collect::<Vec<_>>();
+        balances.sort_by(|a, b| a.0.cmp(b.0));
+        balances
+    }
+
+    pub fn log_summary(&self) {
+        if self.balances.is_empty() {
+            info!("Balances: no balances yet.");
+            return;
+        }
+
+        let summary = self
+            .dump()
+            .iter()
+            .map(|(asset, balance)| match balance.locked {
+                Some(locked) => format!(
+                    "{} {} (locked {}, change {}, {} update(s))",
+                    asset,
+                    balance.wallet_balance.normalize(),
+                    locked.normalize(),
+                    balance.balance_change_total.normalize(),
+                    balance.updates
+                ),
+                None => format!(
+                    "{} {} (cross {}, change {}, {} update(s))",
+                    asset,
+                    balance.wallet_balance.normalize(),
+                    balance.cross_wallet_balance.normalize(),
+                    balance.balance_change_total.normalize(),
+                    balance.updates
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        info!("Balances: {}", summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decimals keep Binance's string exactly, down to the last digit and trailing zero.
+    #[test]
+    fn balance_decimals_round_trip_exactly() {
+        let balance: Balance = serde_json::from_str(
+            r#"{"a":"USDT","wb":"0.00000100","cw":"123456789012.12345678","bc":"-0.00000001"}"#,
+        )
+        .unwrap();
+        assert_eq!(balance.wallet_balance.to_string(), "0.00000100");
+        assert_eq!(
+            balance.cross_wallet_balance.to_string(),
+            "123456789012.12345678"
+        );
+        assert_eq!(balance.balance_change.to_string(), "-0.00000001");
+        assert_eq!(
+            (balance.cross_wallet_balance + balance.balance_change).to_string(),
+            "123456789012.12345677"
+        );
+    }
+
+    /// Trimmed `GET /fapi/v2/account` response.
+    const ACCOUNT: &str = r#"{"totalWalletBalance":"1523.50000000","availableBalance":"1400.00000000",
+        "assets":[
+            {"asset":"USDT","walletBalance":"1500.00000000","unrealizedProfit":"0.00000000","crossWalletBalance":"1450.00000000","availableBalance":"1400.00000000","marginAvailable":true,"updateTime":1625474304765},
+            {"asset":"BNB","walletBalance":"0.00000000","unrealizedProfit":"0.00000000","crossWalletBalance":"0.00000000","availableBalance":"0.00000000","marginAvailable":true,"updateTime":0},
+            {"asset":"BTC","walletBalance":"0.00000000","unrealizedProfit":"0.00000000","crossWalletBalance":"0.00000000","availableBalance":"0.00000000","marginAvailable":true,"updateTime":0}],
+        "positions":[]}"#;
+
+    #[test]
+    fn replace_from_seeds_balances_and_keeps_the_running_change() {
+        let snapshot: AccountSnapshot = serde_json::from_str(ACCOUNT).unwrap();
+        let mut book = BalanceBook::default();
+        book.apply(
+            serde_json::from_str(r#"{"a":"USDT","wb":"1000","cw":"1000","bc":"25.5"}"#).unwrap(),
+        );
+        book.apply(serde_json::from_str(r#"{"a":"BTC","wb":"0.1","cw":"0.1","bc":"0"}"#).unwrap());
+        book.apply(serde_json::from_str(r#"{"a":"ETH","wb":"1","cw":"1","bc":"0"}"#).unwrap());
+
+        book.replace_from(&snapshot);
+
+        let balances = book.dump();
+        // BNB was never held, BTC was emptied while down, ETH is no longer listed.
+        assert_eq!(
+            balances.iter().map(|(asset, _)| *asset).collect::<Vec<_>>(),
+            ["BTC", "USDT"]
+        );
+        let (_, btc) = balances[0];
+        assert!(btc.wallet_balance.is_zero());
+        let (_, usdt) = balances[1];
+        assert_eq!(usdt.wallet_balance.to_string(), "1500.00000000");
+        assert_eq!(usdt.cross_wallet_balance.to_string(), "1450.00000000");
+        assert_eq!(usdt.balance_change_total.to_string(), "25.5");
+        assert_eq!(usdt.updates, 1);
+    }
+}
@@ -0,0 +1,402 @@
+//! Authenticated futures WebSocket API client: logs on once with an Ed25519
+//! signature (`session.logon`), then places and cancels orders over the same
+//! socket, correlating responses by request id.
+
+use account_update_streaming::settings;
+use account_update_streaming::signing::{RequestSigner, PRIVATE_KEY_PEM_ENV};
+use binance_common::endpoints::{self, Market, WS_API_URL_ENV};
+use binance_common::{proxy, tls};
+use dotenv::dotenv;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+#[derive(Debug)]
+enum WsApiCommand {
+    Place {
+        symbol: String,
+        side: String,
+        order_type: String,
+        quantity: String,
+        limit: Option<(String, String)>,
+    },
+    Cancel {
+        symbol: String,
+        order_id: u64,
+    },
+    Status,
+    Help,
+    Quit,
+}
+
+#[derive(Debug)]
+enum PendingRequest {
+    Logon,
+    OrderPlace { symbol: String },
+    OrderCancel { symbol: String, order_id: u64 },
+    Status,
+}
+
+struct WsApiClient {
+    api_key: String,
+    signer: RequestSigner,
+    next_request_id: i64,
+    pending_requests: HashMap<i64, PendingRequest>,
+    logged_on: bool,
+}
+
+impl WsApiClient {
+    fn new(api_key: String, signer: RequestSigner) -> Self {
+        Self {
+            api_key,
+            signer,
+            next_request_id: 1,
+            pending_requests: HashMap::new(),
+            logged_on: false,
+        }
+    }
+
+    fn next_id(&mut self) -> i64 {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        id
+    }
+
+    /// `session.logon` params: every param except `signature`, sorted by name,
+    /// joined as a query string and signed with Ed25519 (base64 encoded).
+    fn logon_request(&mut self) -> Value {
+        let timestamp = timestamp_ms();
+        let payload = format!("apiKey={}&timestamp={}", self.api_key, timestamp);
+        let signature = self.signer.sign(&payload);
+
+        let id = self.next_id();
+        self.pending_requests.insert(id, PendingRequest::Logon);
+        json!({
+            "id": id,
+            "method": "session.logon",
+            "params": {
+                "apiKey": self.api_key,
+                "timestamp": timestamp,
+                "signature": signature,
+            }
+        })
+    }
+
+    /// Builds the request for `command`; `None` for purely local commands.
+    fn command_request(&mut self, command: WsApiCommand) -> Option<Value> {
+        let (method, params, pending) = match command {
+            WsApiCommand::Place {
+                symbol,
+                side,
+                order_type,
+                quantity,
+                limit,
+            } => {
+                let mut params = Map::new();
+                params.insert("symbol".to_string(), json!(symbol.to_uppercase()));
+                params.insert("side".to_string(), json!(side.to_uppercase()));
+                params.insert("type".to_string(), json!(order_type.to_uppercase()));
+                params.insert("quantity".to_string(), json!(quantity));
+                if let Some((price, time_in_force)) = limit {
+                    params.insert("price".to_string(), json!(price));
+                    params.insert(
+                        "timeInForce".to_string(),
+                        json!(time_in_force.to_uppercase()),
+                    );
+                }
+                (
+                    "order.place",
+                    params,
+                    PendingRequest::OrderPlace {
+                        symbol: symbol.to_uppercase(),
+                    },
+                )
+            }
+            WsApiCommand::Cancel { symbol, order_id } => {
+                let mut params = Map::new();
+                params.insert("symbol".to_string(), json!(symbol.to_uppercase()));
+                params.insert("orderId".to_string(), json!(order_id));
+                (
+                    "order.cancel",
+                    params,
+                    PendingRequest::OrderCancel {
+                        symbol: symbol.to_uppercase(),
+                        order_id,
+                    },
+                )
+            }
+            WsApiCommand::Status => ("session.status", Map::new(), PendingRequest::Status),
+            WsApiCommand::Help => {
+                print_ws_api_help();
+                return None;
+            }
+            WsApiCommand::Quit => return None,
+        };
+
+        if !self.logged_on && !matches!(pending, PendingRequest::Status) {
+            warn!("Not logged on yet; {} may be rejected.", method);
+        }
+
+        let mut params = params;
+        // Logged-on sessions are authenticated already; requests still need a timestamp.
+        if method != "session.status" {
+            params.insert("timestamp".to_string(), json!(timestamp_ms()));
+        }
+
+        let id = self.next_id();
+        self.pending_requests.insert(id, pending);
+        Some(json!({ "id": id, "method": method, "params": params }))
+    }
+
+    fn handle_response(&mut self, message: &str) {
+        let value: Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Failed to deserialize message: {}, error: {}", message, e);
+                return;
+            }
+        };
+
+        let Some(id) = value.get("id").and_then(Value::as_i64) else {
+            debug!("Message without numeric id: {:?}", value);
+            return;
+        };
+
+        let Some(pending) = self.pending_requests.remove(&id) else {
+            warn!(
+                "Received response for unknown request id={}: {:?}",
+                id, value
+            );
+            return;
+        };
+
+        if let Some(error_obj) = value.get("error") {
+            let code = error_obj.get("code").and_then(Value::as_i64).unwrap_or(0);
+            let msg = error_obj.get("msg").and_then(Value::as_str).unwrap_or("");
+            match pending {
+                PendingRequest::Logon => error!("session.logon failed ({}): {}", code, msg),
+                PendingRequest::OrderPlace { symbol } => {
+                    error!("order.place {} failed ({}): {}", symbol, code, msg)
+                }
+                PendingRequest::OrderCancel { symbol, order_id } if code == -2011 => {
+                    info!(
+                        "Order {} on {} was already closed: {}",
+                        order_id, symbol, msg
+                    )
+                }
+                PendingRequest::OrderCancel { symbol, order_id } => {
+                    error!(
+                        "order.cancel {} {} failed ({}): {}",
+                        symbol, order_id, code, msg
+                    )
+                }
+                PendingRequest::Status => error!("session.status failed ({}): {}", code, msg),
+            }
+            return;
+        }
+
+        let result = value.get("result").cloned().unwrap_or(Value::Null);
+        match pending {
+            PendingRequest::Logon => {
+                self.logged_on = true;
+                info!("Logged on (id={}) as {}", id, result["apiKey"]);
+            }
+            PendingRequest::OrderPlace { symbol } => {
+                info!(
+                    "Order placed (id={}) - Symbol: {}, Order ID: {}, Status: {}, Executed Qty: {}",
+                    id, symbol, result["orderId"], result["status"], result["executedQty"]
+                );
+            }
+            PendingRequest::OrderCancel { symbol, order_id } => {
+                info!(
+                    "Order cancelled (id={}) - Symbol: {}, Order ID: {}, Status: {}",
+                    id, symbol, order_id, result["status"]
+                );
+            }
+            PendingRequest::Status => {
+                info!("Session status (id={}): {}", id, result);
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    dotenv().ok();
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let log_file = match args.iter().position(|arg| arg == "--log-file") {
+        Some(index) => Some(args.get(index + 1).ok_or("Missing value for --log-file")?),
+        None => None,
+    };
+    settings::init_logging(log_file.map(String::as_str));
+
+    let use_mainnet = args.iter().any(|arg| arg == "--mainnet");
+    let proxy_flag = match args.iter().position(|arg| arg == "--proxy") {
+        Some(index) => Some(args.get(index + 1).ok_or("Missing value for --proxy")?),
+        None => None,
+    };
+    proxy::init(proxy_flag.map(String::as_str))?;
+    tls::init(args.iter().any(|arg| arg == "--native-tls"))?;
+    // The order methods used here are the futures ones.
+    let market = Market::FuturesMainnet.with_testnet(!use_mainnet);
+    let ws_url = endpoints::resolve_url(None, WS_API_URL_ENV, market.ws_api_base());
+
+    let api_key = env::var("BINANCE_API_KEY").map_err(|_| "Missing BINANCE_API_KEY")?;
+    // session.logon only accepts Ed25519 signatures, so there is no HMAC fallback here.
+    let key_path = env::var(PRIVATE_KEY_PEM_ENV)
+        .map_err(|_| format!("Missing {} (PEM Ed25519 private key)", PRIVATE_KEY_PEM_ENV))?;
+    let signer = RequestSigner::ed25519_from_pem_file(key_path.trim())?;
+
+    info!("Connecting to WebSocket API: {}", ws_url);
+    let (ws_stream, _) = proxy::connect_ws(url::Url::parse(&ws_url)?).await?;
+    let (mut write, mut read) = ws_stream.split();
+
+    let mut client = WsApiClient::new(api_key, signer);
+    write
+        .send(Message::Text(client.logon_request().to_string()))
+        .await?;
+
+    let (command_tx, mut command_rx) = mpsc::channel(100);
+    spawn_stdin_command_reader(command_tx);
+    print_ws_api_help();
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C, shutting down.");
+                break;
+            }
+            command = command_rx.recv() => {
+                let Some(command) = command else {
+                    break;
+                };
+                if matches!(command, WsApiCommand::Quit) {
+                    info!("Quit requested; closing websocket.");
+                    break;
+                }
+                if let Some(request) = client.command_request(command) {
+                    debug!("Sending request: {}", request);
+                    write.send(Message::Text(request.to_string())).await?;
+                }
+            }
+            msg = read.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => client.handle_response(&text),
+                    Some(Ok(Message::Binary(payload))) => match String::from_utf8(payload) {
+                        Ok(text) => client.handle_response(&text),
+                        Err(e) => debug!(
+                            "Ignoring {}-byte binary frame that is not UTF-8: {}",
+                            e.as_bytes().len(),
+                            e.utf8_error()
+                        ),
+                    },
+                    Some(Ok(Message::Ping(payload))) => {
+                        write.send(Message::Pong(payload)).await?;
+                    }
+                    Some(Ok(Message::Close(frame))) => {
+                        info!("WebSocket closed: {:?}", frame);
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        error!("WebSocket error: {}", e);
+                        break;
+                    }
+                    None => {
+                        warn!("WebSocket stream ended.");
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let _ = write.send(Message::Close(None)).await;
+    Ok(())
+}
+
+fn spawn_stdin_command_reader(command_tx: mpsc::Sender<WsApiCommand>) {
+    std::thread::spawn(move || {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            let Ok(input) = line else {
+                continue;
+            };
+
+            let parts = input.split_whitespace().collect::<Vec<_>>();
+            let cmd = match parts.as_slice() {
+                ["place", symbol, side, order_type, quantity] => Some(WsApiCommand::Place {
+                    symbol: symbol.to_string(),
+                    side: side.to_string(),
+                    order_type: order_type.to_string(),
+                    quantity: quantity.to_string(),
+                    limit: None,
+                }),
+                ["place", symbol, side, order_type, quantity, price, time_in_force] => {
+                    Some(WsApiCommand::Place {
+                        symbol: symbol.to_string(),
+                        side: side.to_string(),
+                        order_type: order_type.to_string(),
+                        quantity: quantity.to_string(),
+                        limit: Some((price.to_string(), time_in_force.to_string())),
+                    })
+                }
+                ["cancel", symbol, order_id] => match order_id.parse::<u64>() {
+                    Ok(order_id) => Some(WsApiCommand::Cancel {
+                        symbol: symbol.to_string(),
+                        order_id,
+                    }),
+                    Err(_) => {
+                        println!("Invalid order id: {}", order_id);
+                        None
+                    }
+                },
+                ["status"] => Some(WsApiCommand::Status),
+                ["help"] => Some(WsApiCommand::Help),
+                ["quit"] => Some(WsApiCommand::Quit),
+                [] => None,
+                _ => {
+                    println!("Unknown command. Try: place, cancel, status, help, quit");
+                    None
+                }
+            };
+
+            if let Some(cmd) = cmd {
+                let should_quit = matches!(cmd, WsApiCommand::Quit);
+                if command_tx.blocking_send(cmd).is_err() {
+                    break;
+                }
+
+                if should_quit {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn print_ws_api_help() {
+    info!("WebSocket API commands:");
+    info!("  place <symbol> <side> <type> <qty> [<price> <tif>] - order.place");
+    info!("  cancel <symbol> <orderId>                          - order.cancel");
+    info!("  status                                             - session.status");
+    info!("  help                                               - show command help");
+    info!("  quit                                               - close websocket and exit");
+}
+
+fn timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System clock is before the Unix epoch")
+        .as_millis() as u64
+}
@@ -0,0 +1,65 @@
+use thiserror::Error;
+use tokio_tungstenite::tungstenite;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("HTTP status {status}: {body}")]
+    HttpStatus { status: u16, body: String },
+
+    #[error("Invalid header value: {0}")]
+    InvalidHeader(#[from] reqwest::header::InvalidHeaderValue),
+
+    #[error("WebSocket error: {0}")]
+    WebSocket(Box<tungstenite::Error>),
+
+    #[error("Invalid URL: {0}")]
+    Url(#[from] url::ParseError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Binance API error {code}: {msg}")]
+    BinanceApi { code: i32, msg: String },
+
+    #[error("Missing BINANCE_API_KEY environment variable")]
+    MissingApiKey,
+
+    #[error("Missing BINANCE_API_SECRET environment variable")]
+    MissingApiSecret,
+
+    #[error("Invalid Ed25519 private key {0}")]
+    InvalidPrivateKey(String),
+
+    #[error("Invalid order: {0}")]
+    InvalidOrder(String),
+
+    /// Binance code -2011: the order is unknown, usually because it already filled or was cancelled.
+    #[error("Unknown order: {0}")]
+    UnknownOrder(String),
+}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(err: tungstenite::Error) -> Self {
+        ClientError::WebSocket(Box::new(err))
+    }
+}
+
+impl ClientError {
+    /// Binance code -1003: too many requests for the current weight limit.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, ClientError::BinanceApi { code: -1003, .. })
+    }
+
+    /// Failures worth retrying unchanged: network errors, server-side 5xx and
+    /// rate limits. Rejected credentials or requests fail the same way every time.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ClientError::Http(_) => true,
+            ClientError::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            _ => self.is_rate_limited(),
+        }
+    }
+}
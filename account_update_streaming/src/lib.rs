@@ -0,0 +1,6 @@
+//! Modules shared by the account stream (`main.rs`) and the `ws_api` bin.
+
+pub use binance_common::settings;
+
+pub mod error;
+pub mod signing;
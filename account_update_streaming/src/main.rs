@@ -1,25 +1,65 @@
 // src/main.rs
 
 use futures::{SinkExt, StreamExt}; // For StreamExt and SinkExt traits
+use std::collections::HashSet;
 use std::env;
 use std::error::Error;
-use std::time::Duration;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 use tokio::time;
 use tokio_tungstenite::tungstenite::protocol::Message;
 use url::Url;
 
-mod settings;
+mod balances;
+mod orders;
+mod positions;
+mod rate_limit;
+mod snapshot;
+use account_update_streaming::{error, settings, signing};
+use balances::{Balance, BalanceBook, SharedBalanceBook, SpotBalance};
+use binance_common::jitter::with_jitter;
+use binance_common::{endpoints, proxy, tls};
 use dotenv::dotenv;
+use endpoints::{Market, MARKET_NAMES, REST_URL_ENV, WS_URL_ENV};
+use error::ClientError;
+use positions::{Position, PositionBook, SharedPositionBook};
+use snapshot::AccountSnapshot;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
 use reqwest::header::{HeaderMap, HeaderValue};
-use serde::Deserialize;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 
 // =============================== Configuration ===============================
 
-const TESTNET_API_BASE_URL: &str = "https://testnet.binancefuture.com";
-const TESTNET_WS_BASE_URL: &str = "wss://fstream.binancefuture.com/ws"; // WebSocket base URL
+// Futures testnet unless `--market` picks another venue; BINANCE_WS_URL and
+// BINANCE_REST_URL override the market's compiled URL roots.
+const DEFAULT_MARKET: Market = Market::FuturesTestnet;
+static MARKET: OnceLock<Market> = OnceLock::new();
+// Optional recvWindow (ms) sent with signed requests; Binance defaults to 5000 when omitted.
+const RECV_WINDOW_ENV: &str = "BINANCE_RECV_WINDOW_MS";
+
+// Reconnect backoff: starts at the initial delay, doubles up to the cap, and resets
+// once a connection has stayed up longer than the stable threshold.
+const RECONNECT_INITIAL_BACKOFF_SECS: u64 = 1;
+const RECONNECT_MAX_BACKOFF_SECS: u64 = 60;
+const RECONNECT_STABLE_CONNECTION_SECS: u64 = 30;
+// Attempts at creating the first listen key before giving up, so a network that
+// isn't up yet at launch doesn't end the process. Retries use the reconnect backoff.
+const LISTEN_KEY_MAX_ATTEMPTS_ENV: &str = "LISTEN_KEY_MAX_ATTEMPTS";
+const DEFAULT_LISTEN_KEY_MAX_ATTEMPTS: u32 = 5;
+// Listen keys expire after 60 minutes; renew well inside that window.
+const LISTEN_KEY_RENEW_INTERVAL_SECS: u64 = 30 * 60;
+// Upper bound on closing the listen key at shutdown, so a dead network can't hang the exit.
+const LISTEN_KEY_CLOSE_TIMEOUT_SECS: u64 = 5;
+// How often the in-memory position book is logged.
+const POSITION_BOOK_LOG_INTERVAL_SECS: u64 = 60;
+// Balance summary period in seconds; 0 disables the summary.
+const BALANCE_LOG_INTERVAL_ENV: &str = "BALANCE_LOG_INTERVAL_SECS";
+const DEFAULT_BALANCE_LOG_INTERVAL_SECS: u64 = 60;
 
 // =============================== Data Structures ===============================
 
@@ -29,6 +69,12 @@ struct ListenKeyResponse {
     listen_key: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerTimeResponse {
+    server_time: i64,
+}
+
 #[derive(Debug, Deserialize)]
 struct ErrorResponse {
     code: i32,
@@ -46,23 +92,135 @@ enum BinanceEvent {
 
     #[serde(rename = "ACCOUNT_UPDATE")]
     AccountUpdate(AccountUpdate),
+
+    #[serde(rename = "MARGIN_CALL")]
+    MarginCall(MarginCall),
+
+    #[serde(rename = "ACCOUNT_CONFIG_UPDATE")]
+    AccountConfigUpdate(AccountConfigUpdate),
+
+    #[serde(rename = "listenKeyExpired")]
+    ListenKeyExpired(ListenKeyExpired),
+
+    // Spot user data stream (`--market spot`)
+    #[serde(rename = "executionReport")]
+    ExecutionReport(ExecutionReport),
+
+    #[serde(rename = "outboundAccountPosition")]
+    OutboundAccountPosition(OutboundAccountPosition),
     // Add other event types here as needed
 }
 
+/// Tags handled by `BinanceEvent`; an `Unknown` carrying one of these means the
+/// payload changed shape rather than being a new event type.
+const KNOWN_EVENT_TYPES: [&str; 8] = [
+    "ORDER_TRADE_UPDATE",
+    "TRADE_LITE",
+    "ACCOUNT_UPDATE",
+    "MARGIN_CALL",
+    "ACCOUNT_CONFIG_UPDATE",
+    "listenKeyExpired",
+    "executionReport",
+    "outboundAccountPosition",
+];
+
+/// Internally tagged enums cannot capture unmatched tags with data, so new
+/// event types fall through to `Unknown` here instead of failing to parse.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UserDataMessage {
+    Known(BinanceEvent),
+    Unknown {
+        #[serde(rename = "e")]
+        event_type: String,
+        /// Every field except `e`.
+        #[serde(flatten)]
+        raw: serde_json::Value,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum StreamExit {
+    Disconnected,
+    ListenKeyExpired,
+}
+
 #[derive(Debug, Deserialize)]
 struct OrderTradeUpdate {
+    #[serde(rename = "E")]
+    event_time: u64,
     #[serde(rename = "o")]
     order_detail: OrderDetail,
 }
 
 #[derive(Debug, Deserialize)]
 struct OrderDetail {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "o")]
+    order_type: String,
+    #[serde(rename = "q")]
+    original_quantity: Decimal,
+    #[serde(rename = "z")]
+    filled_quantity: Decimal, // Accumulated across fills
+    #[serde(rename = "ap")]
+    average_price: Decimal,
+    #[serde(rename = "L")]
+    last_filled_price: Decimal,
+    #[serde(rename = "rp")]
+    realized_profit: Decimal,
+    // Commission fields are only pushed for updates that carry a fill.
+    #[serde(rename = "n")]
+    commission: Option<Decimal>,
+    #[serde(rename = "N")]
+    commission_asset: Option<String>,
     #[serde(rename = "i")]
     order_id: u64, // Changed from String to u64 based on the error message
     #[serde(rename = "X")]
     order_status: String, // Order status
 }
 
+/// One line of the `--emit-json` feed. Field names and types are a stable
+/// contract for downstream consumers; decimals serialize as strings.
+#[derive(Debug, Serialize)]
+struct OrderUpdateRecord<'a> {
+    order_id: u64,
+    symbol: &'a str,
+    status: &'a str,
+    side: &'a str,
+    filled_qty: Decimal,
+    avg_price: Decimal,
+    ts: u64,
+}
+
+impl<'a> OrderUpdateRecord<'a> {
+    fn from_futures(order: &'a OrderDetail, event_time: u64) -> Self {
+        Self {
+            order_id: order.order_id,
+            symbol: &order.symbol,
+            status: &order.order_status,
+            side: &order.side,
+            filled_qty: order.filled_quantity,
+            avg_price: order.average_price,
+            ts: event_time,
+        }
+    }
+
+    fn from_spot(report: &'a ExecutionReport) -> Self {
+        Self {
+            order_id: report.order_id,
+            symbol: &report.symbol,
+            status: &report.order_status,
+            side: &report.side,
+            filled_qty: report.filled_quantity,
+            avg_price: report.average_price(),
+            ts: report.event_time,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TradeLite {
     #[serde(rename = "i")]
@@ -70,9 +228,9 @@ struct TradeLite {
     #[serde(rename = "s")]
     symbol: String,
     #[serde(rename = "q")]
-    quantity: String,
+    quantity: Decimal,
     #[serde(rename = "p")]
-    price: String,
+    price: Decimal,
     #[serde(rename = "m")]
     is_maker: bool,
     // Add other fields as necessary
@@ -80,6 +238,8 @@ struct TradeLite {
 
 #[derive(Debug, Deserialize)]
 struct AccountUpdate {
+    #[serde(rename = "E")]
+    event_time: u64,
     #[serde(rename = "a")]
     account_info: AccountInfo,
 }
@@ -95,52 +255,196 @@ struct AccountInfo {
 }
 
 #[derive(Debug, Deserialize)]
-struct Balance {
-    #[serde(rename = "a")]
-    asset: String,
-    #[serde(rename = "wb")]
-    available_balance: String,
+struct MarginCall {
     #[serde(rename = "cw")]
-    cross_wallet_balance: String,
-    #[serde(rename = "bc")]
-    balance_change: String,
+    cross_wallet_balance: Option<Decimal>,
+    #[serde(rename = "p")]
+    positions: Vec<MarginCallPosition>,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
-struct Position {
+struct MarginCallPosition {
     #[serde(rename = "s")]
     symbol: String,
-    #[serde(rename = "pa")]
-    position_amount: String,
-    #[serde(rename = "ep")]
-    entry_price: String,
-    #[serde(rename = "cr")]
-    accumulated_realized: String,
-    #[serde(rename = "up")]
-    unrealized_profit: String,
-    #[serde(rename = "mt")]
-    margin_type: String,
-    #[serde(rename = "iw")]
-    isolated_wallet: String,
     #[serde(rename = "ps")]
     position_side: String,
-    #[serde(rename = "ma")]
-    margin_asset: String,
-    #[serde(rename = "bep")]
-    break_even_price: String,
+    #[serde(rename = "pa")]
+    position_amount: Decimal,
+    #[serde(rename = "mp")]
+    mark_price: Decimal,
+    #[serde(rename = "mm")]
+    maintenance_margin_required: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountConfigUpdate {
+    #[serde(rename = "ac")]
+    trade_pair_config: Option<TradePairConfig>,
+    #[serde(rename = "ai")]
+    account_info_config: Option<AccountInfoConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradePairConfig {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "l")]
+    leverage: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountInfoConfig {
+    #[serde(rename = "j")]
+    multi_assets_mode: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListenKeyExpired {
+    #[serde(rename = "E")]
+    event_time: u64,
+}
+
+/// Spot order update; unlike `ORDER_TRADE_UPDATE` the fields are not nested.
+#[derive(Debug, Deserialize)]
+struct ExecutionReport {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "S")]
+    side: String,
+    #[serde(rename = "o")]
+    order_type: String,
+    #[serde(rename = "q")]
+    original_quantity: Decimal,
+    #[serde(rename = "x")]
+    execution_type: String, // NEW, TRADE, CANCELED, ...
+    #[serde(rename = "X")]
+    order_status: String,
+    #[serde(rename = "i")]
+    order_id: u64,
+    #[serde(rename = "z")]
+    filled_quantity: Decimal, // Accumulated across fills
+    #[serde(rename = "Z")]
+    filled_quote_quantity: Decimal,
+    #[serde(rename = "L")]
+    last_filled_price: Decimal,
+    // `N` is null until the order has a fill.
+    #[serde(rename = "n")]
+    commission: Option<Decimal>,
+    #[serde(rename = "N")]
+    commission_asset: Option<String>,
+}
+
+impl ExecutionReport {
+    /// Spot reports carry no average price; derive it from the cumulative quote quantity.
+    fn average_price(&self) -> Decimal {
+        if self.filled_quantity.is_zero() {
+            Decimal::ZERO
+        } else {
+            self.filled_quote_quantity / self.filled_quantity
+        }
+    }
+}
+
+/// Spot balances of the assets changed by an account event.
+#[derive(Debug, Deserialize)]
+struct OutboundAccountPosition {
+    #[serde(rename = "B")]
+    balances: Vec<SpotBalance>,
 }
 
 // =============================== Helper Functions ===============================
 
-async fn create_listen_key() -> Result<String, Box<dyn Error>> {
+/// Sends `request` through the shared rate limiter, then syncs the limiter with
+/// the server's used-weight header. A 429/418 sleeps for `Retry-After` before
+/// the error is returned, so callers never hammer a banned endpoint.
+async fn send_rate_limited(
+    request: reqwest::RequestBuilder,
+    weight: u32,
+) -> Result<reqwest::Response, ClientError> {
+    let limiter = rate_limit::shared();
+    rate_limit::acquire(&limiter, weight).await;
+
+    let resp = request.send().await?;
+    if let Some(retry_after) = rate_limit::observe_response(&limiter, resp.status(), resp.headers())
+    {
+        time::sleep(retry_after).await;
+    }
+    Ok(resp)
+}
+
+/// Turns a non-2xx response into `BinanceApi` when the body carries Binance's
+/// `{code, msg}` shape, falling back to the raw status and body otherwise.
+async fn error_from_response(resp: reqwest::Response) -> ClientError {
+    let status = resp.status();
+    let error_text = match resp.text().await {
+        Ok(text) => text,
+        Err(e) => return ClientError::Http(e),
+    };
+
+    match serde_json::from_str::<ErrorResponse>(&error_text) {
+        Ok(err) if err.code == -2011 => {
+            warn!("Unknown order: {}", err.msg);
+            ClientError::UnknownOrder(err.msg)
+        }
+        Ok(err) => {
+            error!("Error {}: {}", err.code, err.msg);
+            ClientError::BinanceApi {
+                code: err.code,
+                msg: err.msg,
+            }
+        }
+        Err(_) => {
+            error!("HTTP Error {}: {}", status, error_text);
+            ClientError::HttpStatus {
+                status: status.as_u16(),
+                body: error_text,
+            }
+        }
+    }
+}
+
+async fn fetch_server_time() -> Result<i64, ClientError> {
+    let url = format!("{}{}", rest_base_url(), server_time_path());
+    let resp = send_rate_limited(proxy::http_client()?.get(&url), 1).await?;
+
+    if resp.status().is_success() {
+        let data: ServerTimeResponse = resp.json().await?;
+        Ok(data.server_time)
+    } else {
+        Err(error_from_response(resp).await)
+    }
+}
+
+/// Measures server minus local time (midpoint of the round trip) and feeds it
+/// to the signing helper so `timestamp` stays inside Binance's recvWindow.
+async fn sync_clock_offset() -> Result<i64, ClientError> {
+    let sent_at = signing::local_timestamp_ms();
+    let server_time = fetch_server_time().await?;
+    let received_at = signing::local_timestamp_ms();
+
+    let offset = server_time - (sent_at + received_at) / 2;
+    signing::set_clock_offset_ms(offset);
+    if offset.abs() > 1000 {
+        warn!(
+            "Local clock is off by {} ms from Binance server time; check NTP on this machine.",
+            offset
+        );
+    } else {
+        info!("Clock offset vs Binance server time: {} ms", offset);
+    }
+    Ok(offset)
+}
+
+async fn create_listen_key() -> Result<String, ClientError> {
     let api_key = get_api_key()?;
-    let url = format!("{}/fapi/v1/listenKey", TESTNET_API_BASE_URL);
-    let client = reqwest::Client::new();
+    let url = format!("{}{}", rest_base_url(), listen_key_path());
+    let client = proxy::http_client()?;
     let mut headers = HeaderMap::new();
     headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key)?);
 
-    let resp = client.post(&url).headers(headers).send().await?;
+    let resp = send_rate_limited(client.post(&url).headers(headers), 1).await?;
     let status = resp.status(); // Extract status before consuming resp
 
     if status.is_success() {
@@ -148,30 +452,44 @@ async fn create_listen_key() -> Result<String, Box<dyn Error>> {
         info!("Listen Key: {}", data.listen_key);
         Ok(data.listen_key)
     } else {
-        let error_text = resp.text().await?;
-        match serde_json::from_str::<ErrorResponse>(&error_text) {
-            Ok(err) => {
-                error!("Error {}: {}", err.code, err.msg);
-                Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    err.msg,
-                )))
-            }
-            Err(_) => {
-                error!("HTTP Error {}: {}", status, error_text);
-                Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Failed to parse error response",
-                )))
-            }
+        Err(error_from_response(resp).await)
+    }
+}
+
+/// `create_listen_key` for startup: transient failures (network, 5xx, rate
+/// limits) are retried with backoff up to `max_attempts` times in total.
+async fn create_listen_key_with_retry(max_attempts: u32) -> Result<String, ClientError> {
+    let max_backoff = Duration::from_secs(RECONNECT_MAX_BACKOFF_SECS);
+    let mut backoff = Duration::from_secs(RECONNECT_INITIAL_BACKOFF_SECS);
+    let mut attempt = 1;
+
+    loop {
+        let e = match create_listen_key().await {
+            Ok(listen_key) => return Ok(listen_key),
+            Err(e) => e,
+        };
+        if !e.is_transient() || attempt >= max_attempts {
+            return Err(e);
+        }
+        if e.is_rate_limited() {
+            backoff = max_backoff;
         }
+
+        let delay = with_jitter(backoff);
+        warn!(
+            "Failed to create listen key (attempt {}/{}): {}; retrying in {:?}...",
+            attempt, max_attempts, e, delay
+        );
+        time::sleep(delay).await;
+        backoff = (backoff * 2).min(max_backoff);
+        attempt += 1;
     }
 }
 
-async fn renew_listen_key(listen_key: &str) -> Result<(), Box<dyn Error>> {
+async fn renew_listen_key(listen_key: &str) -> Result<(), ClientError> {
     let api_key = get_api_key()?;
-    let url = format!("{}/fapi/v1/listenKey", TESTNET_API_BASE_URL);
-    let client = reqwest::Client::new();
+    let url = format!("{}{}", rest_base_url(), listen_key_path());
+    let client = proxy::http_client()?;
     let mut headers = HeaderMap::new();
     headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key)?);
     headers.insert(
@@ -181,35 +499,90 @@ async fn renew_listen_key(listen_key: &str) -> Result<(), Box<dyn Error>> {
 
     let params = [("listenKey", listen_key)];
 
-    let resp = client
-        .put(&url)
-        .headers(headers)
-        .form(&params)
-        .send()
-        .await?;
+    let resp = send_rate_limited(client.put(&url).headers(headers).form(&params), 1).await?;
     let status = resp.status(); // Extract status before consuming resp
 
     if status.is_success() {
         info!("Listen key renewed successfully.");
         Ok(())
     } else {
-        let error_text = resp.text().await?;
-        match serde_json::from_str::<ErrorResponse>(&error_text) {
-            Ok(err) => {
-                error!("Error {}: {}", err.code, err.msg);
-                Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    err.msg,
-                )))
-            }
-            Err(_) => {
-                error!("HTTP Error {}: {}", status, error_text);
-                Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Failed to parse error response",
-                )))
-            }
+        Err(error_from_response(resp).await)
+    }
+}
+
+/// Deletes the listen key so Binance closes its user data stream right away
+/// instead of letting it expire.
+async fn close_listen_key(listen_key: &str) -> Result<(), ClientError> {
+    let api_key = get_api_key()?;
+    let url = format!("{}{}", rest_base_url(), listen_key_path());
+    let client = proxy::http_client()?;
+    let mut headers = HeaderMap::new();
+    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key)?);
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+
+    let params = [("listenKey", listen_key)];
+
+    let resp = send_rate_limited(client.delete(&url).headers(headers).form(&params), 1).await?;
+    let status = resp.status(); // Extract status before consuming resp
+
+    if status.is_success() {
+        info!("Listen key closed.");
+        Ok(())
+    } else {
+        Err(error_from_response(resp).await)
+    }
+}
+
+async fn fetch_account() -> Result<AccountSnapshot, ClientError> {
+    let api_key = get_api_key()?;
+    let signer = request_signer()?;
+    let mut params = Vec::new();
+    let query = signing::sign_params(&signer, &mut params, recv_window_ms());
+    let url = format!("{}/fapi/v2/account?{}", rest_base_url(), query);
+    let client = proxy::http_client()?;
+    let mut headers = HeaderMap::new();
+    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key)?);
+
+    let resp = send_rate_limited(client.get(&url).headers(headers), 5).await?;
+    let status = resp.status(); // Extract status before consuming resp
+
+    if status.is_success() {
+        Ok(resp.json().await?)
+    } else {
+        Err(error_from_response(resp).await)
+    }
+}
+
+fn log_account_snapshot(snapshot: &AccountSnapshot) {
+    info!(
+        "Account Snapshot - Wallet Balance: {}, Available Balance: {}",
+        snapshot.total_wallet_balance, snapshot.available_balance
+    );
+    for asset in &snapshot.assets {
+        if asset.wallet_balance.is_zero() {
+            continue;
         }
+        info!(
+            "Snapshot Balance - Asset: {}, Wallet: {}, Cross Wallet: {}, Available: {}",
+            asset.asset, asset.wallet_balance, asset.cross_wallet_balance, asset.available_balance
+        );
+    }
+    for position in &snapshot.positions {
+        // The endpoint lists every symbol; only open positions are worth logging.
+        if position.position_amount.is_zero() {
+            continue;
+        }
+        info!(
+            "Snapshot Position - Symbol: {}, Side: {}, Amount: {}, Entry Price: {}, Unrealized PnL: {}",
+            position.symbol,
+            position.position_side,
+            position.position_amount,
+            position.entry_price,
+            position.unrealized_profit
+        );
     }
 }
 
@@ -217,24 +590,144 @@ async fn renew_listen_key(listen_key: &str) -> Result<(), Box<dyn Error>> {
 
 struct BinanceWebSocketClient {
     ws_url: String,
+    /// Rebuild the books from the account snapshot once connected, covering updates
+    /// missed before the stream was up.
+    resync_on_connect: bool,
+    context: StreamContext,
+}
+
+/// State shared by every connection of the user data stream, so it outlives
+/// reconnects.
+#[derive(Clone)]
+struct StreamContext {
+    /// Lock both books through `lock_books`, which always takes them in the same order.
+    positions: SharedPositionBook,
+    balances: SharedBalanceBook,
+    /// Binance time (ms) at which the books were last replaced from a snapshot;
+    /// `ACCOUNT_UPDATE`s from before then are already in it.
+    snapshot_time_ms: Arc<AtomicI64>,
+    /// Uppercase symbols whose order/trade updates are handled; `None` handles all.
+    symbol_filter: Option<Arc<HashSet<String>>>,
+    /// Print each order update as a JSON line on stdout (`--emit-json`).
+    emit_json: bool,
+}
+
+impl StreamContext {
+    fn lock_books(&self) -> (MutexGuard<'_, PositionBook>, MutexGuard<'_, BalanceBook>) {
+        let positions = self.positions.lock().unwrap();
+        let balances = self.balances.lock().unwrap();
+        (positions, balances)
+    }
+
+    /// Replaces both books with the snapshot's positions and balances.
+    /// `requested_at_ms` is the Binance time the snapshot was asked for.
+    fn resync(&self, snapshot: &AccountSnapshot, requested_at_ms: i64) {
+        let (mut positions, mut balances) = self.lock_books();
+        positions.replace_from(snapshot);
+        balances.replace_from(snapshot);
+        self.snapshot_time_ms
+            .store(requested_at_ms, Ordering::Relaxed);
+        info!(
+            "Resynced account state: {} open position(s), {} asset(s) with a balance.",
+            positions.dump().len(),
+            balances.dump().len()
+        );
+    }
+
+    /// Applies an `ACCOUNT_UPDATE` unless it predates the last snapshot. Updates
+    /// carry absolute amounts, so an older one would undo the snapshot.
+    fn apply_account_update(&self, update: AccountUpdate) {
+        if (update.event_time as i64) < self.snapshot_time_ms.load(Ordering::Relaxed) {
+            debug!(
+                "Skipping ACCOUNT_UPDATE from {}, older than the account snapshot.",
+                update.event_time
+            );
+            return;
+        }
+        let (mut book, mut balances) = self.lock_books();
+        for balance in update.account_info.balances {
+            debug!(
+                "Balance - Asset: {}, Wallet: {}, Cross Wallet: {}, Balance Change: {}",
+                balance.asset,
+                balance.wallet_balance,
+                balance.cross_wallet_balance,
+                balance.balance_change
+            );
+            balances.apply(balance);
+        }
+        for position in update.account_info.positions {
+            info!(
+                "Position - Symbol: {}, Amount: {}, Entry Price: {}, Unrealized Profit: {}",
+                position.symbol,
+                position.position_amount,
+                position.entry_price,
+                position.unrealized_profit
+            );
+            book.apply(position);
+        }
+    }
+
+    fn wants_symbol(&self, symbol: &str) -> bool {
+        self.symbol_filter
+            .as_ref()
+            .is_none_or(|symbols| symbols.contains(symbol))
+    }
 }
 
 impl BinanceWebSocketClient {
-    fn new(listen_key: String) -> Self {
-        let ws_url = format!("{}/{}", TESTNET_WS_BASE_URL, listen_key);
-        Self { ws_url }
+    fn new(listen_key: String, resync_on_connect: bool, context: StreamContext) -> Self {
+        let ws_url = format!("{}/ws/{}", ws_root_url(), listen_key);
+        Self {
+            ws_url,
+            resync_on_connect,
+            context,
+        }
     }
 
-    async fn connect_and_listen(&self) -> Result<(), Box<dyn Error>> {
+    async fn connect_and_listen(&self) -> Result<StreamExit, ClientError> {
         let url = Url::parse(&self.ws_url)?;
-        let (mut ws_stream, _) = tokio_tungstenite::connect_async(url).await?;
+        let (mut ws_stream, _) = proxy::connect_ws(url).await?;
         info!("WebSocket handshake successful.");
 
+        // ACCOUNT_UPDATE diffs sent before this connection are gone; start again from
+        // a full snapshot. Fetched after connecting, so nothing is missed, but updates
+        // queued meanwhile may be older than the snapshot; those are skipped.
+        if self.resync_on_connect {
+            let requested_at_ms = signing::server_timestamp_ms();
+            match fetch_account().await {
+                Ok(snapshot) => {
+                    log_account_snapshot(&snapshot);
+                    self.context.resync(&snapshot, requested_at_ms);
+                }
+                Err(e) => warn!(
+                    "Failed to resync account state; positions and balances may be stale until they next change: {}",
+                    e
+                ),
+            }
+        }
+
         while let Some(message) = ws_stream.next().await {
             match message {
                 Ok(Message::Text(text)) => {
-                    self.handle_message(&text).await?;
+                    if !self.handle_message(&text).await? {
+                        let _ = ws_stream.close(None).await;
+                        return Ok(StreamExit::ListenKeyExpired);
+                    }
                 }
+                // Binance sends text, but a binary frame carrying JSON is handled the same way.
+                Ok(Message::Binary(payload)) => match String::from_utf8(payload) {
+                    Ok(text) => {
+                        if !self.handle_message(&text).await? {
+                            let _ = ws_stream.close(None).await;
+                            return Ok(StreamExit::ListenKeyExpired);
+                        }
+                    }
+                    Err(e) => debug!(
+                        "Ignoring {}-byte binary frame that is not UTF-8: {}",
+                        e.as_bytes().len(),
+                        e.utf8_error()
+                    ),
+                },
                 Ok(Message::Close(_)) => {
                     info!("Received close frame from server.");
                     break;
@@ -256,30 +749,74 @@ impl BinanceWebSocketClient {
             }
         }
 
-        Ok(())
+        Ok(StreamExit::Disconnected)
     }
 
-    async fn handle_message(&self, message: &str) -> Result<(), Box<dyn Error>> {
-        // Deserialize the message into BinanceEvent enum
-        let event: BinanceEvent = match serde_json::from_str(message) {
-            Ok(ev) => ev,
+    /// Returns `false` when the stream must be torn down (the listen key expired).
+    async fn handle_message(&self, message: &str) -> Result<bool, ClientError> {
+        // Deserialize the message into BinanceEvent enum, keeping unknown event types
+        let event = match serde_json::from_str(message) {
+            Ok(UserDataMessage::Known(ev)) => ev,
+            Ok(UserDataMessage::Unknown { event_type, raw }) => {
+                if KNOWN_EVENT_TYPES.contains(&event_type.as_str()) {
+                    if let Err(e) = serde_json::from_str::<BinanceEvent>(message) {
+                        warn!(
+                            "Failed to deserialize {} event: {}, error: {}",
+                            event_type, message, e
+                        );
+                    }
+                } else {
+                    debug!("Unknown event type {}: {}", event_type, raw);
+                }
+                return Ok(true);
+            }
             Err(e) => {
                 warn!("Failed to deserialize message: {}, error: {}", message, e);
-                return Ok(());
+                return Ok(true);
             }
         };
 
         match event {
             BinanceEvent::OrderTradeUpdate(update) => {
-                let order_id = update.order_detail.order_id;
-                let status = update.order_detail.order_status;
-                info!("Order Update - ID: {}, Status: {}", order_id, status);
-                if ["FILLED", "CANCELED", "REJECTED", "EXPIRED"].contains(&status.as_str()) {
-                    info!("✅ Order {} has been {}.", order_id, status.to_lowercase());
-                    // Optionally, close the WebSocket connection here if desired
+                if !self.context.wants_symbol(&update.order_detail.symbol) {
+                    debug!(
+                        "Skipping order update for filtered symbol {}",
+                        update.order_detail.symbol
+                    );
+                    return Ok(true);
+                }
+                let order = update.order_detail;
+                if self.context.emit_json {
+                    emit_order_update(&OrderUpdateRecord::from_futures(&order, update.event_time));
                 }
+                let order_id = order.order_id;
+                let status = order.order_status;
+                info!(
+                    "Order Update - Symbol: {}, ID: {}, Status: {}, {} {}, Filled: {}/{}, Avg Price: {}, Last Price: {}",
+                    order.symbol,
+                    order_id,
+                    status,
+                    order.side,
+                    order.order_type,
+                    order.filled_quantity,
+                    order.original_quantity,
+                    order.average_price,
+                    order.last_filled_price
+                );
+                if let (Some(commission), Some(asset)) = (order.commission, &order.commission_asset)
+                {
+                    info!(
+                        "Order {} - Commission: {} {}, Realized Profit: {}",
+                        order_id, commission, asset, order.realized_profit
+                    );
+                }
+                log_if_final(order_id, &status);
             }
             BinanceEvent::TradeLite(trade) => {
+                if !self.context.wants_symbol(&trade.symbol) {
+                    debug!("Skipping trade lite for filtered symbol {}", trade.symbol);
+                    return Ok(true);
+                }
                 info!(
                     "Trade Lite - Trade ID: {}, Symbol: {}, Quantity: {}, Price: {}, Maker: {}",
                     trade.trade_id, trade.symbol, trade.quantity, trade.price, trade.is_maker
@@ -291,29 +828,184 @@ impl BinanceWebSocketClient {
                     "Account Update - Event Type: {}",
                     account_update.account_info.event_type
                 );
-                for balance in account_update.account_info.balances {
+                self.context.apply_account_update(account_update);
+                // Add additional processing logic as needed
+            }
+            BinanceEvent::MarginCall(margin_call) => {
+                warn!(
+                    "Margin Call - Cross Wallet Balance: {}",
+                    margin_call
+                        .cross_wallet_balance
+                        .map(|balance| balance.to_string())
+                        .unwrap_or_else(|| "n/a".to_string())
+                );
+                for position in margin_call.positions {
+                    warn!(
+                        "Margin Call Position - Symbol: {}, Side: {}, Amount: {}, Mark Price: {}, Maintenance Margin: {}",
+                        position.symbol,
+                        position.position_side,
+                        position.position_amount,
+                        position.mark_price,
+                        position.maintenance_margin_required
+                    );
+                }
+            }
+            BinanceEvent::AccountConfigUpdate(config_update) => {
+                if let Some(pair) = config_update.trade_pair_config {
                     info!(
-                        "Balance - Asset: {}, Available: {}, Cross Wallet: {}, Balance Change: {}",
-                        balance.asset,
-                        balance.available_balance,
-                        balance.cross_wallet_balance,
-                        balance.balance_change
+                        "Account Config Update - Symbol: {}, Leverage: {}x",
+                        pair.symbol, pair.leverage
                     );
                 }
-                for position in account_update.account_info.positions {
+                if let Some(account) = config_update.account_info_config {
                     info!(
-                        "Position - Symbol: {}, Amount: {}, Entry Price: {}, Unrealized Profit: {}",
-                        position.symbol,
-                        position.position_amount,
-                        position.entry_price,
-                        position.unrealized_profit
+                        "Account Config Update - Multi-Assets Mode: {}",
+                        account.multi_assets_mode
                     );
                 }
-                // Add additional processing logic as needed
+            }
+            BinanceEvent::ListenKeyExpired(expired) => {
+                warn!(
+                    "Listen key expired at {}; reconnecting with a new key.",
+                    expired.event_time
+                );
+                return Ok(false);
+            }
+            BinanceEvent::ExecutionReport(report) => {
+                if !self.context.wants_symbol(&report.symbol) {
+                    debug!(
+                        "Skipping execution report for filtered symbol {}",
+                        report.symbol
+                    );
+                    return Ok(true);
+                }
+                if self.context.emit_json {
+                    emit_order_update(&OrderUpdateRecord::from_spot(&report));
+                }
+                info!(
+                    "Order Update - Symbol: {}, ID: {}, Status: {} ({}), {} {}, Filled: {}/{}, Avg Price: {}, Last Price: {}",
+                    report.symbol,
+                    report.order_id,
+                    report.order_status,
+                    report.execution_type,
+                    report.side,
+                    report.order_type,
+                    report.filled_quantity,
+                    report.original_quantity,
+                    report.average_price(),
+                    report.last_filled_price
+                );
+                if let (Some(commission), Some(asset)) =
+                    (report.commission, &report.commission_asset)
+                {
+                    info!(
+                        "Order {} - Commission: {} {}",
+                        report.order_id, commission, asset
+                    );
+                }
+                log_if_final(report.order_id, &report.order_status);
+            }
+            BinanceEvent::OutboundAccountPosition(position) => {
+                let mut balances = self.context.balances.lock().unwrap();
+                for balance in position.balances {
+                    debug!(
+                        "Balance - Asset: {}, Free: {}, Locked: {}",
+                        balance.asset, balance.free, balance.locked
+                    );
+                    balances.apply_spot(balance);
+                }
             } // Handle other event types if necessary
         }
 
-        Ok(())
+        Ok(true)
+    }
+}
+
+/// Keeps the user data stream alive, reconnecting with exponential backoff and
+/// refreshing the shared listen key before every reconnect. `key_replaced` is
+/// notified by the renewal task when it had to swap in a brand new key.
+async fn run_with_reconnect(
+    listen_key: Arc<Mutex<String>>,
+    key_replaced: Arc<Notify>,
+    context: StreamContext,
+) {
+    let initial_backoff = Duration::from_secs(RECONNECT_INITIAL_BACKOFF_SECS);
+    let max_backoff = Duration::from_secs(RECONNECT_MAX_BACKOFF_SECS);
+    let stable_threshold = Duration::from_secs(RECONNECT_STABLE_CONNECTION_SECS);
+    let mut backoff = initial_backoff;
+    // Spot balance events are absolute and spot has no positions, so only futures resyncs.
+    let resync_on_connect = market().is_futures();
+
+    loop {
+        let current_key = listen_key.lock().unwrap().clone();
+        let ws_client =
+            BinanceWebSocketClient::new(current_key.clone(), resync_on_connect, context.clone());
+        let connected_at = Instant::now();
+
+        let exit = tokio::select! {
+            result = ws_client.connect_and_listen() => match result {
+                Ok(exit) => exit,
+                Err(e) => {
+                    error!("WebSocket error: {}", e);
+                    StreamExit::Disconnected
+                }
+            },
+            _ = key_replaced.notified() => {
+                info!("Listen key was replaced; reconnecting with the new key.");
+                continue;
+            }
+        };
+
+        if exit == StreamExit::ListenKeyExpired {
+            match create_listen_key().await {
+                Ok(new_key) => {
+                    *listen_key.lock().unwrap() = new_key;
+                    backoff = initial_backoff;
+                    continue;
+                }
+                Err(e) => error!("Failed to recreate expired listen key: {}", e),
+            }
+        }
+
+        if connected_at.elapsed() > stable_threshold {
+            backoff = initial_backoff;
+        }
+
+        let delay = with_jitter(backoff);
+        warn!(
+            "User data stream disconnected; reconnecting in {:?}...",
+            delay
+        );
+        time::sleep(delay).await;
+        backoff = (backoff * 2).min(max_backoff);
+
+        // Renew right before reconnecting so the key's 60-minute clock restarts with the new connection.
+        let current_key = listen_key.lock().unwrap().clone();
+        match refresh_listen_key(&listen_key, &current_key).await {
+            Ok(_) => {}
+            Err(e) if e.is_rate_limited() => {
+                error!("Rate limited while refreshing listen key: {}", e);
+                backoff = max_backoff;
+            }
+            Err(e) => error!("Failed to refresh listen key: {}", e),
+        }
+    }
+}
+
+/// Renews `current_key`, or creates and stores a new key if renewal fails.
+/// Returns `true` when the shared key was replaced.
+async fn refresh_listen_key(
+    listen_key: &Mutex<String>,
+    current_key: &str,
+) -> Result<bool, ClientError> {
+    match renew_listen_key(current_key).await {
+        Ok(_) => Ok(false),
+        Err(e) => {
+            warn!("Failed to renew listen key ({}); creating a new one.", e);
+            let new_key = create_listen_key().await?;
+            *listen_key.lock().unwrap() = new_key;
+            Ok(true)
+        }
     }
 }
 
@@ -322,12 +1014,109 @@ impl BinanceWebSocketClient {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     dotenv().ok();
-    settings::init_logging();
+
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    let emit_json = take_flag(&mut args, "--emit-json");
+    let log_file = take_flag_value(&mut args, "--log-file");
+    // Keep stdout clean for the JSON feed by moving the terminal log to stderr.
+    let console: Box<dyn std::io::Write + Send> = if emit_json {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    settings::init_logging_to(console, log_file.as_ref().ok().and_then(Option::as_deref));
+    if let Err(e) = log_file {
+        error!("{}", e);
+        return Ok(());
+    }
+    let (proxy_flag, symbols_flag) = match take_stream_flags(&mut args) {
+        Ok(flags) => flags,
+        Err(e) => {
+            error!("{}", e);
+            return Ok(());
+        }
+    };
+    if let Err(e) = proxy::init(proxy_flag.as_deref()) {
+        error!("{}", e);
+        return Ok(());
+    }
+    if let Err(e) = tls::init(take_flag(&mut args, "--native-tls")) {
+        error!("{}", e);
+        return Ok(());
+    }
+    let market = match take_flag_value(&mut args, "--market") {
+        Ok(Some(value)) => match Market::parse(&value) {
+            Some(market) => market,
+            None => {
+                error!(
+                    "Invalid --market: {}; expected one of {}",
+                    value,
+                    MARKET_NAMES.join(", ")
+                );
+                return Ok(());
+            }
+        },
+        Ok(None) => DEFAULT_MARKET,
+        Err(e) => {
+            error!("{}", e);
+            return Ok(());
+        }
+    };
+    MARKET.get_or_init(|| market);
+    let listen_key_attempts = match take_flag_value(&mut args, "--listen-key-attempts") {
+        Ok(Some(value)) => match value.parse::<u32>() {
+            Ok(attempts) if attempts > 0 => attempts,
+            _ => {
+                error!("Invalid --listen-key-attempts: {}", value);
+                return Ok(());
+            }
+        },
+        Ok(None) => listen_key_max_attempts(),
+        Err(e) => {
+            error!("{}", e);
+            return Ok(());
+        }
+    };
+    info!("Market: {}", market.name());
+    let symbol_filter = match symbols_flag.map(|value| parse_symbol_filter(&value)) {
+        Some(Ok(symbols)) => Some(symbols),
+        Some(Err(e)) => {
+            error!("--symbols: {}", e);
+            return Ok(());
+        }
+        None => None,
+    };
+    if let Some(symbols) = &symbol_filter {
+        let mut symbols = symbols.iter().map(String::as_str).collect::<Vec<_>>();
+        symbols.sort_unstable();
+        info!("Order/trade updates filtered to: {}", symbols.join(", "));
+    }
+
+    if let Err(e) = sync_clock_offset().await {
+        warn!(
+            "Failed to sync server time; signing with local clock: {}",
+            e
+        );
+    }
+
+    if let Some(command) = args
+        .first()
+        .filter(|cmd| orders::COMMANDS.contains(&cmd.as_str()))
+    {
+        if !market.is_futures() {
+            error!("{} is only supported on the futures market.", command);
+            return Ok(());
+        }
+        if let Err(e) = orders::run_command(command, &args[1..]).await {
+            error!("{} failed: {}", command, e);
+        }
+        return Ok(());
+    }
 
     info!("Starting Binance WebSocket client...");
 
     // Step 1: Create a listen key
-    let listen_key = match create_listen_key().await {
+    let listen_key = match create_listen_key_with_retry(listen_key_attempts).await {
         Ok(key) => key,
         Err(e) => {
             error!("Failed to create listen key: {}", e);
@@ -335,28 +1124,65 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
-    // Step 2: Initialize WebSocket client
-    let ws_client = BinanceWebSocketClient::new(listen_key.clone());
+    // Step 2: Share the listen key so reconnects can swap in a fresh one
+    let listen_key = Arc::new(Mutex::new(listen_key));
 
-    // Step 3: Start a task to handle WebSocket connection
+    // Step 3: Start a task to handle the WebSocket connection, reconnecting on drops
+    let key_replaced = Arc::new(Notify::new());
+    let ws_listen_key = Arc::clone(&listen_key);
+    let ws_key_replaced = Arc::clone(&key_replaced);
+    // Seeded from the account snapshot once the first connection is up.
+    let positions = PositionBook::shared();
+    let balances = BalanceBook::shared();
+    let context = StreamContext {
+        positions: Arc::clone(&positions),
+        balances: Arc::clone(&balances),
+        snapshot_time_ms: Arc::new(AtomicI64::new(0)),
+        symbol_filter: symbol_filter.map(Arc::new),
+        emit_json,
+    };
     let ws_handle = tokio::spawn(async move {
-        if let Err(e) = ws_client.connect_and_listen().await {
-            error!("WebSocket error: {}", e);
-        }
+        run_with_reconnect(ws_listen_key, ws_key_replaced, context).await;
     });
 
-    // Step 4: Start a task to renew the listen key every 55 minutes
-    let listen_key_clone = listen_key.clone();
+    // Periodically dump the position book built from ACCOUNT_UPDATE events (futures only)
+    if market.is_futures() {
+        tokio::spawn(async move {
+            let period = Duration::from_secs(POSITION_BOOK_LOG_INTERVAL_SECS);
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                positions.lock().unwrap().log();
+            }
+        });
+    }
+
+    // Consolidated balance summary instead of per-event balance logs
+    if let Some(period) = balance_log_interval() {
+        tokio::spawn(async move {
+            let mut interval = time::interval_at(time::Instant::now() + period, period);
+            loop {
+                interval.tick().await;
+                balances.lock().unwrap().log_summary();
+            }
+        });
+    }
+
+    // Step 4: Start a task to renew the listen key periodically, replacing it if renewal fails
+    let renew_listen_key_ref = Arc::clone(&listen_key);
     let renew_handle = tokio::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(55 * 60));
+        let period = Duration::from_secs(LISTEN_KEY_RENEW_INTERVAL_SECS);
+        let mut interval = time::interval_at(time::Instant::now() + period, period);
         loop {
             interval.tick().await;
-            match renew_listen_key(&listen_key_clone).await {
-                Ok(_) => {}
-                Err(e) => {
-                    error!("Failed to renew listen key: {}", e);
-                    break;
-                }
+            let current_key = renew_listen_key_ref.lock().unwrap().clone();
+            match refresh_listen_key(&renew_listen_key_ref, &current_key).await {
+                Ok(true) => key_replaced.notify_one(),
+                Ok(false) => {}
+                Err(e) => error!(
+                    "Failed to replace listen key: {}; retrying next interval",
+                    e
+                ),
             }
         }
     });
@@ -379,6 +1205,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
         },
         _ = shutdown_handle => {
             info!("Shutdown signal received.");
+            let current_key = listen_key.lock().unwrap().clone();
+            let timeout = Duration::from_secs(LISTEN_KEY_CLOSE_TIMEOUT_SECS);
+            match time::timeout(timeout, close_listen_key(&current_key)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!("Failed to close listen key: {}", e),
+                Err(_) => warn!("Timed out closing listen key after {:?}.", timeout),
+            }
         },
     }
 
@@ -387,7 +1220,260 @@ async fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn get_api_key() -> Result<String, Box<dyn Error>> {
-    env::var("BINANCE_API_KEY")
-        .map_err(|_| "Missing BINANCE_API_KEY environment variable".into())
+/// Removes a boolean `flag` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let before = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != before
+}
+
+/// Removes `flag` and its value from `args`, so the rest can be dispatched as before.
+fn take_flag_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        }
+        Some(_) => Err(format!("Missing value for {}", flag)),
+        None => Ok(None),
+    }
+}
+
+/// Pulls `--proxy` and `--symbols` out of `args` before command dispatch.
+fn take_stream_flags(args: &mut Vec<String>) -> Result<(Option<String>, Option<String>), String> {
+    let proxy = take_flag_value(args, "--proxy")?;
+    let symbols = take_flag_value(args, "--symbols")?;
+    Ok((proxy, symbols))
+}
+
+/// `btcusdt,ethusdt` -> `{"BTCUSDT", "ETHUSDT"}`; event symbols are uppercase.
+/// Symbols other than ASCII letters and digits are rejected, since they could never match.
+fn parse_symbol_filter(value: &str) -> Result<HashSet<String>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|symbol| !symbol.is_empty())
+        .map(|symbol| {
+            if symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+                Ok(symbol.to_uppercase())
+            } else {
+                Err(format!(
+                    "Invalid symbol {:?}: only letters and digits are allowed, e.g. btcusdt",
+                    symbol
+                ))
+            }
+        })
+        .collect()
+}
+
+fn emit_order_update(record: &OrderUpdateRecord) {
+    match serde_json::to_string(record) {
+        Ok(line) => println!("{}", line),
+        Err(e) => warn!(
+            "Failed to serialize order update {}: {}",
+            record.order_id, e
+        ),
+    }
+}
+
+fn log_if_final(order_id: u64, status: &str) {
+    if ["FILLED", "CANCELED", "REJECTED", "EXPIRED"].contains(&status) {
+        info!("✅ Order {} has been {}.", order_id, status.to_lowercase());
+    }
+}
+
+fn get_api_key() -> Result<String, ClientError> {
+    env::var("BINANCE_API_KEY").map_err(|_| ClientError::MissingApiKey)
+}
+
+fn get_api_secret() -> Result<String, ClientError> {
+    env::var("BINANCE_API_SECRET").map_err(|_| ClientError::MissingApiSecret)
+}
+
+/// Ed25519 when `BINANCE_PRIVATE_KEY_PEM` points at a key, HMAC-SHA256 otherwise.
+fn request_signer() -> Result<signing::RequestSigner, ClientError> {
+    match env::var(signing::PRIVATE_KEY_PEM_ENV) {
+        Ok(path) if !path.trim().is_empty() => {
+            signing::RequestSigner::ed25519_from_pem_file(path.trim())
+        }
+        _ => Ok(signing::RequestSigner::Hmac(get_api_secret()?)),
+    }
+}
+
+fn recv_window_ms() -> Option<u64> {
+    let value = env::var(RECV_WINDOW_ENV).ok()?;
+    match value.trim().parse::<u64>() {
+        Ok(recv_window_ms) => Some(recv_window_ms),
+        Err(_) => {
+            warn!("Ignoring invalid {}={}", RECV_WINDOW_ENV, value);
+            None
+        }
+    }
+}
+
+fn balance_log_interval() -> Option<Duration> {
+    let secs = match env::var(BALANCE_LOG_INTERVAL_ENV) {
+        Ok(value) => match value.trim().parse::<u64>() {
+            Ok(secs) => secs,
+            Err(_) => {
+                warn!("Ignoring invalid {}={}", BALANCE_LOG_INTERVAL_ENV, value);
+                DEFAULT_BALANCE_LOG_INTERVAL_SECS
+            }
+        },
+        Err(_) => DEFAULT_BALANCE_LOG_INTERVAL_SECS,
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+fn listen_key_max_attempts() -> u32 {
+    match env::var(LISTEN_KEY_MAX_ATTEMPTS_ENV) {
+        Ok(value) => match value.trim().parse::<u32>() {
+            Ok(attempts) if attempts > 0 => attempts,
+            _ => {
+                warn!(
+                    "Ignoring invalid {}={} (expected at least 1)",
+                    LISTEN_KEY_MAX_ATTEMPTS_ENV, value
+                );
+                DEFAULT_LISTEN_KEY_MAX_ATTEMPTS
+            }
+        },
+        Err(_) => DEFAULT_LISTEN_KEY_MAX_ATTEMPTS,
+    }
+}
+
+/// The venue picked with `--market`, fixed for the life of the process.
+fn market() -> Market {
+    *MARKET.get().unwrap_or(&DEFAULT_MARKET)
+}
+
+fn rest_base_url() -> String {
+    endpoints::resolve_url(None, REST_URL_ENV, market().rest_base())
+}
+
+fn ws_root_url() -> String {
+    endpoints::resolve_url(None, WS_URL_ENV, market().ws_base())
+}
+
+fn server_time_path() -> &'static str {
+    if market().is_futures() {
+        "/fapi/v1/time"
+    } else {
+        "/api/v3/time"
+    }
+}
+
+/// Listen key endpoint of the user data stream: `POST` creates, `PUT` renews.
+fn listen_key_path() -> &'static str {
+    if market().is_futures() {
+        "/fapi/v1/listenKey"
+    } else {
+        "/api/v3/userDataStream"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MARGIN_CALL payload from Binance's user data stream docs, with a second position.
+    const MARGIN_CALL: &str = r#"{"e":"MARGIN_CALL","E":1587727187525,"cw":"3.16812045","p":[
+        {"s":"ETHUSDT","ps":"LONG","pa":"1.327","mt":"CROSSED","iw":"0","mp":"187.17127","up":"-1.166074","mm":"1.614445"},
+        {"s":"BTCUSDT","ps":"SHORT","pa":"-0.010","mt":"CROSSED","iw":"0","mp":"64250.10","up":"-3.20","mm":"2.570004"}]}"#;
+
+    #[test]
+    fn margin_call_parses_positions_and_cross_wallet() {
+        let Ok(UserDataMessage::Known(BinanceEvent::MarginCall(margin_call))) =
+            serde_json::from_str(MARGIN_CALL)
+        else {
+            panic!("not a margin call");
+        };
+        assert_eq!(
+            margin_call.cross_wallet_balance,
+            Some("3.16812045".parse::<Decimal>().unwrap())
+        );
+        assert_eq!(margin_call.positions.len(), 2);
+
+        let eth = &margin_call.positions[0];
+        assert_eq!(eth.symbol, "ETHUSDT");
+        assert_eq!(eth.position_side, "LONG");
+        assert_eq!(eth.position_amount.to_string(), "1.327");
+        assert_eq!(eth.mark_price.to_string(), "187.17127");
+        assert_eq!(eth.maintenance_margin_required.to_string(), "1.614445");
+
+        let btc = &margin_call.positions[1];
+        assert_eq!(btc.symbol, "BTCUSDT");
+        assert_eq!(btc.position_side, "SHORT");
+        assert_eq!(btc.position_amount.to_string(), "-0.010");
+    }
+
+    fn context() -> StreamContext {
+        StreamContext {
+            positions: PositionBook::shared(),
+            balances: BalanceBook::shared(),
+            snapshot_time_ms: Arc::new(AtomicI64::new(0)),
+            symbol_filter: None,
+            emit_json: false,
+        }
+    }
+
+    /// A USDT balance of `usdt` and an ETHUSDT position of `eth` at event time `event_time`.
+    fn account_update(event_time: u64, usdt: &str, eth: &str) -> AccountUpdate {
+        let update = format!(
+            r#"{{"e":"ACCOUNT_UPDATE","E":{event_time},"T":{event_time},"a":{{"m":"ORDER",
+            "B":[{{"a":"USDT","wb":"{usdt}","cw":"{usdt}","bc":"0"}}],
+            "P":[{{"s":"ETHUSDT","pa":"{eth}","ep":"3000","cr":"0","up":"5","mt":"cross","iw":"0","ps":"BOTH","ma":"USDT","bep":"3001"}}]}}}}"#
+        );
+        let Ok(UserDataMessage::Known(BinanceEvent::AccountUpdate(update))) =
+            serde_json::from_str(&update)
+        else {
+            panic!("not an account update");
+        };
+        update
+    }
+
+    /// The ETH position was closed and USDT paid out while the stream was down.
+    fn snapshot() -> AccountSnapshot {
+        serde_json::from_str(
+            r#"{"totalWalletBalance":"140","availableBalance":"140",
+                "assets":[{"asset":"USDT","walletBalance":"140","crossWalletBalance":"140","availableBalance":"140"}],
+                "positions":[{"symbol":"ETHUSDT","positionAmt":"0","entryPrice":"0","unrealizedProfit":"0","positionSide":"BOTH"}]}"#,
+        )
+        .unwrap()
+    }
+
+    fn usdt_balance(context: &StreamContext) -> String {
+        let balances = context.balances.lock().unwrap();
+        let [("USDT", usdt)] = balances.dump()[..] else {
+            panic!("expected only USDT");
+        };
+        usdt.wallet_balance.to_string()
+    }
+
+    #[test]
+    fn resync_replaces_both_books() {
+        let context = context();
+        context.apply_account_update(account_update(1, "100", "2"));
+
+        context.resync(&snapshot(), 1_000);
+
+        assert!(context.positions.lock().unwrap().dump().is_empty());
+        assert_eq!(usdt_balance(&context), "140");
+    }
+
+    /// Updates queued while the snapshot was fetched can predate it; applying
+    /// them would bring back the closed position.
+    #[test]
+    fn updates_older_than_the_snapshot_are_skipped() {
+        let context = context();
+        context.resync(&snapshot(), 1_000);
+
+        context.apply_account_update(account_update(999, "100", "2"));
+        assert!(context.positions.lock().unwrap().dump().is_empty());
+        assert_eq!(usdt_balance(&context), "140");
+
+        context.apply_account_update(account_update(1_000, "150", "1"));
+        assert_eq!(context.positions.lock().unwrap().dump().len(), 1);
+        assert_eq!(usdt_balance(&context), "150");
+    }
 }
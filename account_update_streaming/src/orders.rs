@@ -0,0 +1,245 @@
+use crate::error::ClientError;
+use crate::{
+    error_from_response, get_api_key, proxy, recv_window_ms, request_signer, rest_base_url,
+    send_rate_limited, signing,
+};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::Method;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const ORDER_SIDES: [&str; 2] = ["BUY", "SELL"];
+const ORDER_TYPES: [&str; 2] = ["MARKET", "LIMIT"];
+const TIME_IN_FORCE: [&str; 4] = ["GTC", "IOC", "FOK", "GTX"];
+
+/// First CLI argument values that run a one-shot order command instead of the stream.
+pub const COMMANDS: [&str; 3] = ["order", "cancel", "cancel-all"];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+    pub order_id: u64,
+    pub symbol: String,
+    pub status: String,
+    pub executed_qty: Decimal,
+}
+
+/// `DELETE /fapi/v1/allOpenOrders` answers with a status-style `{code, msg}` body.
+#[derive(Debug, Deserialize)]
+pub struct CancelAllResponse {
+    pub code: i32,
+    pub msg: String,
+}
+
+/// Limit-only parameters; both are required for `LIMIT` and rejected for `MARKET`.
+#[derive(Debug, Clone)]
+pub struct LimitParams {
+    pub price: Decimal,
+    pub time_in_force: String,
+}
+
+/// Places a futures order via signed `POST /fapi/v1/order`.
+pub async fn place_order(
+    symbol: &str,
+    side: &str,
+    order_type: &str,
+    quantity: Decimal,
+    limit: Option<LimitParams>,
+) -> Result<OrderResponse, ClientError> {
+    let side = side.to_uppercase();
+    let order_type = order_type.to_uppercase();
+
+    if !ORDER_SIDES.contains(&side.as_str()) {
+        return Err(ClientError::InvalidOrder(format!(
+            "side must be BUY or SELL, got {}",
+            side
+        )));
+    }
+    if !ORDER_TYPES.contains(&order_type.as_str()) {
+        return Err(ClientError::InvalidOrder(format!(
+            "type must be MARKET or LIMIT, got {}",
+            order_type
+        )));
+    }
+    if quantity <= Decimal::ZERO {
+        return Err(ClientError::InvalidOrder(format!(
+            "quantity must be positive, got {}",
+            quantity
+        )));
+    }
+
+    let mut params = vec![
+        ("symbol".to_string(), symbol.to_uppercase()),
+        ("side".to_string(), side),
+        ("type".to_string(), order_type.clone()),
+        ("quantity".to_string(), quantity.to_string()),
+    ];
+
+    match (order_type.as_str(), limit) {
+        ("LIMIT", Some(limit)) => {
+            let time_in_force = limit.time_in_force.to_uppercase();
+            if !TIME_IN_FORCE.contains(&time_in_force.as_str()) {
+                return Err(ClientError::InvalidOrder(format!(
+                    "timeInForce must be one of {:?}, got {}",
+                    TIME_IN_FORCE, time_in_force
+                )));
+            }
+            if limit.price <= Decimal::ZERO {
+                return Err(ClientError::InvalidOrder(format!(
+                    "price must be positive, got {}",
+                    limit.price
+                )));
+            }
+            params.push(("price".to_string(), limit.price.to_string()));
+            params.push(("timeInForce".to_string(), time_in_force));
+        }
+        ("LIMIT", None) => {
+            return Err(ClientError::InvalidOrder(
+                "LIMIT orders require a price and timeInForce".to_string(),
+            ));
+        }
+        (_, Some(_)) => {
+            return Err(ClientError::InvalidOrder(
+                "MARKET orders do not take a price or timeInForce".to_string(),
+            ));
+        }
+        (_, None) => {}
+    }
+
+    let order: OrderResponse = send_signed(Method::POST, "/fapi/v1/order", 1, params).await?;
+    info!(
+        "Order placed - ID: {}, Symbol: {}, Status: {}, Executed Qty: {}",
+        order.order_id, order.symbol, order.status, order.executed_qty
+    );
+    Ok(order)
+}
+
+/// Cancels one order via signed `DELETE /fapi/v1/order`. Returns
+/// `ClientError::UnknownOrder` if it already filled or was cancelled.
+pub async fn cancel_order(symbol: &str, order_id: u64) -> Result<OrderResponse, ClientError> {
+    let params = vec![
+        ("symbol".to_string(), symbol.to_uppercase()),
+        ("orderId".to_string(), order_id.to_string()),
+    ];
+
+    let order: OrderResponse = send_signed(Method::DELETE, "/fapi/v1/order", 1, params).await?;
+    info!(
+        "Order cancelled - ID: {}, Symbol: {}, Status: {}, Executed Qty: {}",
+        order.order_id, order.symbol, order.status, order.executed_qty
+    );
+    Ok(order)
+}
+
+/// Cancels every open order on `symbol` via signed `DELETE /fapi/v1/allOpenOrders`.
+pub async fn cancel_all_open_orders(symbol: &str) -> Result<CancelAllResponse, ClientError> {
+    let params = vec![("symbol".to_string(), symbol.to_uppercase())];
+
+    let response: CancelAllResponse =
+        send_signed(Method::DELETE, "/fapi/v1/allOpenOrders", 1, params).await?;
+    info!(
+        "Cancel all open orders - Symbol: {}, Code: {}, Message: {}",
+        symbol.to_uppercase(),
+        response.code,
+        response.msg
+    );
+    Ok(response)
+}
+
+async fn send_signed<T: DeserializeOwned>(
+    method: Method,
+    path: &str,
+    weight: u32,
+    mut params: Vec<(String, String)>,
+) -> Result<T, ClientError> {
+    let api_key = get_api_key()?;
+    let signer = request_signer()?;
+    let query = signing::sign_params(&signer, &mut params, recv_window_ms());
+    let url = format!("{}{}?{}", rest_base_url(), path, query);
+    let client = proxy::http_client()?;
+    let mut headers = HeaderMap::new();
+    headers.insert("X-MBX-APIKEY", HeaderValue::from_str(&api_key)?);
+
+    let resp = send_rate_limited(client.request(method, &url).headers(headers), weight).await?;
+    if !resp.status().is_success() {
+        return Err(error_from_response(resp).await);
+    }
+
+    Ok(resp.json().await?)
+}
+
+/// Runs one of `COMMANDS` from the command line instead of starting the stream:
+/// `order <symbol> <side> <type> <quantity> [<price> <timeInForce>]`,
+/// `cancel <symbol> <orderId>`, or `cancel-all <symbol>`.
+pub async fn run_command(command: &str, args: &[String]) -> Result<(), ClientError> {
+    match command {
+        "order" => run_order_command(args).await,
+        "cancel" => {
+            let [symbol, order_id] = args else {
+                return Err(ClientError::InvalidOrder(
+                    "usage: cancel <symbol> <orderId>".to_string(),
+                ));
+            };
+            let order_id = order_id
+                .parse::<u64>()
+                .map_err(|_| ClientError::InvalidOrder(format!("not an order id: {}", order_id)))?;
+
+            match cancel_order(symbol, order_id).await {
+                Ok(_) => Ok(()),
+                Err(ClientError::UnknownOrder(msg)) => {
+                    info!("Order {} was already closed: {}", order_id, msg);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        }
+        "cancel-all" => {
+            let [symbol] = args else {
+                return Err(ClientError::InvalidOrder(
+                    "usage: cancel-all <symbol>".to_string(),
+                ));
+            };
+            cancel_all_open_orders(symbol).await?;
+            Ok(())
+        }
+        other => Err(ClientError::InvalidOrder(format!(
+            "unknown command: {}",
+            other
+        ))),
+    }
+}
+
+async fn run_order_command(args: &[String]) -> Result<(), ClientError> {
+    let usage = || {
+        ClientError::InvalidOrder(
+            "usage: order <symbol> <BUY|SELL> <MARKET|LIMIT> <quantity> [<price> <timeInForce>]"
+                .to_string(),
+        )
+    };
+    let parse_decimal = |value: &str| {
+        value
+            .parse::<Decimal>()
+            .map_err(|_| ClientError::InvalidOrder(format!("not a number: {}", value)))
+    };
+
+    let (symbol, side, order_type, quantity, limit) = match args {
+        [symbol, side, order_type, quantity] => (symbol, side, order_type, quantity, None),
+        [symbol, side, order_type, quantity, price, time_in_force] => (
+            symbol,
+            side,
+            order_type,
+            quantity,
+            Some(LimitParams {
+                price: parse_decimal(price)?,
+                time_in_force: time_in_force.clone(),
+            }),
+        ),
+        _ => return Err(usage()),
+    };
+
+    place_order(symbol, side, order_type, parse_decimal(quantity)?, limit).await?;
+    Ok(())
+}
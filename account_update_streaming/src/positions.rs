@@ -0,0 +1,207 @@
+use crate::snapshot::{AccountSnapshot, AccountSnapshotPosition};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+pub type SharedPositionBook = Arc<Mutex<PositionBook>>;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Position {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "pa")]
+    pub position_amount: Decimal,
+    #[serde(rename = "ep")]
+    pub entry_price: Decimal,
+    #[serde(rename = "cr")]
+    pub accumulated_realized: Decimal,
+    #[serde(rename = "up")]
+    pub unrealized_profit: Decimal,
+    #[serde(rename = "mt")]
+    pub margin_type: String,
+    #[serde(rename = "iw")]
+    pub isolated_wallet: Decimal,
+    #[serde(rename = "ps")]
+    pub position_side: String,
+    #[serde(rename = "ma")]
+    pub margin_asset: String,
+    #[serde(rename = "bep")]
+    pub break_even_price: Decimal,
+}
+
+impl Position {
+    /// The snapshot doesn't carry `cr` or `ma`; they stay zero/empty until the next
+    /// `ACCOUNT_UPDATE` for the position.
+    fn from_snapshot(position: &AccountSnapshotPosition) -> Self {
+        Self {
+            symbol: position.symbol.clone(),
+            position_amount: position.position_amount,
+            entry_price: position.entry_price,
+            accumulated_realized: Decimal::ZERO,
+            unrealized_profit: position.unrealized_profit,
+            margin_type: if position.isolated {
+                "isolated".to_string()
+            } else {
+                "cross".to_string()
+            },
+            isolated_wallet: position.isolated_wallet,
+            position_side: position.position_side.clone(),
+            margin_asset: String::new(),
+            break_even_price: position.break_even_price,
+        }
+    }
+}
+
+/// Open positions keyed by `(symbol, position side)`, kept current from the
+/// `ACCOUNT_UPDATE` positions. Side is `BOTH` in one-way mode, `LONG`/`SHORT`
+/// in hedge mode.
+#[derive(Debug, Default)]
+pub struct PositionBook {
+    positions: HashMap<(String, String), Position>,
+}
+
+impl PositionBook {
+    pub fn shared() -> SharedPositionBook {
+        Arc::new(Mutex::new(Self::default()))
+    }
+
+    /// Replaces the entry for the position's symbol and side; a zero amount means
+    /// the position was closed and drops the entry.
+    pub fn apply(&mut self, position: Position) {
+        let key = (position.symbol.clone(), position.position_side.clone());
+        if position.position_amount.is_zero() {
+            if self.positions.remove(&key).is_some() {
+                info!("Position closed - Symbol: {}, Side: {}", key.0, key.1);
+            }
+        } else {
+            self.positions.insert(key, position);
+        }
+    }
+
+    /// Rebuilds the book from a REST snapshot, dropping positions the stream never
+    /// reported closed (e.g. closed while disconnected).
+    pub fn replace_from(&mut self, snapshot: &AccountSnapshot) {
+        let previous = std::mem::take(&mut self.positions);
+        for position in &snapshot.positions {
+            if !position.position_amount.is_zero() {
+                self.apply(Position::from_snapshot(position));
+            }
+        }
+        for (symbol, side) in previous.into_keys() {
+            if !self.positions.contains_key(&(symbol.clone(), side.clone())) {
+                info!(
+                    "Position closed while disconnected - Symbol: {}, Side: {}",
+                    symbol, side
+                );
+            }
+        }
+    }
+
+    /// Net signed amount across all sides of `symbol` (short amounts are negative).
+    pub fn net_position(&self, symbol: &str) -> Decimal {
+        self.positions
+            .values()
+            .filter(|position| position.symbol == symbol)
+            .map(|position| position.position_amount)
+            .sum()
+    }
+
+    pub fn total_unrealized_pnl(&self) -> Decimal {
+        self.positions
+            .values()
+            .map(|position| position.unrealized_profit)
+            .sum()
+    }
+
+    /// Current open positions ordered by symbol, then side.
+    pub fn dump(&self) -> Vec<&Position> {
+        let mut positions = self.positions.values().collect::<Vec<_>>();
+        positions.sort_by(|a, b| {
+            a.symbol
+                .cmp(&b.symbol)
+                .then_with(|| a.position_side.cmp(&b.position_side))
+        });
+        positions
+    }
+
+    pub fn log(&self) {
+        if self.positions.is_empty() {
+            info!("Position book: no open positions.");
+            return;
+        }
+
+        info!(
+            "Position book: {} open position(s), total unrealized PnL: {}",
+            self.positions.len(),
+            self.total_unrealized_pnl()
+        );
+        for position in self.dump() {
+            info!(
+                "Book Position - Symbol: {}, Side: {}, Amount: {}, Entry Price: {}, Break-even: {}, Unrealized PnL: {}, Net {}: {}",
+                position.symbol,
+                position.position_side,
+                position.position_amount,
+                position.entry_price,
+                position.break_even_price,
+                position.unrealized_profit,
+                position.symbol,
+                self.net_position(&position.symbol)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(positions: &str) -> AccountSnapshot {
+        serde_json::from_str(&format!(
+            r#"{{"totalWalletBalance":"100","availableBalance":"90","assets":[],"positions":[{}]}}"#,
+            positions
+        ))
+        .unwrap()
+    }
+
+    fn stream_position(symbol: &str, side: &str, amount: &str) -> Position {
+        serde_json::from_str(&format!(
+            r#"{{"s":"{}","pa":"{}","ep":"100","cr":"0","up":"1","mt":"cross","iw":"0","ps":"{}","ma":"USDT","bep":"100"}}"#,
+            symbol, amount, side
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn replace_from_drops_positions_closed_during_a_gap() {
+        let mut book = PositionBook::default();
+        book.apply(stream_position("BTCUSDT", "LONG", "0.5"));
+        book.apply(stream_position("ETHUSDT", "BOTH", "2"));
+
+        book.replace_from(&snapshot(
+            r#"{"symbol":"BTCUSDT","positionAmt":"0.750","entryPrice":"64000.0","breakEvenPrice":"64010.5","unrealizedProfit":"12.30","positionSide":"LONG","isolated":true,"isolatedWallet":"410.2"},
+               {"symbol":"ETHUSDT","positionAmt":"0","entryPrice":"0.0","unrealizedProfit":"0.00","positionSide":"BOTH","isolated":false},
+               {"symbol":"SOLUSDT","positionAmt":"-3","entryPrice":"150.0","unrealizedProfit":"-1.5","positionSide":"SHORT","isolated":false}"#,
+        ));
+
+        let positions = book.dump();
+        assert_eq!(positions.len(), 2);
+        let btc = positions[0];
+        assert_eq!(
+            (btc.symbol.as_str(), btc.position_side.as_str()),
+            ("BTCUSDT", "LONG")
+        );
+        assert_eq!(btc.position_amount.to_string(), "0.750");
+        assert_eq!(btc.break_even_price.to_string(), "64010.5");
+        assert_eq!(btc.margin_type, "isolated");
+        assert_eq!(btc.isolated_wallet.to_string(), "410.2");
+        assert_eq!(positions[1].symbol, "SOLUSDT");
+        assert_eq!(book.net_position("ETHUSDT"), Decimal::ZERO);
+        assert_eq!(book.net_position("SOLUSDT").to_string(), "-3");
+        assert_eq!(book.total_unrealized_pnl().to_string(), "10.80");
+    }
+}
@@ -0,0 +1,149 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+// USD-M futures defaults: 2400 request weight and 1200 orders per minute per IP/account.
+pub const REQUESTS_PER_MINUTE: u32 = 1200;
+pub const WEIGHT_PER_MINUTE: u32 = 2400;
+const USED_WEIGHT_HEADER: &str = "x-mbx-used-weight-1m";
+
+pub type SharedRateLimiter = Arc<Mutex<RateLimiter>>;
+
+/// Token bucket over both request count and request weight. Each bucket holds up
+/// to one minute's allowance and refills continuously.
+pub struct RateLimiter {
+    requests_per_minute: f64,
+    weight_per_minute: f64,
+    request_tokens: f64,
+    weight_tokens: f64,
+    last_refill: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: u32, weight_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute: requests_per_minute as f64,
+            weight_per_minute: weight_per_minute as f64,
+            request_tokens: requests_per_minute as f64,
+            weight_tokens: weight_per_minute as f64,
+            last_refill: Instant::now(),
+            blocked_until: None,
+        }
+    }
+
+    /// Takes one request of `weight` if both buckets allow it and returns
+    /// `Duration::ZERO`; otherwise takes nothing and returns how long to wait.
+    pub fn try_acquire(&mut self, weight: u32) -> Duration {
+        let now = Instant::now();
+        if let Some(blocked_until) = self.blocked_until {
+            if blocked_until > now {
+                return blocked_until - now;
+            }
+            self.blocked_until = None;
+        }
+
+        self.refill(now);
+        let weight = (weight as f64).min(self.weight_per_minute);
+        let request_wait = deficit_secs(self.request_tokens, 1.0, self.requests_per_minute);
+        let weight_wait = deficit_secs(self.weight_tokens, weight, self.weight_per_minute);
+        let wait = request_wait.max(weight_wait);
+        if wait > 0.0 {
+            return Duration::from_secs_f64(wait);
+        }
+
+        self.request_tokens -= 1.0;
+        self.weight_tokens -= weight;
+        Duration::ZERO
+    }
+
+    /// Aligns the weight bucket with the server's `X-MBX-USED-WEIGHT-1M` count,
+    /// which also covers weight spent by other processes on the same IP.
+    pub fn sync_used_weight(&mut self, used_weight: u32) {
+        let remaining = (self.weight_per_minute - used_weight as f64).max(0.0);
+        self.weight_tokens = self.weight_tokens.min(remaining);
+    }
+
+    /// Refuses all requests for `duration`, e.g. after a 429 `Retry-After`.
+    pub fn block_for(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.blocked_until = Some(
+            self.blocked_until
+                .map_or(until, |current| current.max(until)),
+        );
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed_minutes = now.duration_since(self.last_refill).as_secs_f64() / 60.0;
+        self.last_refill = now;
+        self.request_tokens = (self.request_tokens + elapsed_minutes * self.requests_per_minute)
+            .min(self.requests_per_minute);
+        self.weight_tokens = (self.weight_tokens + elapsed_minutes * self.weight_per_minute)
+            .min(self.weight_per_minute);
+    }
+}
+
+fn deficit_secs(available: f64, needed: f64, per_minute: f64) -> f64 {
+    if available >= needed {
+        0.0
+    } else {
+        (needed - available) / per_minute * 60.0
+    }
+}
+
+/// Process-wide limiter shared by every REST helper.
+pub fn shared() -> SharedRateLimiter {
+    static LIMITER: OnceLock<SharedRateLimiter> = OnceLock::new();
+    Arc::clone(LIMITER.get_or_init(|| {
+        Arc::new(Mutex::new(RateLimiter::new(
+            REQUESTS_PER_MINUTE,
+            WEIGHT_PER_MINUTE,
+        )))
+    }))
+}
+
+/// Waits until `limiter` admits a request of `weight`.
+pub async fn acquire(limiter: &SharedRateLimiter, weight: u32) {
+    loop {
+        let wait = limiter.lock().unwrap().try_acquire(weight);
+        if wait.is_zero() {
+            return;
+        }
+        debug!("Rate limiter delaying request by {:?}", wait);
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Feeds response headers back into `limiter`. On 429/418 the `Retry-After`
+/// duration blocks further requests and is returned.
+pub fn observe_response(
+    limiter: &SharedRateLimiter,
+    status: reqwest::StatusCode,
+    headers: &HeaderMap,
+) -> Option<Duration> {
+    let mut limiter = limiter.lock().unwrap();
+
+    if let Some(used_weight) = header_u64(headers, USED_WEIGHT_HEADER) {
+        limiter.sync_used_weight(used_weight as u32);
+    }
+
+    if status.as_u16() == 429 || status.as_u16() == 418 {
+        let retry_after =
+            Duration::from_secs(header_u64(headers, RETRY_AFTER.as_str()).unwrap_or(60));
+        warn!(
+            "Binance returned {}; pausing REST requests for {:?}",
+            status, retry_after
+        );
+        limiter.block_for(retry_after);
+        return Some(retry_after);
+    }
+
+    None
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
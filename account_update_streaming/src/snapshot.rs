@@ -0,0 +1,41 @@
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+/// `GET /fapi/v2/account`: the full account state that streamed `ACCOUNT_UPDATE`
+/// diffs build on. Lists every symbol, open or not.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSnapshot {
+    pub total_wallet_balance: Decimal,
+    pub available_balance: Decimal,
+    /// One entry per margin asset, including ones with no balance.
+    pub assets: Vec<AccountSnapshotAsset>,
+    pub positions: Vec<AccountSnapshotPosition>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSnapshotAsset {
+    pub asset: String,
+    pub wallet_balance: Decimal,
+    pub cross_wallet_balance: Decimal,
+    pub available_balance: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSnapshotPosition {
+    pub symbol: String,
+    #[serde(rename = "positionAmt")]
+    pub position_amount: Decimal,
+    pub entry_price: Decimal,
+    #[serde(default)]
+    pub break_even_price: Decimal,
+    pub unrealized_profit: Decimal,
+    /// `BOTH` in one-way mode, `LONG`/`SHORT` in hedge mode.
+    pub position_side: String,
+    #[serde(default)]
+    pub isolated: bool,
+    #[serde(default)]
+    pub isolated_wallet: Decimal,
+}
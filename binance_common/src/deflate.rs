@@ -0,0 +1,604 @@
+//! `permessage-deflate` (RFC 7692) for the websocket client.
+//!
+//! `tungstenite` 0.20 never offers the extension and rejects frames with the RSV1
+//! (compressed) bit set, so `DeflateStream` sits between the socket and
+//! `tungstenite`. It reads the handshake response to learn whether the server
+//! accepted the offer, and if so inflates every compressed message into a plain
+//! frame (with `flate2`) before `tungstenite` parses it. Outgoing messages stay
+//! uncompressed, which the extension allows.
+
+use flate2::{Decompress, FlushDecompress, Status};
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Sent in `Sec-WebSocket-Extensions`. We never compress what we send, so
+/// promising not to carry the client's compression context over is free.
+pub const EXTENSION_OFFER: &str = "permessage-deflate; client_no_context_takeover";
+const EXTENSION_NAME: &str = "permessage-deflate";
+/// Same cap as `tungstenite`'s default `max_message_size`, so a small compressed
+/// frame can't inflate without bound.
+const MAX_MESSAGE_BYTES: usize = 64 << 20;
+const READ_CHUNK_BYTES: usize = 16 * 1024;
+/// Each flush ends in an empty stored block whose last four bytes the sender drops.
+const FLUSH_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Stores whether websocket connections offer compression (`--compress`). Call
+/// once, before the first connection; later calls keep the first value.
+pub fn init(enabled: bool) {
+    if *ENABLED.get_or_init(|| enabled) {
+        info!("Offering permessage-deflate on websocket connections");
+    }
+}
+
+/// The value set by `init`; off if `init` was never called.
+pub fn enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// Bytes of compressed payload read and what they inflated to, shared with the
+/// stats line.
+#[derive(Debug, Default)]
+pub struct CompressionCounters {
+    compressed: AtomicU64,
+    inflated: AtomicU64,
+}
+
+impl CompressionCounters {
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed.load(Ordering::Relaxed)
+    }
+
+    pub fn inflated_bytes(&self) -> u64 {
+        self.inflated.load(Ordering::Relaxed)
+    }
+
+    /// Inflated size over compressed size; `None` until a compressed message arrives.
+    pub fn ratio(&self) -> Option<f64> {
+        let compressed = self.compressed_bytes();
+        (compressed > 0).then(|| self.inflated_bytes() as f64 / compressed as f64)
+    }
+
+    fn record(&self, compressed: usize, inflated: usize) {
+        self.compressed
+            .fetch_add(compressed as u64, Ordering::Relaxed);
+        self.inflated.fetch_add(inflated as u64, Ordering::Relaxed);
+    }
+}
+
+/// Raw DEFLATE decoder for one connection. The server may refer back into
+/// earlier messages, so one `Decompress` lives as long as the connection.
+pub struct Inflater {
+    decompress: Decompress,
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Self {
+            decompress: Decompress::new(false),
+        }
+    }
+}
+
+impl Inflater {
+    /// Inflates one message's payload (all of its frames, joined).
+    pub fn inflate_message(&mut self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let mut input = Vec::with_capacity(payload.len() + FLUSH_TAIL.len());
+        input.extend_from_slice(payload);
+        input.extend_from_slice(&FLUSH_TAIL);
+
+        let mut remaining = input.as_slice();
+        let mut message = Vec::with_capacity(payload.len() * 4);
+        loop {
+            if message.len() == message.capacity() {
+                if message.len() >= MAX_MESSAGE_BYTES {
+                    return Err(invalid_data("inflated message too large"));
+                }
+                message.reserve(message.len().max(READ_CHUNK_BYTES));
+            }
+            let consumed_before = self.decompress.total_in();
+            let status = self
+                .decompress
+                .decompress_vec(remaining, &mut message, FlushDecompress::Sync)
+                .map_err(|e| invalid_data(&e.to_string()))?;
+            remaining = &remaining[(self.decompress.total_in() - consumed_before) as usize..];
+            if status == Status::StreamEnd {
+                // A final block ends the context; the next message starts afresh.
+                self.decompress.reset(false);
+                return Ok(message);
+            }
+            // Out of input with room to spare means everything has been flushed.
+            if message.len() < message.capacity() {
+                if remaining.is_empty() {
+                    return Ok(message);
+                }
+                if status == Status::BufError {
+                    return Err(invalid_data("deflate stream stalled"));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadMode {
+    /// Waiting for the end of the handshake response, to see whether the server
+    /// accepted the offer.
+    Handshake,
+    Inflate,
+    Passthrough,
+}
+
+/// Socket wrapper that hands `tungstenite` inflated frames once the server has
+/// accepted `permessage-deflate`, and passes everything through otherwise.
+pub struct DeflateStream<S> {
+    inner: S,
+    mode: ReadMode,
+    /// Read from `inner` but not yet turned into frames for `tungstenite`.
+    raw: Vec<u8>,
+    /// Ready for `tungstenite`, from `ready_pos` on.
+    ready: Vec<u8>,
+    ready_pos: usize,
+    /// Opcode and payload so far of a fragmented compressed message.
+    fragments: Option<(u8, Vec<u8>)>,
+    inflater: Inflater,
+    counters: Arc<CompressionCounters>,
+}
+
+impl<S> DeflateStream<S> {
+    /// `offered` says whether the handshake request carries `EXTENSION_OFFER`;
+    /// without it the stream never inflates.
+    pub fn new(inner: S, offered: bool) -> Self {
+        Self {
+            inner,
+            mode: if offered {
+                ReadMode::Handshake
+            } else {
+                ReadMode::Passthrough
+            },
+            raw: Vec::new(),
+            ready: Vec::new(),
+            ready_pos: 0,
+            fragments: None,
+            inflater: Inflater::default(),
+            counters: Arc::new(CompressionCounters::default()),
+        }
+    }
+
+    /// Whether the server accepted `permessage-deflate`. Only meaningful once
+    /// the handshake is done.
+    pub fn is_negotiated(&self) -> bool {
+        self.mode == ReadMode::Inflate
+    }
+
+    pub fn counters(&self) -> Arc<CompressionCounters> {
+        Arc::clone(&self.counters)
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Moves whatever `raw` holds that `tungstenite` can take now into `ready`.
+    fn process(&mut self) -> io::Result<()> {
+        match self.mode {
+            ReadMode::Handshake => {
+                let Some(end) = find(&self.raw, b"\r\n\r\n") else {
+                    return Ok(());
+                };
+                let header_end = end + 4;
+                let accepted = response_accepts_deflate(&self.raw[..header_end]);
+                self.ready.extend(self.raw.drain(..header_end));
+                self.mode = if accepted {
+                    ReadMode::Inflate
+                } else {
+                    ReadMode::Passthrough
+                };
+                self.process()
+            }
+            ReadMode::Passthrough => {
+                self.ready.append(&mut self.raw);
+                Ok(())
+            }
+            ReadMode::Inflate => {
+                while let Some(frame) = FrameHeader::parse(&self.raw)? {
+                    if self.raw.len() < frame.len() {
+                        break;
+                    }
+                    let bytes = self.raw.drain(..frame.len()).collect::<Vec<_>>();
+                    self.on_frame(&frame, bytes)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn on_frame(&mut self, frame: &FrameHeader, bytes: Vec<u8>) -> io::Result<()> {
+        // Control frames may sit between the fragments of a message; masked
+        // frames are invalid from a server, so let tungstenite reject them.
+        if frame.opcode >= 0x8 || frame.masked {
+            self.ready.extend_from_slice(&bytes);
+            return Ok(());
+        }
+        let payload = &bytes[frame.header_len..];
+
+        if let Some((opcode, mut message)) = self.fragments.take() {
+            if frame.opcode != OPCODE_CONTINUATION {
+                return Err(invalid_data("expected a continuation frame"));
+            }
+            if message.len() + payload.len() > MAX_MESSAGE_BYTES {
+                return Err(invalid_data("compressed message too large"));
+            }
+            message.extend_from_slice(payload);
+            if frame.fin {
+                self.emit_inflated(opcode, &message)?;
+            } else {
+                self.fragments = Some((opcode, message));
+            }
+            return Ok(());
+        }
+
+        let starts_message = matches!(frame.opcode, OPCODE_TEXT | OPCODE_BINARY);
+        if !(starts_message && frame.rsv1) {
+            // Uncompressed messages, and their continuations, go through as is.
+            self.ready.extend_from_slice(&bytes);
+        } else if frame.fin {
+            self.emit_inflated(frame.opcode, payload)?;
+        } else {
+            self.fragments = Some((frame.opcode, payload.to_vec()));
+        }
+        Ok(())
+    }
+
+    fn emit_inflated(&mut self, opcode: u8, compressed: &[u8]) -> io::Result<()> {
+        let message = self
+            .inflater
+            .inflate_message(compressed)
+            .map_err(|e| invalid_data(&format!("permessage-deflate: {}", e)))?;
+        self.counters.record(compressed.len(), message.len());
+        write_frame_header(&mut self.ready, opcode, message.len());
+        self.ready.extend_from_slice(&message);
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for DeflateStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.ready_pos < this.ready.len() {
+                let n = buf.remaining().min(this.ready.len() - this.ready_pos);
+                buf.put_slice(&this.ready[this.ready_pos..this.ready_pos + n]);
+                this.ready_pos += n;
+                if this.ready_pos == this.ready.len() {
+                    this.ready.clear();
+                    this.ready_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.mode == ReadMode::Passthrough && this.raw.is_empty() {
+                return Pin::new(&mut this.inner).poll_read(cx, buf);
+            }
+
+            let mut chunk = [0u8; READ_CHUNK_BYTES];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+            let read = chunk_buf.filled();
+            if read.is_empty() {
+                // EOF: hand over any partial frame so tungstenite reports the truncation.
+                this.ready.append(&mut this.raw);
+                if this.ready.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+            this.raw.extend_from_slice(read);
+            this.process()?;
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for DeflateStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The fields of a websocket frame header (RFC 6455 5.2) that matter here.
+#[derive(Debug)]
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    masked: bool,
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl FrameHeader {
+    /// `None` until `data` holds the whole header.
+    fn parse(data: &[u8]) -> io::Result<Option<Self>> {
+        let [first, second, ..] = *data else {
+            return Ok(None);
+        };
+        let masked = second & 0x80 != 0;
+        let (extended, payload_len) = match second & 0x7f {
+            126 => match data.get(2..4) {
+                Some(bytes) => (2, u64::from(u16::from_be_bytes([bytes[0], bytes[1]]))),
+                None => return Ok(None),
+            },
+            127 => match data.get(2..10) {
+                Some(bytes) => (8, u64::from_be_bytes(bytes.try_into().unwrap())),
+                None => return Ok(None),
+            },
+            len => (0, u64::from(len)),
+        };
+        let header_len = 2 + extended + if masked { 4 } else { 0 };
+        if data.len() < header_len {
+            return Ok(None);
+        }
+        let payload_len = usize::try_from(payload_len)
+            .ok()
+            .filter(|len| *len <= MAX_MESSAGE_BYTES)
+            .ok_or_else(|| invalid_data("frame too large"))?;
+        Ok(Some(Self {
+            fin: first & 0x80 != 0,
+            rsv1: first & 0x40 != 0,
+            opcode: first & 0x0f,
+            masked,
+            header_len,
+            payload_len,
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.header_len + self.payload_len
+    }
+}
+
+/// Header of a final, unmasked, uncompressed frame carrying `payload_len` bytes.
+fn write_frame_header(out: &mut Vec<u8>, opcode: u8, payload_len: usize) {
+    out.push(0x80 | opcode);
+    if payload_len < 126 {
+        out.push(payload_len as u8);
+    } else if let Ok(len) = u16::try_from(payload_len) {
+        out.push(126);
+        out.extend_from_slice(&len.to_be_bytes());
+    } else {
+        out.push(127);
+        out.extend_from_slice(&(payload_len as u64).to_be_bytes());
+    }
+}
+
+/// Whether a handshake response's `Sec-WebSocket-Extensions` header accepts
+/// `permessage-deflate`.
+fn response_accepts_deflate(response: &[u8]) -> bool {
+    String::from_utf8_lossy(response).lines().any(|line| {
+        let Some((name, value)) = line.split_once(':') else {
+            return false;
+        };
+        name.trim().eq_ignore_ascii_case("sec-websocket-extensions")
+            && value.split(',').any(|extension| {
+                extension.split(';').next().unwrap_or_default().trim() == EXTENSION_NAME
+            })
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression, FlushCompress};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const TRADE: &str =
+        r#"{"e":"trade","E":1,"s":"ETHUSDT","t":42,"p":"3000.50","q":"0.25","T":1,"m":false}"#;
+    /// `TRADE` through zlib's raw deflate with fixed Huffman codes and a sync
+    /// flush, minus the `00 00 ff ff` tail, as a server sends it.
+    const TRADE_FIXED: &[u8] = &[
+        0xaa, 0x56, 0x4a, 0x55, 0xb2, 0x52, 0x2a, 0x29, 0x4a, 0x4c, 0x49, 0x55, 0xd2, 0x51, 0x72,
+        0x55, 0xb2, 0x32, 0xd4, 0x51, 0x2a, 0x06, 0x8a, 0xb8, 0x86, 0x78, 0x84, 0x06, 0xbb, 0x84,
+        0x00, 0xc5, 0x4a, 0x94, 0xac, 0x4c, 0x8c, 0x74, 0x94, 0x0a, 0x80, 0x82, 0xc6, 0x06, 0x06,
+        0x06, 0x7a, 0xa6, 0x06, 0x40, 0xc1, 0x42, 0x20, 0xcf, 0x40, 0xcf, 0xc8, 0x14, 0xc8, 0x0c,
+        0x01, 0xeb, 0xc9, 0x55, 0xb2, 0x4a, 0x4b, 0xcc, 0x29, 0x4e, 0xad, 0x05, 0x00,
+    ];
+    /// `TRADE` sent again by the same compressor: one back-reference into the
+    /// previous message.
+    const TRADE_AGAIN: &[u8] = &[0xaa, 0xa6, 0xb6, 0x81, 0x00, 0x00];
+    const DEPTH: &str = r#"{"e":"depthUpdate","E":2,"s":"ETHUSDT","U":10,"u":12,"b":[["2999.00","1.5"],["2998.00","2.5"],["2997.00","3.5"],["2996.00","4.5"]],"a":[["3001.00","1.0"],["3002.00","2.0"],["3003.00","3.0"]]}"#;
+    /// `DEPTH` with zlib's default (dynamic Huffman) codes.
+    const DEPTH_DYNAMIC: &[u8] = &[
+        0x44, 0xcc, 0x31, 0x0b, 0x83, 0x30, 0x10, 0x86, 0xe1, 0xff, 0xf2, 0xcd, 0x87, 0x5c, 0x12,
+        0x6d, 0x9b, 0xcc, 0x15, 0xdc, 0x35, 0x93, 0x64, 0xb0, 0x18, 0x70, 0x14, 0xb4, 0x93, 0xf4,
+        0xbf, 0xf7, 0x4c, 0x88, 0x4e, 0x07, 0xcf, 0x77, 0xbc, 0x07, 0x22, 0x1c, 0xe6, 0xb8, 0xee,
+        0x8b, 0x5f, 0xe7, 0x69, 0x8f, 0x20, 0xb4, 0x70, 0x9a, 0xb0, 0x89, 0xb7, 0x43, 0xe7, 0xfb,
+        0xf7, 0x20, 0xe6, 0xe1, 0x14, 0x13, 0xbe, 0x72, 0x64, 0xfb, 0xc0, 0x8d, 0x23, 0xb4, 0xb5,
+        0xb6, 0x62, 0x96, 0x55, 0x55, 0x0d, 0x02, 0x25, 0x79, 0x65, 0xd1, 0xb7, 0x3c, 0xb3, 0x98,
+        0x5b, 0x1e, 0x59, 0xea, 0x53, 0x02, 0x61, 0x4a, 0x35, 0xc3, 0xac, 0x4a, 0x8d, 0xd3, 0xa7,
+        0x88, 0x2e, 0xb5, 0x4b, 0x4c, 0xa9, 0x89, 0x84, 0xdf, 0x1f,
+    ];
+
+    /// `TRADE` in a single stored (uncompressed) block, as zlib level 0 sends it.
+    fn trade_stored() -> Vec<u8> {
+        let mut block = vec![0x00, 0x51, 0x00, 0xae, 0xff];
+        block.extend_from_slice(TRADE.as_bytes());
+        block.push(0x00);
+        block
+    }
+
+    fn frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_frame_header(&mut bytes, first_byte & 0x0f, payload.len());
+        bytes[0] = first_byte;
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn inflates_fixed_dynamic_and_stored_blocks() {
+        let mut inflater = Inflater::default();
+        assert_eq!(
+            inflater.inflate_message(TRADE_FIXED).unwrap(),
+            TRADE.as_bytes()
+        );
+        let mut inflater = Inflater::default();
+        assert_eq!(
+            inflater.inflate_message(DEPTH_DYNAMIC).unwrap(),
+            DEPTH.as_bytes()
+        );
+        let mut inflater = Inflater::default();
+        assert_eq!(
+            inflater.inflate_message(&trade_stored()).unwrap(),
+            TRADE.as_bytes()
+        );
+    }
+
+    #[test]
+    fn later_messages_can_refer_back_into_earlier_ones() {
+        let mut inflater = Inflater::default();
+        inflater.inflate_message(TRADE_FIXED).unwrap();
+        assert_eq!(
+            inflater.inflate_message(TRADE_AGAIN).unwrap(),
+            TRADE.as_bytes()
+        );
+
+        // Without the first message there is nothing to refer to.
+        let fresh = Inflater::default().inflate_message(TRADE_AGAIN);
+        assert!(fresh.map_or(true, |message| message != TRADE.as_bytes()));
+    }
+
+    /// Messages much larger than their compressed form, from a compressor that
+    /// keeps its context, as Binance's does.
+    #[test]
+    fn inflates_a_long_run_of_messages_sharing_one_context() {
+        let mut compress = Compress::new(Compression::default(), false);
+        let mut inflater = Inflater::default();
+        for k in 0..200 {
+            let message = TRADE.replace("42", &k.to_string()).repeat(1 + k % 300);
+            let mut compressed = Vec::with_capacity(message.len() + 1024);
+            compress
+                .compress_vec(message.as_bytes(), &mut compressed, FlushCompress::Sync)
+                .unwrap();
+            assert!(compressed.ends_with(&FLUSH_TAIL));
+            compressed.truncate(compressed.len() - FLUSH_TAIL.len());
+            assert_eq!(
+                inflater.inflate_message(&compressed).unwrap(),
+                message.as_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_input_is_an_error() {
+        let mut bad_block = Inflater::default();
+        let err = bad_block.inflate_message(&[0x07]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn extension_header_is_matched_by_name() {
+        let accepted = b"HTTP/1.1 101 Switching Protocols\r\nsec-websocket-extensions: permessage-deflate; client_no_context_takeover\r\n\r\n";
+        let other = b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: x-permessage-deflate-ish\r\n\r\n";
+        assert!(response_accepts_deflate(accepted));
+        assert!(!response_accepts_deflate(other));
+        assert!(!response_accepts_deflate(
+            b"HTTP/1.1 101 Switching Protocols\r\n\r\n"
+        ));
+    }
+
+    /// Compressed frames come out as plain ones, fragments joined, with control
+    /// and uncompressed frames left alone; the counters see only compressed bytes.
+    #[tokio::test]
+    async fn stream_rewrites_compressed_frames_after_an_accepting_handshake() {
+        let handshake: &[u8] =
+            b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let (first, rest) = DEPTH_DYNAMIC.split_at(50);
+        let mut wire = handshake.to_vec();
+        wire.extend(frame(0xc1, TRADE_FIXED));
+        wire.extend(frame(0x41, first));
+        wire.extend(frame(0x89, b"ping"));
+        wire.extend(frame(0x80, rest));
+        wire.extend(frame(0x81, b"plain"));
+
+        let (mut server, client) = tokio::io::duplex(64);
+        let mut stream = DeflateStream::new(client, true);
+        tokio::spawn(async move { server.write_all(&wire).await.unwrap() });
+        let mut read = Vec::new();
+        stream.read_to_end(&mut read).await.unwrap();
+
+        let mut expected = handshake.to_vec();
+        expected.extend(frame(0x81, TRADE.as_bytes()));
+        expected.extend(frame(0x89, b"ping"));
+        expected.extend(frame(0x81, DEPTH.as_bytes()));
+        expected.extend(frame(0x81, b"plain"));
+        assert_eq!(read, expected);
+        assert!(stream.is_negotiated());
+        let counters = stream.counters();
+        assert_eq!(
+            counters.compressed_bytes() as usize,
+            TRADE_FIXED.len() + DEPTH_DYNAMIC.len()
+        );
+        assert_eq!(
+            counters.inflated_bytes() as usize,
+            TRADE.len() + DEPTH.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_passes_everything_through_when_the_offer_is_ignored() {
+        let mut wire = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n".to_vec();
+        wire.extend(frame(0x81, TRADE.as_bytes()));
+
+        let (mut server, client) = tokio::io::duplex(64);
+        let mut stream = DeflateStream::new(client, true);
+        let sent = wire.clone();
+        tokio::spawn(async move { server.write_all(&sent).await.unwrap() });
+        let mut read = Vec::new();
+        stream.read_to_end(&mut read).await.unwrap();
+
+        assert_eq!(read, wire);
+        assert!(!stream.is_negotiated());
+        assert_eq!(stream.counters().ratio(), None);
+    }
+}
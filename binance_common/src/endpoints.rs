@@ -0,0 +1,103 @@
+use std::env;
+
+/// Overrides the websocket root (scheme + host[:port]), e.g. `ws://localhost:9000`.
+pub const WS_URL_ENV: &str = "BINANCE_WS_URL";
+/// Overrides the REST root, e.g. `http://localhost:8080`.
+pub const REST_URL_ENV: &str = "BINANCE_REST_URL";
+/// Overrides the full WebSocket API URL, e.g. for a local mock.
+pub const WS_API_URL_ENV: &str = "BINANCE_WS_API_URL";
+
+/// Resolves an endpoint root with precedence: explicit CLI flag > env var > compiled default.
+/// Paths such as `/ws` or `/api/v3/depth` are appended by the caller.
+pub fn resolve_url(cli_value: Option<&str>, env_var: &str, default: &str) -> String {
+    let url = match cli_value {
+        Some(value) => value.to_string(),
+        None => match env::var(env_var) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => default.to_string(),
+        },
+    };
+
+    url.trim().trim_end_matches('/').to_string()
+}
+
+/// Names accepted by `--market`, in `Market` declaration order.
+pub const MARKET_NAMES: [&str; 4] = ["spot", "spot-testnet", "futures", "futures-testnet"];
+
+/// Venue and network a bin talks to. Owns the compiled default URL roots so the
+/// bins never pair a spot websocket with a futures REST base (or vice versa).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Market {
+    #[default]
+    SpotMainnet,
+    SpotTestnet,
+    /// USD-M futures.
+    FuturesMainnet,
+    FuturesTestnet,
+}
+
+impl Market {
+    /// Parses a `--market` value; `mainnet` may be spelled out, e.g. `futures-mainnet`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "spot" | "spot-mainnet" => Some(Market::SpotMainnet),
+            "spot-testnet" => Some(Market::SpotTestnet),
+            "futures" | "futures-mainnet" => Some(Market::FuturesMainnet),
+            "futures-testnet" => Some(Market::FuturesTestnet),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Market::SpotMainnet => "spot",
+            Market::SpotTestnet => "spot-testnet",
+            Market::FuturesMainnet => "futures",
+            Market::FuturesTestnet => "futures-testnet",
+        }
+    }
+
+    pub fn is_futures(self) -> bool {
+        matches!(self, Market::FuturesMainnet | Market::FuturesTestnet)
+    }
+
+    /// Same venue on the other network; backs the `--testnet`/`--mainnet` shorthands.
+    pub fn with_testnet(self, testnet: bool) -> Self {
+        match (self.is_futures(), testnet) {
+            (false, false) => Market::SpotMainnet,
+            (false, true) => Market::SpotTestnet,
+            (true, false) => Market::FuturesMainnet,
+            (true, true) => Market::FuturesTestnet,
+        }
+    }
+
+    /// Default websocket root (`/ws` or `/stream` is appended by the caller).
+    pub fn ws_base(self) -> &'static str {
+        match self {
+            Market::SpotMainnet => "wss://stream.binance.com:9443",
+            Market::SpotTestnet => "wss://testnet.binance.vision",
+            Market::FuturesMainnet => "wss://fstream.binance.com",
+            Market::FuturesTestnet => "wss://fstream.binancefuture.com",
+        }
+    }
+
+    /// Default WebSocket API URL, including its versioned path.
+    pub fn ws_api_base(self) -> &'static str {
+        match self {
+            Market::SpotMainnet => "wss://ws-api.binance.com:443/ws-api/v3",
+            Market::SpotTestnet => "wss://ws-api.testnet.binance.vision/ws-api/v3",
+            Market::FuturesMainnet => "wss://ws-fapi.binance.com/ws-fapi/v1",
+            Market::FuturesTestnet => "wss://ws-fapi.binancefuture.com/ws-fapi/v1",
+        }
+    }
+
+    /// Default REST root (`/api/v3/...` or `/fapi/v1/...` is appended by the caller).
+    pub fn rest_base(self) -> &'static str {
+        match self {
+            Market::SpotMainnet => "https://api.binance.com",
+            Market::SpotTestnet => "https://testnet.binance.vision",
+            Market::FuturesMainnet => "https://fapi.binance.com",
+            Market::FuturesTestnet => "https://testnet.binancefuture.com",
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Randomized reconnect delays, so clients dropped together don't all reconnect
+//! in the same instant. Every reconnect loop in both packages goes through
+//! `with_jitter`.
+
+use rand::Rng;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Percent by which each reconnect delay is randomized either way (0 = no jitter).
+pub const RECONNECT_JITTER_ENV: &str = "RECONNECT_JITTER_PCT";
+pub const DEFAULT_RECONNECT_JITTER_PCT: u32 = 50;
+
+static JITTER_PCT: OnceLock<u32> = OnceLock::new();
+
+/// `delay` scaled by a random factor in `[1 - j, 1 + j]`, where `j` is
+/// `RECONNECT_JITTER_PCT` percent (default 50; `0` disables jitter).
+pub fn with_jitter(delay: Duration) -> Duration {
+    jittered(delay, jitter_pct())
+}
+
+fn jittered(delay: Duration, pct: u32) -> Duration {
+    if pct == 0 {
+        return delay;
+    }
+    let spread = f64::from(pct) / 100.0;
+    delay.mul_f64(rand::thread_rng().gen_range(1.0 - spread..=1.0 + spread))
+}
+
+/// Read from the environment once, on the first reconnect.
+fn jitter_pct() -> u32 {
+    *JITTER_PCT.get_or_init(|| match env::var(RECONNECT_JITTER_ENV) {
+        Ok(value) => match value.trim().parse::<u32>() {
+            Ok(pct) if pct <= 100 => pct,
+            _ => {
+                warn!(
+                    "Invalid {} value '{}' (expected 0-100), using {}",
+                    RECONNECT_JITTER_ENV, value, DEFAULT_RECONNECT_JITTER_PCT
+                );
+                DEFAULT_RECONNECT_JITTER_PCT
+            }
+        },
+        Err(_) => DEFAULT_RECONNECT_JITTER_PCT,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_stays_within_the_spread() {
+        let delay = Duration::from_secs(10);
+        assert_eq!(jittered(delay, 0), delay);
+        for _ in 0..100 {
+            let jittered = jittered(delay, 50);
+            assert!(jittered >= Duration::from_secs(5), "{:?}", jittered);
+            assert!(jittered <= Duration::from_secs(15), "{:?}", jittered);
+        }
+    }
+}
@@ -0,0 +1,8 @@
+//! Connection plumbing shared by `public_data_streaming` and `account_update_streaming`.
+
+pub mod deflate;
+pub mod endpoints;
+pub mod jitter;
+pub mod proxy;
+pub mod settings;
+pub mod tls;
@@ -0,0 +1,265 @@
+//! Optional HTTP proxy for REST calls and the websocket handshake.
+//!
+//! The proxy is resolved once at startup (`--proxy` flag > `HTTPS_PROXY` >
+//! `ALL_PROXY`) and then applied to every connection: reqwest clients get it via
+//! `.proxy(...)`, websockets tunnel through it with an HTTP `CONNECT`.
+
+use crate::deflate::{self, DeflateStream};
+use crate::tls;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rustls::ServerName;
+use std::env;
+use std::io;
+use std::sync::OnceLock;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::UrlError;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::{self, handshake::client::Response};
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream};
+use url::Url;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Checked in order when no `--proxy` flag is given; lowercase variants are the
+/// curl convention and common in shells.
+pub const PROXY_ENV_VARS: [&str; 4] = ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"];
+// Upper bound on the proxy's CONNECT response headers.
+const MAX_CONNECT_RESPONSE_BYTES: usize = 8 * 1024;
+
+static PROXY: OnceLock<Option<Url>> = OnceLock::new();
+
+/// Resolves and stores the process-wide proxy. Call once, before the first
+/// connection; later calls keep the first value.
+pub fn init(cli_value: Option<&str>) -> Result<Option<&'static Url>, String> {
+    let configured = match cli_value {
+        Some(value) => Some(("--proxy".to_string(), value.to_string())),
+        None => PROXY_ENV_VARS.iter().find_map(|name| {
+            env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| (name.to_string(), value))
+        }),
+    };
+
+    let proxy = match configured {
+        Some((source, value)) => Some(parse_proxy_url(&source, &value)?),
+        None => None,
+    };
+    if let Some(url) = &proxy {
+        info!("Using proxy {}", redacted(url));
+    }
+
+    Ok(PROXY.get_or_init(|| proxy).as_ref())
+}
+
+/// The proxy set by `init`, if any.
+pub fn current() -> Option<&'static Url> {
+    PROXY.get().and_then(Option::as_ref)
+}
+
+fn parse_proxy_url(source: &str, value: &str) -> Result<Url, String> {
+    let url = Url::parse(value.trim())
+        .map_err(|e| format!("Invalid proxy URL from {} ({}): {}", source, value, e))?;
+    if url.scheme() != "http" {
+        return Err(format!(
+            "Unsupported proxy scheme '{}' from {}; only http:// proxies are supported",
+            url.scheme(),
+            source
+        ));
+    }
+    if url.host_str().is_none() {
+        return Err(format!("Proxy URL from {} has no host: {}", source, value));
+    }
+    Ok(url)
+}
+
+/// The proxy URL without credentials, for logging.
+fn redacted(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.to_string()
+}
+
+/// A reqwest client that goes through the configured proxy (or direct without one)
+/// and trusts the roots chosen by `tls::init`.
+pub fn http_client() -> reqwest::Result<reqwest::Client> {
+    let builder = reqwest::Client::builder().use_preconfigured_tls((*tls::client_config()).clone());
+    let builder = match current() {
+        Some(url) => builder.proxy(reqwest::Proxy::all(url.as_str())?),
+        None => builder.no_proxy(),
+    };
+    builder.build()
+}
+
+/// `tokio_tungstenite::connect_async`, tunnelled through the proxy when one is set.
+pub async fn connect_ws(
+    url: Url,
+) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, Response), tungstenite::Error> {
+    let connector = Some(Connector::Rustls(tls::client_config()));
+    let result = match current() {
+        None => {
+            tokio_tungstenite::connect_async_tls_with_config(url.clone(), None, false, connector)
+                .await
+        }
+        Some(proxy) => match connect_tunnel(proxy, &url).await {
+            Ok(stream) => {
+                tokio_tungstenite::client_async_tls_with_config(
+                    url.clone(),
+                    stream,
+                    None,
+                    connector,
+                )
+                .await
+            }
+            Err(e) => Err(e.into()),
+        },
+    };
+    result.map_err(|e| tls::explain_connect_error(&url, e))
+}
+
+/// Like `connect_ws`, but offers `permessage-deflate`. A server that ignores the
+/// offer is read uncompressed; one that fails the handshake over it is tried once
+/// more without the offer.
+pub async fn connect_ws_deflate(
+    url: Url,
+) -> Result<
+    (
+        WebSocketStream<DeflateStream<MaybeTlsStream<TcpStream>>>,
+        Response,
+    ),
+    tungstenite::Error,
+> {
+    let result = match handshake_deflate(&url, true).await {
+        Err(tungstenite::Error::Http(response)) => {
+            warn!(
+                "Handshake with permessage-deflate offered failed ({}); retrying uncompressed",
+                response.status()
+            );
+            handshake_deflate(&url, false).await
+        }
+        result => result,
+    };
+    let (ws_stream, response) = result.map_err(|e| tls::explain_connect_error(&url, e))?;
+    if ws_stream.get_ref().is_negotiated() {
+        info!("Server accepted permessage-deflate; inflating compressed messages");
+    } else {
+        warn!("Server didn't accept permessage-deflate; reading uncompressed frames");
+    }
+    Ok((ws_stream, response))
+}
+
+async fn handshake_deflate(
+    url: &Url,
+    offer: bool,
+) -> Result<
+    (
+        WebSocketStream<DeflateStream<MaybeTlsStream<TcpStream>>>,
+        Response,
+    ),
+    tungstenite::Error,
+> {
+    let mut request = url.as_str().into_client_request()?;
+    if offer {
+        request.headers_mut().insert(
+            "Sec-WebSocket-Extensions",
+            HeaderValue::from_static(deflate::EXTENSION_OFFER),
+        );
+    }
+    let stream = DeflateStream::new(open_stream(url).await?, offer);
+    tokio_tungstenite::client_async(request, stream).await
+}
+
+/// The socket for `url`, tunnelled through the proxy when one is set and wrapped
+/// in TLS (trusting the roots chosen by `tls::init`) for `wss://`. The TLS layer
+/// is set up here rather than by `tokio_tungstenite`, so that `DeflateStream`
+/// can sit between it and the websocket.
+async fn open_stream(url: &Url) -> Result<MaybeTlsStream<TcpStream>, tungstenite::Error> {
+    let host = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .ok_or(tungstenite::Error::Url(UrlError::NoHostName))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or(tungstenite::Error::Url(UrlError::UnsupportedUrlScheme))?;
+    let socket = match current() {
+        None => TcpStream::connect((host, port)).await?,
+        Some(proxy) => connect_tunnel(proxy, url).await?,
+    };
+    if url.scheme() != "wss" {
+        return Ok(MaybeTlsStream::Plain(socket));
+    }
+
+    let domain =
+        ServerName::try_from(host).map_err(|_| tungstenite::Error::Url(UrlError::NoHostName))?;
+    let stream = TlsConnector::from(tls::client_config())
+        .connect(domain, socket)
+        .await?;
+    Ok(MaybeTlsStream::Rustls(stream))
+}
+
+async fn connect_tunnel(proxy: &Url, target: &Url) -> io::Result<TcpStream> {
+    let target_host = target
+        .host_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "websocket URL has no host"))?;
+    let target_port = target
+        .port_or_known_default()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "websocket URL has no port"))?;
+    let proxy_host = proxy.host_str().unwrap_or_default();
+    let proxy_port = proxy.port_or_known_default().unwrap_or(80);
+
+    debug!(
+        "Opening CONNECT tunnel to {}:{} via {}:{}",
+        target_host, target_port, proxy_host, proxy_port
+    );
+    let mut stream = TcpStream::connect((proxy_host, proxy_port)).await?;
+
+    let mut request = format!(
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n",
+        host = target_host,
+        port = target_port
+    );
+    if !proxy.username().is_empty() {
+        let credentials = format!(
+            "{}:{}",
+            proxy.username(),
+            proxy.password().unwrap_or_default()
+        );
+        request.push_str(&format!(
+            "Proxy-Authorization: Basic {}\r\n",
+            BASE64.encode(credentials)
+        ));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read byte by byte so nothing past the header block (the TLS handshake) is consumed.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_CONNECT_RESPONSE_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "proxy CONNECT response headers too large",
+            ));
+        }
+        let byte = stream.read_u8().await?;
+        response.push(byte);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("proxy CONNECT failed: {}", status_line),
+        ));
+    }
+
+    Ok(stream)
+}
@@ -0,0 +1,411 @@
+//! Logging setup for every bin: a colored (or JSON) console log plus a rotating
+//! plain-text log file, configured from the environment.
+
+use chrono::Local;
+use fern::colors::{Color, ColoredLevelConfig};
+use fern::Dispatch;
+use log::{LevelFilter, Record};
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Set to `json` to emit one JSON object per log record instead of colored lines.
+const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Pretty,
+    Json,
+}
+
+/// Path of the plain-text log file (rotated copies get `.1`, `.2`, ... appended).
+const LOG_FILE_ENV: &str = "LOG_FILE";
+const DEFAULT_LOG_FILE: &str = "output.log";
+/// Rotate once the log file grows past this many MB; `0` disables rotation.
+const LOG_MAX_SIZE_MB_ENV: &str = "LOG_MAX_SIZE_MB";
+const DEFAULT_LOG_MAX_SIZE_MB: u64 = 10;
+const LOG_MAX_BACKUPS: u32 = 5;
+
+/// Fractional-second digits in log timestamps: 0, 3 (default), 6 or 9.
+const LOG_TIMESTAMP_PRECISION_ENV: &str = "LOG_TIMESTAMP_PRECISION";
+const DEFAULT_LOG_TIMESTAMP_PRECISION: u8 = 3;
+
+static TIMESTAMP_FORMAT: OnceLock<&'static str> = OnceLock::new();
+
+/// Checked in order; the first non-empty value wins. Accepts a bare level
+/// (off/error/warn/info/debug/trace) or `RUST_LOG`-style directives such as
+/// `info,hyper=warn,public_data_streaming::session=debug`.
+const LOG_LEVEL_ENVS: [&str; 2] = ["LOG_LEVEL", "RUST_LOG"];
+
+/// A default level plus per-target overrides, applied with fern's `level_for`.
+#[derive(Debug, PartialEq)]
+struct LogFilter {
+    level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    /// Parses comma-separated `level` and `target=level` directives; later ones win.
+    /// A bare word that isn't a level is rejected rather than read as a target,
+    /// so a misspelt level still falls back to info with a warning.
+    fn parse(value: &str) -> Option<Self> {
+        let mut filter = Self::from(LevelFilter::Info);
+        for directive in value.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return None;
+                    }
+                    let level = level.trim().parse::<LevelFilter>().ok()?;
+                    filter.targets.push((target.to_string(), level));
+                }
+                None => filter.level = directive.parse().ok()?,
+            }
+        }
+        Some(filter)
+    }
+
+    fn apply(self, mut dispatch: Dispatch) -> Dispatch {
+        dispatch = dispatch.level(self.level);
+        for (target, level) in self.targets {
+            dispatch = dispatch.level_for(target, level);
+        }
+        dispatch
+    }
+}
+
+impl From<LevelFilter> for LogFilter {
+    fn from(level: LevelFilter) -> Self {
+        Self {
+            level,
+            targets: Vec::new(),
+        }
+    }
+}
+
+/// Returns the configured filter, plus the offending `VAR=value` if it could not be parsed.
+fn log_level() -> (LogFilter, Option<String>) {
+    for var in LOG_LEVEL_ENVS {
+        let Ok(value) = env::var(var) else {
+            continue;
+        };
+        if value.trim().is_empty() {
+            continue;
+        }
+
+        return match LogFilter::parse(&value) {
+            Some(filter) => (filter, None),
+            None => (LevelFilter::Info.into(), Some(format!("{}={}", var, value))),
+        };
+    }
+
+    (LevelFilter::Info.into(), None)
+}
+
+fn log_max_size_mb() -> (u64, Option<String>) {
+    match env::var(LOG_MAX_SIZE_MB_ENV) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(size_mb) => (size_mb, None),
+            Err(_) => (
+                DEFAULT_LOG_MAX_SIZE_MB,
+                Some(format!("{}={}", LOG_MAX_SIZE_MB_ENV, value)),
+            ),
+        },
+        _ => (DEFAULT_LOG_MAX_SIZE_MB, None),
+    }
+}
+
+/// The bin's `--log-file`, then `LOG_FILE`, then `output.log`.
+fn log_file_path(log_file: Option<&str>) -> String {
+    log_file
+        .map(str::to_string)
+        .or_else(|| env::var(LOG_FILE_ENV).ok())
+        .filter(|path| !path.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_LOG_FILE.to_string())
+}
+
+fn timestamp_format() -> (&'static str, Option<String>) {
+    let precision = match env::var(LOG_TIMESTAMP_PRECISION_ENV) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u8>() {
+            Ok(digits @ (0 | 3 | 6 | 9)) => digits,
+            _ => {
+                return (
+                    timestamp_format_for(DEFAULT_LOG_TIMESTAMP_PRECISION),
+                    Some(format!("{}={}", LOG_TIMESTAMP_PRECISION_ENV, value)),
+                )
+            }
+        },
+        _ => DEFAULT_LOG_TIMESTAMP_PRECISION,
+    };
+    (timestamp_format_for(precision), None)
+}
+
+fn timestamp_format_for(digits: u8) -> &'static str {
+    match digits {
+        0 => "%Y-%m-%d %H:%M:%S",
+        6 => "%Y-%m-%d %H:%M:%S%.6f",
+        9 => "%Y-%m-%d %H:%M:%S%.9f",
+        _ => "%Y-%m-%d %H:%M:%S%.3f",
+    }
+}
+
+/// Append-only log file that rolls `path` -> `path.1` -> ... -> `path.N` once it exceeds `max_bytes`.
+/// Rotation is checked on flush, which fern issues after every record, so records never straddle files.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    written: u64,
+    file: File,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            written,
+            file,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..LOG_MAX_BACKUPS).rev() {
+            let from = backup_path(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, backup_path(&self.path, index + 1))?;
+            }
+        }
+        fs::rename(&self.path, backup_path(&self.path, 1))?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_bytes > 0 && self.written >= self.max_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+}
+
+fn backup_path(path: &Path, index: u32) -> PathBuf {
+    let mut backup = OsString::from(path.as_os_str());
+    backup.push(format!(".{}", index));
+    PathBuf::from(backup)
+}
+
+fn log_format() -> LogFormat {
+    match env::var(LOG_FORMAT_ENV) {
+        Ok(value) if value.trim().eq_ignore_ascii_case("json") => LogFormat::Json,
+        _ => LogFormat::Pretty,
+    }
+}
+
+fn timestamp() -> String {
+    let format = TIMESTAMP_FORMAT
+        .get()
+        .copied()
+        .unwrap_or_else(|| timestamp_format_for(DEFAULT_LOG_TIMESTAMP_PRECISION));
+    Local::now().format(format).to_string()
+}
+
+fn json_line(message: &fmt::Arguments, record: &Record) -> String {
+    serde_json::json!({
+        "timestamp": timestamp(),
+        "level": record.level().to_string(),
+        "file": record.file().unwrap_or("unknown_file"),
+        "line": record.line().unwrap_or(0),
+        "message": message.to_string(),
+    })
+    .to_string()
+}
+
+/// Logs to stdout and to `log_file` (the bin's `--log-file`), falling back to
+/// `LOG_FILE` and then `output.log` when it is `None`.
+pub fn init_logging(log_file: Option<&str>) {
+    init_logging_to(Box::new(io::stdout()), log_file);
+}
+
+/// Like `init_logging`, but the terminal copy of the log goes to `console`, e.g.
+/// stderr when stdout is reserved for machine-readable output.
+pub fn init_logging_to(console: Box<dyn Write + Send>, log_file: Option<&str>) {
+    let format = log_format();
+    let (level, invalid_level) = log_level();
+    let (max_size_mb, invalid_max_size) = log_max_size_mb();
+    let (timestamp_format, invalid_precision) = timestamp_format();
+    let _ = TIMESTAMP_FORMAT.set(timestamp_format);
+    let log_path = log_file_path(log_file);
+    // A read-only working directory shouldn't take the process down; the console
+    // copy of the log still works.
+    let log_file = match RotatingFile::open(PathBuf::from(&log_path), max_size_mb * 1024 * 1024) {
+        Ok(log_file) => Some(log_file),
+        Err(e) => {
+            eprintln!(
+                "Failed to open log file {}: {}; logging to the console only",
+                log_path, e
+            );
+            None
+        }
+    };
+
+    // Define color configuration for different log levels
+    let colors = ColoredLevelConfig::new()
+        .error(Color::Red)
+        .warn(Color::Yellow)
+        .info(Color::Green)
+        .debug(Color::Cyan)
+        .trace(Color::BrightBlack);
+
+    // ========================
+    // 1. Configure Terminal Logging with Colors
+    // ========================
+    let stdout_dispatch = Dispatch::new()
+        // Apply colored formatting (or JSON when LOG_FORMAT=json)
+        .format(move |out, message, record| {
+            if format == LogFormat::Json {
+                return out.finish(format_args!("{}", json_line(message, record)));
+            }
+
+            // Timestamp
+            let timestamp = timestamp();
+
+            // Retrieve source file and line number
+            let file = record.file().unwrap_or("unknown_file");
+            let line = record.line().unwrap_or(0);
+
+            // Apply color to the log level
+            let level = colors.color(record.level());
+
+            // Write the formatted log message
+            out.finish(format_args!(
+                "{} {} [{}:{}] - {}",
+                timestamp, level, file, line, message
+            ))
+        })
+        // Chain to the terminal (stdout unless redirected)
+        .chain(console);
+
+    // ========================
+    // 2. Configure File Logging without Colors
+    // ========================
+    let file_dispatch = Dispatch::new()
+        // Apply plain formatting without colors
+        .format(move |out, message, record| {
+            if format == LogFormat::Json {
+                return out.finish(format_args!("{}", json_line(message, record)));
+            }
+
+            // Timestamp
+            let timestamp = timestamp();
+
+            // Retrieve source file and line number
+            let file = record.file().unwrap_or("unknown_file");
+            let line = record.line().unwrap_or(0);
+
+            // Get the log level as plain text
+            let level = record.level();
+
+            // Write the formatted log message without color
+            out.finish(format_args!(
+                "{} {} [{}:{}] - {}",
+                timestamp, level, file, line, message
+            ))
+        });
+
+    // ========================
+    // 3. Merge Both Dispatches
+    // ========================
+    // Merge the terminal dispatch with the file dispatch, if the file opened
+    // The level and per-target overrides filter both outputs
+    let mut dispatch = level.apply(Dispatch::new()).chain(stdout_dispatch);
+    if let Some(log_file) = log_file {
+        // Chain to the rotating log file
+        dispatch = dispatch.chain(file_dispatch.chain(Box::new(log_file) as Box<dyn Write + Send>));
+    }
+    // Apply the combined configuration
+    dispatch.apply().unwrap();
+
+    if let Some(invalid_level) = invalid_level {
+        log::warn!(
+            "Ignoring invalid log level {}; falling back to info",
+            invalid_level
+        );
+    }
+    if let Some(invalid_max_size) = invalid_max_size {
+        log::warn!(
+            "Ignoring invalid log size {}; falling back to {} MB",
+            invalid_max_size,
+            DEFAULT_LOG_MAX_SIZE_MB
+        );
+    }
+    if let Some(invalid_precision) = invalid_precision {
+        log::warn!(
+            "Ignoring invalid timestamp precision {}; expected 0, 3, 6 or 9, falling back to {}",
+            invalid_precision,
+            DEFAULT_LOG_TIMESTAMP_PRECISION
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_level_sets_the_default() {
+        assert_eq!(
+            LogFilter::parse(" debug "),
+            Some(LogFilter::from(LevelFilter::Debug))
+        );
+    }
+
+    #[test]
+    fn directives_add_per_target_levels() {
+        let filter = LogFilter::parse("warn,hyper=error, reqwest::connect = debug").unwrap();
+        assert_eq!(filter.level, LevelFilter::Warn);
+        assert_eq!(
+            filter.targets,
+            vec![
+                ("hyper".to_string(), LevelFilter::Error),
+                ("reqwest::connect".to_string(), LevelFilter::Debug),
+            ]
+        );
+        // Without a bare level the default stays at info.
+        assert_eq!(
+            LogFilter::parse("hyper=warn").unwrap().level,
+            LevelFilter::Info
+        );
+    }
+
+    #[test]
+    fn bad_directive_rejects_the_whole_value() {
+        assert_eq!(LogFilter::parse("info,hyper=loud"), None);
+        assert_eq!(LogFilter::parse("=warn"), None);
+        assert_eq!(LogFilter::parse("verbose"), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! TLS trust roots shared by the REST client and the websocket connector.
+//!
+//! The bundled Mozilla roots (webpki-roots) are trusted by default. `--native-tls`
+//! or `BINANCE_NATIVE_TLS=1` switches to the OS certificate store instead, which
+//! is what corporate TLS-inspecting proxies usually install their CA into.
+//! Certificates from a PEM bundle named by `SSL_CERT_FILE` are added on top of
+//! either set.
+
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::{Arc, OnceLock};
+use tokio_tungstenite::tungstenite;
+use url::Url;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+pub const NATIVE_TLS_ENV: &str = "BINANCE_NATIVE_TLS";
+pub const CA_FILE_ENV: &str = "SSL_CERT_FILE";
+
+static CLIENT_CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+
+/// Builds and stores the process-wide TLS config (`--native-tls` flag >
+/// `BINANCE_NATIVE_TLS` > bundled roots). Call once, before the first
+/// connection; later calls keep the first value.
+pub fn init(native_flag: bool) -> Result<(), String> {
+    let native = native_flag || native_tls_from_env();
+    let config = build_client_config(native)?;
+    CLIENT_CONFIG.get_or_init(|| Arc::new(config));
+    Ok(())
+}
+
+/// The config set by `init`, or one trusting the bundled roots if `init` was
+/// never called.
+pub fn client_config() -> Arc<ClientConfig> {
+    Arc::clone(
+        CLIENT_CONFIG.get_or_init(|| Arc::new(client_config_with_roots(webpki_root_store()))),
+    )
+}
+
+fn native_tls_from_env() -> bool {
+    match env::var(NATIVE_TLS_ENV) {
+        Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" => true,
+            "" | "0" | "false" | "no" => false,
+            _ => {
+                warn!(
+                    "Invalid {} value '{}', using the bundled root certificates",
+                    NATIVE_TLS_ENV, value
+                );
+                false
+            }
+        },
+        Err(_) => false,
+    }
+}
+
+fn build_client_config(native: bool) -> Result<ClientConfig, String> {
+    let mut roots = if native {
+        native_root_store()?
+    } else {
+        webpki_root_store()
+    };
+
+    if let Some(path) = env::var(CA_FILE_ENV)
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+    {
+        let added = add_ca_file(&mut roots, &path)
+            .map_err(|e| format!("Failed to load {} ({}): {}", CA_FILE_ENV, path, e))?;
+        info!("Trusting {} certificate(s) from {}", added, path);
+    }
+
+    Ok(client_config_with_roots(roots))
+}
+
+fn client_config_with_roots(roots: RootCertStore) -> ClientConfig {
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth()
+}
+
+fn webpki_root_store() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    roots
+}
+
+fn native_root_store() -> Result<RootCertStore, String> {
+    let certs = rustls_native_certs::load_native_certs()
+        .map_err(|e| format!("Failed to load the OS certificate store: {}", e))?;
+    let certs = certs.into_iter().map(|cert| cert.0).collect::<Vec<_>>();
+    let mut roots = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(&certs);
+    if added == 0 {
+        return Err("The OS certificate store has no usable root certificates".to_string());
+    }
+    info!(
+        "Using the OS certificate store: {} root certificate(s), {} ignored",
+        added, ignored
+    );
+    Ok(roots)
+}
+
+fn add_ca_file(roots: &mut RootCertStore, path: &str) -> io::Result<usize> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader)?;
+    let (added, _) = roots.add_parsable_certificates(&certs);
+    if added == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no PEM certificates found",
+        ));
+    }
+    Ok(added)
+}
+
+/// Rewrites a failed websocket connect so TLS handshake failures and refused
+/// connections read differently in the logs; other errors pass through.
+pub fn explain_connect_error(url: &Url, err: tungstenite::Error) -> tungstenite::Error {
+    let tungstenite::Error::Io(io_err) = err else {
+        return err;
+    };
+    let host = url.host_str().unwrap_or_default();
+
+    let is_tls = io_err
+        .get_ref()
+        .is_some_and(|inner| inner.is::<rustls::Error>());
+    if is_tls {
+        return tungstenite::Error::Io(io::Error::new(
+            io_err.kind(),
+            format!(
+                "TLS handshake with {} failed: {} (if a proxy or firewall re-signs traffic, try --native-tls or {})",
+                host, io_err, CA_FILE_ENV
+            ),
+        ));
+    }
+    if io_err.kind() == io::ErrorKind::ConnectionRefused {
+        return tungstenite::Error::Io(io::Error::new(
+            io_err.kind(),
+            format!("connection to {} refused: {}", host, io_err),
+        ));
+    }
+    tungstenite::Error::Io(io_err)
+}
@@ -0,0 +1,324 @@
+use public_data_streaming::cli::{self, CommonArgs};
+use public_data_streaming::deflate;
+use public_data_streaming::handler::{self, TradeHandler};
+use public_data_streaming::models::{self, BookTickerEvent, TradeEvent};
+use public_data_streaming::proxy;
+use public_data_streaming::reconnect::{self, Backoff, ErrorClass};
+use public_data_streaming::session::{self, WsSession};
+use public_data_streaming::settings;
+use public_data_streaming::stats::RunSummary;
+use public_data_streaming::streams;
+use public_data_streaming::tls;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const DEFAULT_SYMBOLS: [&str; 3] = ["btcusdt", "ethusdt", "bnbusdt"];
+const DEFAULT_REFRESH_MS: u64 = 500;
+// ANSI: clear screen and move the cursor home before each redraw.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+struct DashboardConfig {
+    common: CommonArgs,
+    symbols: Vec<String>,
+    refresh_ms: u64,
+}
+
+/// Latest best bid/ask per symbol, redrawn on a timer rather than per message.
+#[derive(Default)]
+struct Dashboard {
+    quotes: BTreeMap<String, BookTickerEvent>,
+    updates: u64,
+}
+
+impl TradeHandler for Dashboard {
+    fn on_trade(&mut self, _trade: &TradeEvent) {}
+
+    fn on_book_ticker(&mut self, ticker: &BookTickerEvent) {
+        self.updates += 1;
+        self.quotes.insert(ticker.symbol.clone(), ticker.clone());
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        debug!("Other message: {:?}", value);
+    }
+}
+
+impl Dashboard {
+    fn render(&self, symbols: &[String], reconnects: u64) {
+        let mut out = io::stdout().lock();
+        let _ = write!(out, "{}", CLEAR_SCREEN);
+        let _ = writeln!(
+            out,
+            "{:<12} {:>16} {:>16} {:>14} {:>10}",
+            "symbol", "bid", "ask", "spread", "bps"
+        );
+        for symbol in symbols {
+            match self.quotes.get(&symbol.to_uppercase()) {
+                Some(quote) => {
+                    let spread = quote.best_ask_price - quote.best_bid_price;
+                    let _ = writeln!(
+                        out,
+                        "{:<12} {:>16} {:>16} {:>14} {:>10}",
+                        quote.symbol,
+                        quote.best_bid_price.normalize(),
+                        quote.best_ask_price.normalize(),
+                        spread.normalize(),
+                        spread_bps(quote)
+                            .map(|bps| bps.round_dp(2).to_string())
+                            .unwrap_or_else(|| "-".to_string())
+                    );
+                }
+                None => {
+                    let _ = writeln!(
+                        out,
+                        "{:<12} {:>16} {:>16} {:>14} {:>10}",
+                        symbol.to_uppercase(),
+                        "-",
+                        "-",
+                        "-",
+                        "-"
+                    );
+                }
+            }
+        }
+        let _ = writeln!(
+            out,
+            "\n{} update(s), {} reconnect(s). Ctrl+C to quit.",
+            self.updates, reconnects
+        );
+        let _ = out.flush();
+    }
+}
+
+/// Spread relative to the mid price, in basis points.
+fn spread_bps(quote: &BookTickerEvent) -> Option<Decimal> {
+    let mid = (quote.best_bid_price + quote.best_ask_price) / Decimal::TWO;
+    if mid.is_zero() {
+        return None;
+    }
+    Some((quote.best_ask_price - quote.best_bid_price) / mid * Decimal::from(10_000))
+}
+
+fn parse_args() -> Result<DashboardConfig, String> {
+    let mut common = CommonArgs::default();
+    let mut symbols = Vec::new();
+    let mut refresh_ms = DEFAULT_REFRESH_MS;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if common.parse_flag(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--symbol" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                symbols.push(
+                    streams::normalize_symbol(&value).map_err(|e| format!("--symbol: {}", e))?,
+                );
+            }
+            "--symbols" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                for symbol in value.split(',').filter(|symbol| !symbol.trim().is_empty()) {
+                    symbols.push(
+                        streams::normalize_symbol(symbol)
+                            .map_err(|e| format!("--symbols: {}", e))?,
+                    );
+                }
+            }
+            "--refresh-ms" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                refresh_ms = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| format!("Invalid --refresh-ms: {}", value))?;
+            }
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            other => {
+                return Err(format!("Unknown option: {}", other));
+            }
+        }
+    }
+
+    if symbols.is_empty() {
+        symbols = DEFAULT_SYMBOLS.iter().map(|s| s.to_string()).collect();
+    }
+    let mut seen = HashSet::new();
+    symbols.retain(|symbol| seen.insert(symbol.clone()));
+
+    Ok(DashboardConfig {
+        common,
+        symbols,
+        refresh_ms,
+    })
+}
+
+fn print_help() {
+    println!("Usage:");
+    println!("  cargo run -p public_data_streaming --bin dashboard -- [options]");
+    println!();
+    println!("Options:");
+    println!("  --symbol <symbol>   Add a symbol to the table (repeatable)");
+    println!("  --symbols <a,b,c>   Comma-separated symbols (default: btcusdt,ethusdt,bnbusdt)");
+    println!("  --refresh-ms <ms>   Redraw period in milliseconds (default: 500)");
+    cli::print_common_help();
+}
+
+fn handle_text_message(dashboard: &mut Dashboard, message: &str) {
+    match serde_json::from_str::<models::CombinedStreamEnvelope>(message) {
+        Ok(envelope) => match serde_json::from_value::<models::BinanceMessage>(envelope.data) {
+            Ok(msg) => handler::dispatch(dashboard, msg),
+            Err(e) => {
+                warn!("Failed to parse {} payload, error: {}", envelope.stream, e);
+            }
+        },
+        Err(e) => {
+            warn!(
+                "Failed to unwrap combined stream envelope: {}, error: {}",
+                message, e
+            );
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config = parse_args();
+    settings::init_logging(
+        config
+            .as_ref()
+            .ok()
+            .and_then(|config| config.common.log_file.as_deref()),
+    );
+
+    let config = match config {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("{}", err);
+            print_help();
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = proxy::init(config.common.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.common.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+    deflate::init(config.common.compress);
+
+    let ws_root = config.common.ws_root_url();
+    // Always the combined endpoint, even for one symbol, so payloads arrive enveloped.
+    let streams = config
+        .symbols
+        .iter()
+        .map(|symbol| format!("{}@bookTicker", symbol))
+        .collect::<Vec<_>>();
+    let url = format!("{}/stream?streams={}", ws_root, streams.join("/"));
+    info!("Starting bookTicker dashboard: {}", url);
+
+    let pong_interval_secs = session::resolve_pong_interval_secs(config.common.pong_interval_secs);
+    let mut dashboard = Dashboard::default();
+    let mut backoff = Backoff::new();
+    let mut summary = RunSummary::new();
+    let deadline = session::run_deadline(config.common.duration_secs);
+
+    loop {
+        let connected_at = Instant::now();
+        let mut last_error = None;
+        match WsSession::connect(&url).await {
+            Ok(session) => {
+                let mut session = session
+                    .with_stale_timeout(config.common.stale_timeout_secs)
+                    .with_pong_interval(pong_interval_secs)
+                    .with_reconnects(backoff.total_reconnects());
+                let mut refresh = tokio::time::interval(Duration::from_millis(config.refresh_ms));
+
+                let interrupted = loop {
+                    tokio::select! {
+                        _ = tokio::signal::ctrl_c() => {
+                            info!("Received Ctrl+C, shutting down.");
+                            break true;
+                        }
+                        _ = session::sleep_until(deadline) => {
+                            info!("Run duration of {}s elapsed, shutting down.", config.common.duration_secs);
+                            break true;
+                        }
+                        _ = refresh.tick() => {
+                            dashboard.render(&config.symbols, backoff.total_reconnects());
+                        }
+                        text = session.next_text() => {
+                            match text {
+                                Ok(Some(text)) => handle_text_message(&mut dashboard, &text),
+                                Ok(None) => break false,
+                                Err(e) => {
+                                    let class = ErrorClass::of(e.as_ref());
+                                    error!("WebSocket error ({}): {}", class, e);
+                                    last_error = Some(class);
+                                    break false;
+                                }
+                            }
+                        }
+                    }
+                };
+
+                summary.add_session(session.stats());
+                last_error = last_error.or(session.error_class());
+                if interrupted {
+                    session.print_stats();
+                    session.shutdown().await?;
+                    break;
+                }
+            }
+            Err(e) => {
+                let class = ErrorClass::of(e.as_ref());
+                error!("WebSocket connect error ({}): {}", class, e);
+                last_error = Some(class);
+            }
+        }
+
+        let Some(delay) = backoff.next_delay(connected_at.elapsed()) else {
+            error!(
+                "Giving up after {} consecutive reconnect attempts.",
+                backoff.max_attempts()
+            );
+            break;
+        };
+        let delay = reconnect::delay_after(last_error, delay);
+
+        warn!(
+            "Disconnected; reconnect attempt {}/{} in {:?}...",
+            backoff.attempt(),
+            backoff.max_attempts(),
+            delay
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C while disconnected, shutting down.");
+                break;
+            }
+            _ = session::sleep_until(deadline) => {
+                info!("Run duration of {}s elapsed while disconnected, shutting down.", config.common.duration_secs);
+                break;
+            }
+        }
+    }
+
+    summary.log(backoff.total_reconnects());
+    Ok(())
+}
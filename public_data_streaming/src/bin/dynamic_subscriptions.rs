@@ -1,29 +1,79 @@
-use futures::stream::{SplitSink, SplitStream};
-use futures::{SinkExt, StreamExt};
-use public_data_streaming::models;
+use futures::future;
+use public_data_streaming::candles::{CandleAggregator, WithCandles};
+use public_data_streaming::cli::{self, CommonArgs};
+use public_data_streaming::deflate;
+use public_data_streaming::handler::{self, LoggingHandler, TradeFormat, TradeHandler};
+use public_data_streaming::metrics::{self, Metrics};
+use public_data_streaming::models::{
+    self, BinanceEvent, BinanceMessage, BookTickerEvent, PartialDepthEvent, TradeEvent,
+};
+use public_data_streaming::proxy;
+use public_data_streaming::reconnect::{
+    self, ErrorClass, ReconnectPolicy, RECONNECT_POLICY_NAMES, RECONNECT_STABLE_CONNECTION_SECS,
+};
+use public_data_streaming::requests::RequestTracker;
+use public_data_streaming::session::{self, WsSession};
 use public_data_streaming::settings;
+use public_data_streaming::sink::EventSink;
+use public_data_streaming::state::{ConnectionState, ConnectionStateMachine};
+use public_data_streaming::stats::{RunSummary, StatsMode};
+use public_data_streaming::streams;
+use public_data_streaming::tls;
+use public_data_streaming::validate;
+use rust_decimal::Decimal;
 use serde_json::{json, Value};
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
 use std::error::Error;
+use std::fs;
 use std::io::{self, BufRead};
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::time::interval;
-use tokio_tungstenite::tungstenite::protocol::Message;
-use url::Url;
+use tokio::time::Instant;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
-use tokio_tungstenite::MaybeTlsStream;
-use tokio_tungstenite::WebSocketStream;
 
-const TESTNET_WS_BASE_URL: &str = "wss://testnet.binance.vision/ws";
-const MAINNET_WS_BASE_URL: &str = "wss://stream.binance.com:9443/ws";
-const STATS_INTERVAL_SECS: u64 = 5;
-const UNSOLICITED_PONG_INTERVAL_SECS: u64 = 180;
-const RECONNECT_DELAY_SECS: u64 = 3;
-
-type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+// addsub/delsub commands arriving within this window share one SUBSCRIBE/UNSUBSCRIBE frame.
+const SUBSCRIBE_BATCH_WINDOW_MS: u64 = 50;
+// Requests without a response after this long are expired and rolled back.
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+const REQUEST_TIMEOUT_CHECK_INTERVAL_SECS: u64 = 1;
+// Binance allows at most 1024 streams per connection.
+const DEFAULT_MAX_STREAMS: usize = 1024;
+// Binance disconnects clients sending more than 5 messages per second, so
+// SUBSCRIBE/UNSUBSCRIBE/LIST_SUBSCRIPTIONS frames go out at least this far apart.
+const OUTGOING_REQUEST_SPACING_MS: u64 = 200;
+// Parsed messages buffered between the shards and the handler task (`--shards`).
+const SHARD_OUTPUT_CAPACITY: usize = 10_000;
+
+struct DynamicConfig {
+    common: CommonArgs,
+    use_combined_stream: bool,
+    metrics_port: Option<u16>,
+    stats_mode: StatsMode,
+    quiet: bool,
+    /// How trade lines are rendered (`--format`).
+    format: TradeFormat,
+    /// Smaller trades are logged at debug (`--min-qty`).
+    min_qty: Option<Decimal>,
+    /// Log per-symbol volume and VWAP each stats interval (`--vwap`).
+    vwap: bool,
+    validate: bool,
+    candle_interval_ms: Option<u64>,
+    max_streams: usize,
+    /// `--reconnect` spec, already validated; parsed once per connection since
+    /// every shard needs its own policy. `None` keeps the client's default.
+    reconnect_policy: Option<String>,
+    /// File (or `-` for stdin) listing the initial streams, one per line.
+    streams_from: Option<String>,
+    /// With `streams_from`, keep streaming instead of exiting once subscribed.
+    keep_open: bool,
+    /// Websocket connections the desired streams are spread across (`--shards`).
+    shards: usize,
+}
 
 #[derive(Debug, Clone)]
 enum WebSocketCommand {
@@ -31,6 +81,10 @@ enum WebSocketCommand {
     Unsubscribe(String),
     ListLocal,
     ListServer,
+    ListPending,
+    Save(String),
+    Load(String),
+    ResetStats,
     Help,
     Quit,
 }
@@ -42,137 +96,455 @@ enum PendingRequest {
     ListServer,
 }
 
+/// A request frame waiting for its send slot under the outgoing rate limit.
+#[derive(Debug)]
+struct OutgoingRequest {
+    id: i64,
+    request: PendingRequest,
+    frame: String,
+}
+
+/// Per-connection settings applied to every `WsSession` the client opens.
+struct SessionOptions {
+    metrics: Option<Metrics>,
+    stale_timeout_secs: u64,
+    pong_interval_secs: u64,
+    stats_mode: StatsMode,
+    /// Receives every typed event for consumers running in another task.
+    event_sink: Option<EventSink>,
+    /// Log which typed variant each payload matched (`--validate`).
+    validate: bool,
+}
+
 struct DynamicWebSocket {
-    ws_url: String,
-    next_request_id: i64,
+    ws_root_url: String,
+    use_combined_stream: bool,
     desired_subscriptions: HashSet<String>,
     active_subscriptions: HashSet<String>,
-    pending_requests: HashMap<i64, PendingRequest>,
+    /// Upper bound on `desired_subscriptions`; `addsub`/`load` past it are rejected.
+    max_streams: usize,
+    pending_requests: RequestTracker<PendingRequest>,
+    /// Requests held back by the outgoing rate limit, sent oldest first.
+    outgoing_requests: VecDeque<OutgoingRequest>,
+    next_send_at: Instant,
+    batched_subscribes: Vec<String>,
+    batched_unsubscribes: Vec<String>,
+    batch_deadline: Option<Instant>,
     command_rx: mpsc::Receiver<WebSocketCommand>,
+    /// Commands that need a connection, received while disconnected; replayed
+    /// after the next connection's initial subscribe.
+    queued_commands: Vec<WebSocketCommand>,
     shutdown_requested: bool,
+    /// When `--duration` ends the run; `None` runs until `quit` or Ctrl+C.
+    run_deadline: Option<Instant>,
+    reconnects: u64,
+    reconnect_policy: Box<dyn ReconnectPolicy>,
+    /// Consecutive reconnects since the last stable connection, as passed to the policy.
+    reconnect_attempt: u32,
+    /// Exit once every desired stream has been answered (`--streams-from` without `--keep-open`).
+    exit_when_subscribed: bool,
+    /// Set when this client is shard `.0` of a `ShardRouter` (`--shards`): it stays
+    /// disconnected while it has no streams, and reports subscribes that didn't stick
+    /// on `.1` so the router can free their slots.
+    shard: Option<(usize, mpsc::UnboundedSender<RejectedStream>)>,
+    /// The last connection was closed for having no streams left, not lost.
+    parked: bool,
+    state: ConnectionStateMachine,
+    summary: RunSummary,
+    options: SessionOptions,
 }
 
 impl DynamicWebSocket {
     fn new(
-        use_testnet: bool,
+        ws_root_url: String,
+        use_combined_stream: bool,
         initial_subscriptions: Vec<String>,
+        max_streams: usize,
+        run_deadline: Option<Instant>,
         command_rx: mpsc::Receiver<WebSocketCommand>,
+        options: SessionOptions,
     ) -> Self {
-        let ws_url = if use_testnet {
-            TESTNET_WS_BASE_URL.to_string()
-        } else {
-            MAINNET_WS_BASE_URL.to_string()
-        };
-
-        let desired_subscriptions = initial_subscriptions
-            .into_iter()
-            .map(|topic| topic.to_lowercase())
-            .collect::<HashSet<_>>();
+        let desired_subscriptions = initial_subscriptions.into_iter().collect::<HashSet<_>>();
+        let mut state = ConnectionStateMachine::new();
+        if let Some(metrics) = &options.metrics {
+            state = state.with_callback(metrics.state_callback());
+        }
 
         Self {
-            ws_url,
-            next_request_id: 1,
+            ws_root_url,
+            use_combined_stream,
             desired_subscriptions,
             active_subscriptions: HashSet::new(),
-            pending_requests: HashMap::new(),
+            max_streams,
+            pending_requests: RequestTracker::new(Duration::from_secs(REQUEST_TIMEOUT_SECS)),
+            outgoing_requests: VecDeque::new(),
+            next_send_at: Instant::now(),
+            batched_subscribes: Vec::new(),
+            batched_unsubscribes: Vec::new(),
+            batch_deadline: None,
             command_rx,
+            queued_commands: Vec::new(),
             shutdown_requested: false,
+            run_deadline,
+            reconnects: 0,
+            reconnect_policy: Box::new(reconnect::FixedDelay::new(Duration::from_secs(
+                reconnect::DEFAULT_FIXED_RECONNECT_DELAY_SECS,
+            ))),
+            reconnect_attempt: 0,
+            exit_when_subscribed: false,
+            shard: None,
+            parked: false,
+            state,
+            summary: RunSummary::new(),
+            options,
         }
     }
 
-    async fn connect_and_listen(&mut self) -> Result<(), Box<dyn Error>> {
-        while !self.shutdown_requested {
-            let url = Url::parse(&self.ws_url)?;
-            info!("Connecting to WebSocket endpoint: {}", self.ws_url);
+    /// Replaces the default reconnect policy (a fixed 3s delay, retrying forever).
+    fn with_reconnect_policy(mut self, reconnect_policy: Box<dyn ReconnectPolicy>) -> Self {
+        self.reconnect_policy = reconnect_policy;
+        self
+    }
 
-            match tokio_tungstenite::connect_async(url).await {
-                Ok((ws_stream, _)) => {
-                    info!("WebSocket handshake successful.");
-                    self.active_subscriptions.clear();
-                    self.pending_requests.clear();
+    fn with_exit_when_subscribed(mut self, exit_when_subscribed: bool) -> Self {
+        self.exit_when_subscribed = exit_when_subscribed;
+        self
+    }
 
-                    let (write, read) = ws_stream.split();
-                    self.run_websocket_loop(write, read).await?;
+    /// Runs this client as shard `index` of a `ShardRouter`.
+    fn with_shard(
+        mut self,
+        index: usize,
+        rejected_tx: mpsc::UnboundedSender<RejectedStream>,
+    ) -> Self {
+        self.shard = Some((index, rejected_tx));
+        self
+    }
+
+    async fn connect_and_listen<H: TradeHandler>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<(), Box<dyn Error>> {
+        while !self.shutdown_requested {
+            if self.shard.is_some() && self.desired_subscriptions.is_empty() {
+                self.wait_for_streams(handler).await;
+                continue;
+            }
+            let ws_url = self.connect_url();
+            self.state.transition(ConnectionState::Connecting);
+            info!("Connecting to WebSocket endpoint: {}", ws_url);
+            let connected_at = Instant::now();
+
+            let last_error = match WsSession::connect(&ws_url).await {
+                Ok(session) => {
+                    self.state.transition(ConnectionState::Connected);
+                    let mut session = session
+                        .with_metrics(self.options.metrics.clone())
+                        .with_stale_timeout(self.options.stale_timeout_secs)
+                        .with_pong_interval(self.options.pong_interval_secs)
+                        .with_event_sink(self.options.event_sink.as_ref())
+                        .with_stats_mode(self.options.stats_mode)
+                        .with_reconnects(self.reconnects);
+                    self.active_subscriptions.clear();
+                    let dropped = self.pending_requests.clear();
+                    if dropped > 0 {
+                        warn!(
+                            "Dropping {} request(s) in flight on the previous connection; the desired set is re-subscribed instead.",
+                            dropped
+                        );
+                    }
+                    if !self.outgoing_requests.is_empty() {
+                        warn!(
+                            "Dropping {} request(s) still waiting for a send slot; the desired set is re-subscribed instead.",
+                            self.outgoing_requests.len()
+                        );
+                        self.outgoing_requests.clear();
+                    }
+                    // The initial SUBSCRIBE covers everything desired, batched or not.
+                    self.clear_batch();
+
+                    let result = self.run_websocket_loop(&mut session, handler).await;
+                    self.summary.add_session(session.stats());
+                    match result {
+                        Ok(()) => session.error_class(),
+                        Err(e) => {
+                            let class = ErrorClass::of(e.as_ref());
+                            error!("WebSocket error ({}): {}", class, e);
+                            Some(class)
+                        }
+                    }
                 }
                 Err(e) => {
-                    error!("WebSocket connect error: {}", e);
+                    let class = ErrorClass::of(e.as_ref());
+                    error!("WebSocket connect error ({}): {}", class, e);
+                    Some(class)
                 }
-            }
+            };
 
+            if self.parked {
+                self.parked = false;
+                self.state.transition(ConnectionState::Closed);
+                continue;
+            }
             if !self.shutdown_requested {
-                warn!("Disconnected; reconnecting in {}s...", RECONNECT_DELAY_SECS);
-                tokio::time::sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+                if connected_at.elapsed() > Duration::from_secs(RECONNECT_STABLE_CONNECTION_SECS) {
+                    self.reconnect_attempt = 0;
+                }
+                self.reconnect_attempt += 1;
+                let Some(delay) = self.reconnect_policy.next_delay(self.reconnect_attempt) else {
+                    error!(
+                        "Reconnect policy gave up after {} consecutive attempt(s).",
+                        self.reconnect_attempt - 1
+                    );
+                    break;
+                };
+                let delay = reconnect::delay_after(last_error, delay);
+                self.state.transition(ConnectionState::Reconnecting);
+                warn!(
+                    "Disconnected; reconnect attempt {} in {:?}...",
+                    self.reconnect_attempt, delay
+                );
+                // Nothing is active until the next connection re-subscribes.
+                self.active_subscriptions.clear();
+                self.reconnects += 1;
+                if let Some(metrics) = &self.options.metrics {
+                    metrics.record_reconnect();
+                }
+                self.wait_to_reconnect(delay, handler).await;
             }
         }
 
+        self.state.transition(ConnectionState::Closed);
+        self.summary.log(self.reconnects);
         Ok(())
     }
 
-    async fn run_websocket_loop(
+    /// Sleeps out the reconnect delay while still reading commands: local ones run
+    /// immediately, the rest are queued for the next connection instead of waiting
+    /// unread in the channel.
+    async fn wait_to_reconnect<H: TradeHandler>(&mut self, delay: Duration, handler: &mut H) {
+        self.wait_offline(Some(Instant::now() + delay), handler)
+            .await;
+    }
+
+    /// Keeps a shard with no streams disconnected until the router assigns it one.
+    async fn wait_for_streams<H: TradeHandler>(&mut self, handler: &mut H) {
+        info!("No streams assigned; not connecting until one is.");
+        self.wait_offline(None, handler).await;
+    }
+
+    /// Reads commands while disconnected, until `until`, or with `None` until the
+    /// first `Subscribe`, which then goes out with the connection's initial subscribe.
+    async fn wait_offline<H: TradeHandler>(&mut self, until: Option<Instant>, handler: &mut H) {
+        loop {
+            tokio::select! {
+                _ = session::sleep_until(until) => return,
+                _ = tokio::signal::ctrl_c() => {
+                    info!("Received Ctrl+C while disconnected, shutting down.");
+                    self.shutdown_requested = true;
+                    return;
+                }
+                _ = session::sleep_until(self.run_deadline) => {
+                    info!("Run duration elapsed while disconnected, shutting down.");
+                    self.shutdown_requested = true;
+                    return;
+                }
+                cmd = self.command_rx.recv() => match cmd {
+                    Some(WebSocketCommand::Subscribe(stream)) if until.is_none() => {
+                        self.desired_subscriptions.insert(stream);
+                        return;
+                    }
+                    Some(WebSocketCommand::Quit) => {
+                        info!("Quit requested while disconnected.");
+                        self.shutdown_requested = true;
+                        return;
+                    }
+                    Some(cmd) => self.handle_offline_command(cmd, handler),
+                    None => {
+                        warn!("Command channel closed; shutting down.");
+                        self.shutdown_requested = true;
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    fn handle_offline_command<H: TradeHandler>(&mut self, cmd: WebSocketCommand, handler: &mut H) {
+        match cmd {
+            WebSocketCommand::ListLocal => self.list_local_subscriptions(),
+            WebSocketCommand::ListPending => self.list_pending_requests(),
+            WebSocketCommand::Save(path) => self.save_subscriptions(&path),
+            WebSocketCommand::ResetStats => handler.reset_stats(),
+            WebSocketCommand::Help => print_dynamic_help(),
+            cmd => {
+                info!("Disconnected; queued {:?} until the next connection.", cmd);
+                self.queued_commands.push(cmd);
+            }
+        }
+    }
+
+    /// The combined endpoint takes its initial streams from the query string, so the
+    /// current desired set is baked into the URL on every (re)connect.
+    fn connect_url(&self) -> String {
+        if !self.use_combined_stream {
+            return format!("{}/ws", self.ws_root_url);
+        }
+        if self.desired_subscriptions.is_empty() {
+            return format!("{}/stream", self.ws_root_url);
+        }
+
+        let mut streams = self
+            .desired_subscriptions
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        streams.sort();
+        format!("{}/stream?streams={}", self.ws_root_url, streams.join("/"))
+    }
+
+    async fn run_websocket_loop<H: TradeHandler>(
         &mut self,
-        mut write: SplitSink<WsStream, Message>,
-        mut read: SplitStream<WsStream>,
+        session: &mut WsSession,
+        handler: &mut H,
     ) -> Result<(), Box<dyn Error>> {
-        let start_time = Instant::now();
-        let mut message_count = 0usize;
-        let mut last_message_time = Instant::now();
-        let mut print_stats_interval = interval(Duration::from_secs(STATS_INTERVAL_SECS));
-        let mut pong_interval = interval(Duration::from_secs(UNSOLICITED_PONG_INTERVAL_SECS));
-
-        self.send_subscribe_request(
-            &mut write,
-            self.desired_subscriptions.iter().cloned().collect(),
-        )
-        .await?;
+        if self.use_combined_stream {
+            self.active_subscriptions = self.desired_subscriptions.clone();
+            info!(
+                "Combined stream connected with {} stream(s) from URL.",
+                self.active_subscriptions.len()
+            );
+            self.state.transition(ConnectionState::Live);
+        } else {
+            self.state.transition(ConnectionState::Subscribing);
+            self.send_subscribe_request(
+                session,
+                self.desired_subscriptions.iter().cloned().collect(),
+            )
+            .await?;
+        }
+        self.update_live_state();
+
+        // Commands from the disconnected window go out after the initial subscribe.
+        if !self.queued_commands.is_empty() {
+            info!(
+                "Replaying {} command(s) queued while disconnected.",
+                self.queued_commands.len()
+            );
+        }
+        for cmd in std::mem::take(&mut self.queued_commands) {
+            if !self.handle_command(Some(cmd), session, handler).await? {
+                return Ok(());
+            }
+        }
+
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        let mut timeout_check =
+            tokio::time::interval(Duration::from_secs(REQUEST_TIMEOUT_CHECK_INTERVAL_SECS));
 
         loop {
+            // With nothing subscribed, silence is expected; the session then
+            // judges liveness by pinging the server instead of by messages.
+            session.set_idle(self.desired_subscriptions.is_empty());
             tokio::select! {
+                _ = &mut ctrl_c => {
+                    self.shutdown_requested = true;
+                    info!("Received Ctrl+C, shutting down.");
+                    session.print_stats();
+                    session.shutdown().await?;
+                    break;
+                }
+                _ = session::sleep_until(self.run_deadline) => {
+                    self.shutdown_requested = true;
+                    info!("Run duration elapsed, shutting down.");
+                    session.print_stats();
+                    session.shutdown().await?;
+                    break;
+                }
+                _ = timeout_check.tick() => {
+                    self.expire_pending_requests();
+                    self.update_live_state();
+                }
+                _ = session::sleep_until(self.batch_deadline) => {
+                    self.flush_batch(session).await?;
+                }
+                _ = session::sleep_until(self.next_outgoing_deadline()) => {
+                    self.send_next_outgoing(session).await?;
+                }
                 cmd = self.command_rx.recv() => {
-                    if !self.handle_command(cmd, &mut write).await? {
+                    if !self.handle_command(cmd, session, handler).await? {
                         break;
                     }
                 }
-                msg = read.next() => {
-                    if !self.handle_message(msg, &mut write, &mut message_count, &mut last_message_time).await? {
-                        break;
+                text = session.next_text() => {
+                    match text? {
+                        Some(text) => {
+                            if let Some(stream) = self.handle_text_message(&text, handler) {
+                                session.record_stream(stream);
+                            }
+                            self.update_live_state();
+                            if self.exit_when_subscribed && self.subscriptions_settled() {
+                                self.shutdown_requested = true;
+                                info!(
+                                    "All requests answered; {} stream(s) active. Exiting (pass --keep-open to keep streaming).",
+                                    self.active_subscriptions.len()
+                                );
+                                session.print_stats();
+                                session.shutdown().await?;
+                                break;
+                            }
+                            if self.shard.is_some()
+                                && self.desired_subscriptions.is_empty()
+                                && self.subscriptions_settled()
+                            {
+                                info!("No streams left on this connection; closing it until one is assigned.");
+                                self.parked = true;
+                                session.shutdown().await?;
+                                break;
+                            }
+                        }
+                        None => break,
                     }
                 }
-                _ = print_stats_interval.tick() => {
-                    self.print_stats(start_time, message_count);
-                }
-                _ = pong_interval.tick() => {
-                    self.send_unsolicited_pong(&mut write).await?;
-                }
             }
         }
 
         Ok(())
     }
 
-    async fn handle_command(
+    async fn handle_command<H: TradeHandler>(
         &mut self,
         cmd: Option<WebSocketCommand>,
-        write: &mut SplitSink<WsStream, Message>,
+        session: &mut WsSession,
+        handler: &mut H,
     ) -> Result<bool, Box<dyn Error>> {
         match cmd {
             Some(WebSocketCommand::Subscribe(stream)) => {
-                let stream = normalize_stream(&stream);
-                if !self.desired_subscriptions.insert(stream.clone()) {
+                if self.desired_subscriptions.contains(&stream) {
                     info!("Already requested: {}", stream);
                     return Ok(true);
                 }
+                if self.desired_subscriptions.len() >= self.max_streams {
+                    error!(
+                        "Cannot subscribe to {}: already at the limit of {} stream(s) per connection (--max-streams)",
+                        stream, self.max_streams
+                    );
+                    self.report_rejected(stream);
+                    return Ok(true);
+                }
+                self.desired_subscriptions.insert(stream.clone());
 
-                self.send_subscribe_request(write, vec![stream]).await?;
+                self.queue_batched(stream, true);
                 Ok(true)
             }
             Some(WebSocketCommand::Unsubscribe(stream)) => {
-                let stream = normalize_stream(&stream);
                 if !self.desired_subscriptions.remove(&stream) {
                     warn!("Stream not in desired set: {}", stream);
                     return Ok(true);
                 }
 
-                self.send_unsubscribe_request(write, vec![stream]).await?;
+                self.queue_batched(stream, false);
                 Ok(true)
             }
             Some(WebSocketCommand::ListLocal) => {
@@ -180,7 +552,23 @@ impl DynamicWebSocket {
                 Ok(true)
             }
             Some(WebSocketCommand::ListServer) => {
-                self.send_list_server_request(write).await?;
+                self.send_list_server_request(session).await?;
+                Ok(true)
+            }
+            Some(WebSocketCommand::ListPending) => {
+                self.list_pending_requests();
+                Ok(true)
+            }
+            Some(WebSocketCommand::Save(path)) => {
+                self.save_subscriptions(&path);
+                Ok(true)
+            }
+            Some(WebSocketCommand::Load(path)) => {
+                self.load_subscriptions(&path);
+                Ok(true)
+            }
+            Some(WebSocketCommand::ResetStats) => {
+                handler.reset_stats();
                 Ok(true)
             }
             Some(WebSocketCommand::Help) => {
@@ -190,7 +578,7 @@ impl DynamicWebSocket {
             Some(WebSocketCommand::Quit) => {
                 self.shutdown_requested = true;
                 info!("Quit requested; closing websocket.");
-                write.send(Message::Close(None)).await?;
+                session.shutdown().await?;
                 Ok(false)
             }
             None => {
@@ -201,93 +589,127 @@ impl DynamicWebSocket {
         }
     }
 
-    async fn handle_message(
-        &mut self,
-        msg: Option<Result<Message, tokio_tungstenite::tungstenite::Error>>,
-        write: &mut SplitSink<WsStream, Message>,
-        message_count: &mut usize,
-        last_message_time: &mut Instant,
-    ) -> Result<bool, Box<dyn Error>> {
-        match msg {
-            Some(Ok(Message::Text(text))) => {
-                self.handle_text_message(&text, message_count, last_message_time)
-                    .await;
-                Ok(true)
-            }
-            Some(Ok(Message::Ping(payload))) => {
-                info!("Received Ping, sending Pong.");
-                write.send(Message::Pong(payload)).await?;
-                Ok(true)
-            }
-            Some(Ok(Message::Pong(_))) => Ok(true),
-            Some(Ok(Message::Close(frame))) => {
-                if let Some(cf) = frame {
-                    info!("WebSocket closed: {:?}", cf);
-                } else {
-                    info!("WebSocket closed without a close frame.");
-                }
-                Ok(false)
-            }
-            Some(Err(e)) => {
-                error!("WebSocket error: {}", e);
-                Ok(false)
+    /// Writes the desired set to `path` as a sorted JSON array of stream names.
+    fn save_subscriptions(&self, path: &str) {
+        write_saved_streams(path, self.desired_subscriptions.iter().cloned().collect());
+    }
+
+    /// Subscribes to every stream in a `save` file that isn't already desired.
+    /// Streams missing from the file are left alone.
+    fn load_subscriptions(&mut self, path: &str) {
+        let Some(streams) = read_saved_streams(path) else {
+            return;
+        };
+
+        let mut added = 0usize;
+        let mut over_limit = 0usize;
+        for stream in streams {
+            if self.desired_subscriptions.contains(&stream) {
+                continue;
             }
-            None => {
-                warn!("WebSocket stream ended.");
-                Ok(false)
+            if self.desired_subscriptions.len() >= self.max_streams {
+                over_limit += 1;
+                continue;
             }
-            _ => Ok(true),
+            self.desired_subscriptions.insert(stream.clone());
+            self.queue_batched(stream, true);
+            added += 1;
+        }
+        if over_limit > 0 {
+            error!(
+                "Skipped {} stream(s) from {}: the limit is {} stream(s) per connection (--max-streams)",
+                over_limit, path, self.max_streams
+            );
+        }
+        info!(
+            "Loaded {}: {} new subscription(s), {} desired in total",
+            path,
+            added,
+            self.desired_subscriptions.len()
+        );
+    }
+
+    /// Adds a stream to the next batched frame. A subscribe and unsubscribe of the same
+    /// stream inside one window cancel out, since the server never saw the first one.
+    fn queue_batched(&mut self, stream: String, subscribe: bool) {
+        let (queue, opposite) = if subscribe {
+            (&mut self.batched_subscribes, &mut self.batched_unsubscribes)
+        } else {
+            (&mut self.batched_unsubscribes, &mut self.batched_subscribes)
+        };
+
+        if let Some(index) = opposite.iter().position(|queued| *queued == stream) {
+            opposite.remove(index);
+        } else {
+            queue.push(stream);
+        }
+
+        if self.batch_deadline.is_none() {
+            self.batch_deadline =
+                Some(Instant::now() + Duration::from_millis(SUBSCRIBE_BATCH_WINDOW_MS));
         }
     }
 
-    async fn handle_text_message(
+    async fn flush_batch(&mut self, session: &mut WsSession) -> Result<(), Box<dyn Error>> {
+        let subscribes = std::mem::take(&mut self.batched_subscribes);
+        let unsubscribes = std::mem::take(&mut self.batched_unsubscribes);
+        self.batch_deadline = None;
+
+        self.send_subscribe_request(session, subscribes).await?;
+        self.send_unsubscribe_request(session, unsubscribes).await?;
+        Ok(())
+    }
+
+    fn clear_batch(&mut self) {
+        self.batched_subscribes.clear();
+        self.batched_unsubscribes.clear();
+        self.batch_deadline = None;
+    }
+
+    /// Handles one text frame and returns the stream it belongs to, for per-stream stats.
+    fn handle_text_message<H: TradeHandler>(
         &mut self,
         message: &str,
-        message_count: &mut usize,
-        last_message_time: &mut Instant,
-    ) {
-        let now = Instant::now();
-        let time_since_last = now.duration_since(*last_message_time);
-        *last_message_time = now;
-        *message_count += 1;
-        debug!("Time since last message: {:?}", time_since_last);
-
+        handler: &mut H,
+    ) -> Option<String> {
         let value: Value = match serde_json::from_str(message) {
             Ok(value) => value,
             Err(e) => {
                 warn!("Failed to deserialize message: {}, error: {}", message, e);
-                return;
+                return None;
             }
         };
 
         if value.get("id").is_some() {
             self.handle_api_response(value);
-            return;
+            return None;
         }
 
-        match serde_json::from_value::<models::BinanceMessage>(value) {
-            Ok(models::BinanceMessage::Event(models::BinanceEvent::Trade(trade))) => {
-                info!(
-                    "Trade - Symbol: {}, Price: {}, Quantity: {}, Trade Time: {}",
-                    trade.symbol, trade.price, trade.quantity, trade.trade_time
-                );
-            }
-            Ok(models::BinanceMessage::Event(other)) => {
-                debug!("Non-trade event: {:?}", other);
-            }
-            Ok(models::BinanceMessage::Other(other)) => {
-                debug!("Other message: {:?}", other);
-            }
-            Ok(models::BinanceMessage::SubscriptionResponse { result, id }) => {
-                debug!(
-                    "Unmatched subscription response: id={}, result={:?}",
-                    id, result
-                );
+        let (stream, value) = if self.use_combined_stream {
+            match serde_json::from_value::<models::CombinedStreamEnvelope>(value) {
+                Ok(envelope) => {
+                    debug!("Combined stream payload from {}", envelope.stream);
+                    (Some(envelope.stream), envelope.data)
+                }
+                Err(e) => {
+                    warn!("Failed to unwrap combined stream envelope, error: {}", e);
+                    return None;
+                }
             }
+        } else {
+            (raw_stream_key(&value), value)
+        };
+
+        if self.options.validate {
+            validate::log_payload(stream.as_deref(), &value);
+        }
+        match serde_json::from_value::<models::BinanceMessage>(value) {
+            Ok(msg) => handler::dispatch_with_sink(handler, msg, self.options.event_sink.as_ref()),
             Err(e) => {
                 warn!("Failed to parse typed message, error: {}", e);
             }
         }
+        stream
     }
 
     fn handle_api_response(&mut self, response: Value) {
@@ -296,7 +718,7 @@ impl DynamicWebSocket {
             return;
         };
 
-        let Some(pending) = self.pending_requests.remove(&id) else {
+        let Some(pending) = self.pending_requests.resolve(id) else {
             warn!(
                 "Received response for unknown request id={}: {:?}",
                 id, response
@@ -305,20 +727,7 @@ impl DynamicWebSocket {
         };
 
         if let Some(error_obj) = response.get("error") {
-            match pending {
-                PendingRequest::Subscribe(streams) => {
-                    for stream in streams {
-                        self.desired_subscriptions.remove(&stream);
-                    }
-                }
-                PendingRequest::Unsubscribe(streams) => {
-                    for stream in streams {
-                        self.desired_subscriptions.insert(stream);
-                    }
-                }
-                PendingRequest::ListServer => {}
-            }
-
+            self.rollback_desired(pending);
             error!("Request id={} failed: {:?}", id, error_obj);
             return;
         }
@@ -353,95 +762,193 @@ impl DynamicWebSocket {
         }
     }
 
+    /// Undoes the optimistic `desired_subscriptions` change made for a request the
+    /// server rejected or never answered.
+    fn rollback_desired(&mut self, pending: PendingRequest) {
+        match pending {
+            PendingRequest::Subscribe(streams) => {
+                for stream in streams {
+                    if self.desired_subscriptions.remove(&stream) {
+                        self.report_rejected(stream);
+                    }
+                }
+            }
+            PendingRequest::Unsubscribe(streams) => {
+                for stream in streams {
+                    self.desired_subscriptions.insert(stream);
+                }
+            }
+            PendingRequest::ListServer => {}
+        }
+    }
+
+    /// Tells the `ShardRouter` (with `--shards`) that `stream` is no longer desired here.
+    fn report_rejected(&self, stream: String) {
+        if let Some((shard, rejected_tx)) = &self.shard {
+            let _ = rejected_tx.send(RejectedStream {
+                shard: *shard,
+                stream,
+            });
+        }
+    }
+
+    fn expire_pending_requests(&mut self) {
+        for (id, request) in self.pending_requests.expire() {
+            warn!(
+                "Request id={} timed out after {}s without a response: {:?}",
+                id,
+                self.pending_requests.timeout().as_secs(),
+                request
+            );
+            self.rollback_desired(request);
+        }
+    }
+
     async fn send_subscribe_request(
         &mut self,
-        write: &mut SplitSink<WsStream, Message>,
+        session: &mut WsSession,
         streams: Vec<String>,
     ) -> Result<(), Box<dyn Error>> {
         if streams.is_empty() {
             return Ok(());
         }
 
-        let id = self.next_id();
+        let id = self.pending_requests.next_id();
         let msg = json!({
             "method": "SUBSCRIBE",
-            "params": streams,
+            "params": &streams,
             "id": id
         });
-
-        write.send(Message::Text(msg.to_string())).await?;
-
-        let streams_for_state = msg
-            .get("params")
-            .and_then(Value::as_array)
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(Value::as_str)
-                    .map(str::to_string)
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        self.pending_requests
-            .insert(id, PendingRequest::Subscribe(streams_for_state.clone()));
-        info!("Sent SUBSCRIBE id={} streams={:?}", id, streams_for_state);
-
-        Ok(())
+        self.submit_request(session, id, PendingRequest::Subscribe(streams), msg)
+            .await
     }
 
     async fn send_unsubscribe_request(
         &mut self,
-        write: &mut SplitSink<WsStream, Message>,
+        session: &mut WsSession,
         streams: Vec<String>,
     ) -> Result<(), Box<dyn Error>> {
         if streams.is_empty() {
             return Ok(());
         }
 
-        let id = self.next_id();
+        let id = self.pending_requests.next_id();
         let msg = json!({
             "method": "UNSUBSCRIBE",
-            "params": streams,
+            "params": &streams,
             "id": id
         });
-
-        write.send(Message::Text(msg.to_string())).await?;
-
-        let streams_for_state = msg
-            .get("params")
-            .and_then(Value::as_array)
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(Value::as_str)
-                    .map(str::to_string)
-                    .collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        self.pending_requests
-            .insert(id, PendingRequest::Unsubscribe(streams_for_state.clone()));
-        info!("Sent UNSUBSCRIBE id={} streams={:?}", id, streams_for_state);
-
-        Ok(())
+        self.submit_request(session, id, PendingRequest::Unsubscribe(streams), msg)
+            .await
     }
 
     async fn send_list_server_request(
         &mut self,
-        write: &mut SplitSink<WsStream, Message>,
+        session: &mut WsSession,
     ) -> Result<(), Box<dyn Error>> {
-        let id = self.next_id();
+        let id = self.pending_requests.next_id();
         let msg = json!({
             "method": "LIST_SUBSCRIPTIONS",
             "id": id
         });
+        self.submit_request(session, id, PendingRequest::ListServer, msg)
+            .await
+    }
+
+    /// Sends a request right away if the rate limit allows, otherwise queues it
+    /// behind the requests already waiting.
+    async fn submit_request(
+        &mut self,
+        session: &mut WsSession,
+        id: i64,
+        request: PendingRequest,
+        msg: Value,
+    ) -> Result<(), Box<dyn Error>> {
+        let outgoing = OutgoingRequest {
+            id,
+            request,
+            frame: msg.to_string(),
+        };
+        if self.outgoing_requests.is_empty() && Instant::now() >= self.next_send_at {
+            return self.send_outgoing(session, outgoing).await;
+        }
+
+        if self.outgoing_requests.is_empty() {
+            warn!(
+                "Throttling requests to {} per second; queued id={}",
+                1000 / OUTGOING_REQUEST_SPACING_MS,
+                id
+            );
+        } else {
+            debug!(
+                "Queued id={} behind {} throttled request(s)",
+                id,
+                self.outgoing_requests.len()
+            );
+        }
+        self.outgoing_requests.push_back(outgoing);
+        Ok(())
+    }
 
-        write.send(Message::Text(msg.to_string())).await?;
-        self.pending_requests.insert(id, PendingRequest::ListServer);
-        info!("Sent LIST_SUBSCRIPTIONS id={}", id);
+    async fn send_outgoing(
+        &mut self,
+        session: &mut WsSession,
+        outgoing: OutgoingRequest,
+    ) -> Result<(), Box<dyn Error>> {
+        session.send_text(outgoing.frame).await?;
+        self.next_send_at = Instant::now() + Duration::from_millis(OUTGOING_REQUEST_SPACING_MS);
 
+        match &outgoing.request {
+            PendingRequest::Subscribe(streams) => {
+                info!("Sent SUBSCRIBE id={} streams={:?}", outgoing.id, streams)
+            }
+            PendingRequest::Unsubscribe(streams) => {
+                info!("Sent UNSUBSCRIBE id={} streams={:?}", outgoing.id, streams)
+            }
+            PendingRequest::ListServer => info!("Sent LIST_SUBSCRIPTIONS id={}", outgoing.id),
+        }
+        self.pending_requests
+            .register(outgoing.id, outgoing.request);
         Ok(())
     }
 
+    /// When the next throttled request may go out; `None` with nothing queued.
+    fn next_outgoing_deadline(&self) -> Option<Instant> {
+        (!self.outgoing_requests.is_empty()).then_some(self.next_send_at)
+    }
+
+    async fn send_next_outgoing(&mut self, session: &mut WsSession) -> Result<(), Box<dyn Error>> {
+        let Some(outgoing) = self.outgoing_requests.pop_front() else {
+            return Ok(());
+        };
+        self.send_outgoing(session, outgoing).await?;
+        if self.outgoing_requests.is_empty() {
+            info!("Throttled request queue drained.");
+        }
+        Ok(())
+    }
+
+    /// Leaves `Subscribing` once the initial subscribe (and anything queued behind
+    /// it) has been answered, confirmed or not.
+    fn update_live_state(&mut self) {
+        if self.state.state() == ConnectionState::Subscribing
+            && self.pending_requests.is_empty()
+            && self.outgoing_requests.is_empty()
+        {
+            self.state.transition(ConnectionState::Live);
+        }
+    }
+
+    /// Nothing is queued, batched or awaiting a response, and the server has
+    /// confirmed (or rejected and rolled back) every desired stream.
+    fn subscriptions_settled(&self) -> bool {
+        self.pending_requests.is_empty()
+            && self.outgoing_requests.is_empty()
+            && self.batched_subscribes.is_empty()
+            && self.batched_unsubscribes.is_empty()
+            && self.active_subscriptions == self.desired_subscriptions
+    }
+
     fn list_local_subscriptions(&self) {
         let mut desired = self
             .desired_subscriptions
@@ -461,45 +968,523 @@ impl DynamicWebSocket {
         info!("Active subscriptions: {:?}", active);
     }
 
-    fn print_stats(&self, start_time: Instant, message_count: usize) {
-        let elapsed = start_time.elapsed().as_secs_f64();
-        info!(
-            "Messages received: {}, Frequency: {:.2} msg/s",
-            message_count,
-            message_count as f64 / elapsed
+    fn list_pending_requests(&self) {
+        for outgoing in &self.outgoing_requests {
+            info!(
+                "Waiting to send (throttled) id={} request={:?}",
+                outgoing.id, outgoing.request
+            );
+        }
+        if self.pending_requests.is_empty() {
+            info!("No requests in flight.");
+            return;
+        }
+
+        for (id, age, request) in self.pending_requests.in_flight() {
+            info!(
+                "In flight id={} age={:.1}s request={:?}",
+                id,
+                age.as_secs_f64(),
+                request
+            );
+        }
+    }
+}
+
+/// What a shard hands to the consumer task that owns the real handler.
+#[allow(clippy::large_enum_variant)]
+enum ShardOutput {
+    Message(BinanceMessage),
+    ResetStats,
+}
+
+/// A subscribe the server rejected or never answered, reported by its shard.
+struct RejectedStream {
+    shard: usize,
+    stream: String,
+}
+
+/// Stands in for the real handler on each shard: clones every parsed message into
+/// the shared output channel. Never blocks the shard's socket loop; when the
+/// consumer falls behind and the channel is full, the message is dropped and counted.
+struct ForwardingHandler {
+    output_tx: mpsc::Sender<ShardOutput>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl ForwardingHandler {
+    fn forward(&self, message: BinanceMessage) {
+        if self
+            .output_tx
+            .try_send(ShardOutput::Message(message))
+            .is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl TradeHandler for ForwardingHandler {
+    fn on_trade(&mut self, trade: &TradeEvent) {
+        self.forward(BinanceMessage::Event(BinanceEvent::Trade(trade.clone())));
+    }
+
+    fn on_event(&mut self, event: &BinanceEvent) {
+        self.forward(BinanceMessage::Event(event.clone()));
+    }
+
+    fn on_book_ticker(&mut self, ticker: &BookTickerEvent) {
+        self.forward(BinanceMessage::BookTicker(ticker.clone()));
+    }
+
+    fn on_partial_depth(&mut self, depth: &PartialDepthEvent) {
+        self.forward(BinanceMessage::PartialDepth(depth.clone()));
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        self.forward(BinanceMessage::Other(value.clone()));
+    }
+}
+
+/// Tracks which shard owns each desired stream and routes commands to the shards
+/// (`--shards`). New streams go to the least-loaded shard, so shards fill up
+/// round-robin; after a `delsub` streams move until the loads differ by at most one.
+struct ShardRouter {
+    shard_txs: Vec<mpsc::Sender<WebSocketCommand>>,
+    output_tx: mpsc::Sender<ShardOutput>,
+    assignments: HashMap<String, usize>,
+    loads: Vec<usize>,
+    max_streams: usize,
+}
+
+impl ShardRouter {
+    fn new(shards: usize, max_streams: usize, output_tx: mpsc::Sender<ShardOutput>) -> Self {
+        Self {
+            shard_txs: Vec::new(),
+            output_tx,
+            assignments: HashMap::new(),
+            loads: vec![0; shards],
+            max_streams,
+        }
+    }
+
+    /// Picks a shard for `stream` and records it; `None` when every shard is full.
+    fn assign(&mut self, stream: String) -> Option<usize> {
+        let shard = (0..self.loads.len())
+            .filter(|shard| self.loads[*shard] < self.max_streams)
+            .min_by_key(|shard| (self.loads[*shard], *shard))?;
+        self.loads[shard] += 1;
+        self.assignments.insert(stream, shard);
+        Some(shard)
+    }
+
+    async fn send(&self, shard: usize, cmd: WebSocketCommand) {
+        if let Err(e) = self.shard_txs[shard].send(cmd).await {
+            warn!("Shard {} has exited; dropped {:?}", shard, e.0);
+        }
+    }
+
+    async fn broadcast(&self, cmd: WebSocketCommand) {
+        for shard in 0..self.shard_txs.len() {
+            self.send(shard, cmd.clone()).await;
+        }
+    }
+
+    async fn handle_command(&mut self, cmd: WebSocketCommand) {
+        match cmd {
+            WebSocketCommand::Subscribe(stream) => self.subscribe(stream).await,
+            WebSocketCommand::Unsubscribe(stream) => {
+                let Some(shard) = self.assignments.remove(&stream) else {
+                    warn!("Stream not in desired set: {}", stream);
+                    return;
+                };
+                self.loads[shard] -= 1;
+                self.send(shard, WebSocketCommand::Unsubscribe(stream))
+                    .await;
+                self.rebalance().await;
+            }
+            WebSocketCommand::ListLocal => {
+                for (shard, load) in self.loads.iter().enumerate() {
+                    info!("Shard {}: {} desired stream(s)", shard, load);
+                }
+                self.broadcast(WebSocketCommand::ListLocal).await;
+            }
+            WebSocketCommand::Save(path) => {
+                write_saved_streams(&path, self.assignments.keys().cloned().collect());
+            }
+            WebSocketCommand::Load(path) => {
+                let Some(streams) = read_saved_streams(&path) else {
+                    return;
+                };
+                let before = self.assignments.len();
+                for stream in streams {
+                    if !self.assignments.contains_key(&stream) {
+                        self.subscribe(stream).await;
+                    }
+                }
+                info!(
+                    "Loaded {}: {} new subscription(s), {} desired in total",
+                    path,
+                    self.assignments.len() - before,
+                    self.assignments.len()
+                );
+            }
+            WebSocketCommand::ResetStats => {
+                let _ = self.output_tx.send(ShardOutput::ResetStats).await;
+            }
+            WebSocketCommand::Help => print_dynamic_help(),
+            cmd => self.broadcast(cmd).await,
+        }
+    }
+
+    /// Frees the slot of a stream its shard couldn't subscribe, unless the stream has
+    /// been moved or removed since.
+    async fn release(&mut self, rejected: RejectedStream) {
+        if self.assignments.get(&rejected.stream) != Some(&rejected.shard) {
+            return;
+        }
+        self.assignments.remove(&rejected.stream);
+        self.loads[rejected.shard] -= 1;
+        warn!(
+            "Shard {} couldn't subscribe to {}; removed it from the desired set",
+            rejected.shard, rejected.stream
         );
+        self.rebalance().await;
     }
 
-    async fn send_unsolicited_pong(
-        &self,
-        write: &mut SplitSink<WsStream, Message>,
-    ) -> Result<(), Box<dyn Error>> {
-        debug!("Sending unsolicited pong heartbeat.");
-        write.send(Message::Pong(vec![])).await?;
-        Ok(())
+    async fn subscribe(&mut self, stream: String) {
+        if self.assignments.contains_key(&stream) {
+            info!("Already requested: {}", stream);
+            return;
+        }
+        match self.assign(stream.clone()) {
+            Some(shard) => self.send(shard, WebSocketCommand::Subscribe(stream)).await,
+            None => error!(
+                "Cannot subscribe to {}: all {} shard(s) are at the limit of {} stream(s) per connection (--max-streams)",
+                stream,
+                self.loads.len(),
+                self.max_streams
+            ),
+        }
     }
 
-    fn next_id(&mut self) -> i64 {
-        let id = self.next_request_id;
-        self.next_request_id += 1;
-        id
+    /// Moves streams from the fullest shard to the emptiest until the loads are
+    /// within one. The new shard subscribes before the old one unsubscribes, so a
+    /// moved stream may briefly be delivered twice but is never missing.
+    async fn rebalance(&mut self) {
+        loop {
+            let (Some(fullest), Some(emptiest)) = (
+                (0..self.loads.len()).max_by_key(|shard| self.loads[*shard]),
+                (0..self.loads.len()).min_by_key(|shard| self.loads[*shard]),
+            ) else {
+                return;
+            };
+            if self.loads[fullest] - self.loads[emptiest] <= 1 {
+                return;
+            }
+            let Some(stream) = self
+                .assignments
+                .iter()
+                .filter(|(_, shard)| **shard == fullest)
+                .map(|(stream, _)| stream.clone())
+                .min()
+            else {
+                return;
+            };
+
+            info!(
+                "Rebalancing {} from shard {} to shard {}",
+                stream, fullest, emptiest
+            );
+            self.assignments.insert(stream.clone(), emptiest);
+            self.loads[fullest] -= 1;
+            self.loads[emptiest] += 1;
+            self.send(emptiest, WebSocketCommand::Subscribe(stream.clone()))
+                .await;
+            self.send(fullest, WebSocketCommand::Unsubscribe(stream))
+                .await;
+        }
     }
 }
 
-fn normalize_stream(stream: &str) -> String {
-    stream.trim().to_lowercase()
+fn parse_args() -> Result<DynamicConfig, String> {
+    let mut common = CommonArgs::default();
+    let mut use_combined_stream = false;
+    let mut metrics_port = None;
+    let mut stats_mode = StatsMode::default();
+    let mut quiet = false;
+    let mut format = TradeFormat::default();
+    let mut min_qty = None;
+    let mut vwap = false;
+    let mut validate = false;
+    let mut candle_interval_ms = None;
+    let mut max_streams = DEFAULT_MAX_STREAMS;
+    let mut reconnect_policy = None;
+    let mut streams_from = None;
+    let mut keep_open = false;
+    let mut shards = 1;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if common.parse_flag(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--combined" => {
+                use_combined_stream = true;
+            }
+            "--metrics-port" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                metrics_port = Some(
+                    value
+                        .parse::<u16>()
+                        .map_err(|_| format!("Invalid --metrics-port: {}", value))?,
+                );
+            }
+            "--quiet" => {
+                quiet = true;
+            }
+            "--vwap" => {
+                vwap = true;
+            }
+            "--format" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                format = TradeFormat::parse(&value).ok_or_else(|| {
+                    format!("Invalid --format: {} (expected human, csv or json)", value)
+                })?;
+            }
+            "--min-qty" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                min_qty = Some(
+                    value
+                        .parse::<Decimal>()
+                        .ok()
+                        .filter(|qty| *qty >= Decimal::ZERO)
+                        .ok_or_else(|| format!("Invalid --min-qty: {}", value))?,
+                );
+            }
+            "--validate" => {
+                validate = true;
+            }
+            "--stats-mode" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                stats_mode = StatsMode::parse(&value).ok_or_else(|| {
+                    format!("Invalid --stats-mode: {} (expected log or table)", value)
+                })?;
+            }
+            "--candles" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                candle_interval_ms = Some(
+                    CandleAggregator::parse_interval(&value)
+                        .ok_or_else(|| format!("Invalid --candles interval: {}", value))?,
+                );
+            }
+            "--max-streams" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                max_streams = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|max| *max > 0)
+                    .ok_or_else(|| format!("Invalid --max-streams: {}", value))?;
+            }
+            "--streams-from" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                streams_from = Some(value);
+            }
+            "--keep-open" => {
+                keep_open = true;
+            }
+            "--reconnect" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                if reconnect::parse_policy(&value).is_none() {
+                    return Err(format!(
+                        "Invalid --reconnect: {}; expected one of {}",
+                        value,
+                        RECONNECT_POLICY_NAMES.join(", ")
+                    ));
+                }
+                reconnect_policy = Some(value);
+            }
+            "--shards" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                shards = value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|shards| *shards > 0)
+                    .ok_or_else(|| format!("Invalid value for --shards: {}", value))?;
+            }
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            other => {
+                return Err(format!("Unknown option: {}", other));
+            }
+        }
+    }
+
+    if keep_open && streams_from.is_none() {
+        return Err("--keep-open only applies with --streams-from".to_string());
+    }
+
+    Ok(DynamicConfig {
+        common,
+        use_combined_stream,
+        metrics_port,
+        stats_mode,
+        quiet,
+        format,
+        min_qty,
+        vwap,
+        validate,
+        candle_interval_ms,
+        max_streams,
+        reconnect_policy,
+        streams_from,
+        keep_open,
+        shards,
+    })
+}
+
+fn print_help() {
+    println!("Usage:");
+    println!("  cargo run -p public_data_streaming --bin dynamic_subscriptions -- [options]");
+    println!();
+    println!("Options:");
+    println!("  --combined          Use the combined /stream?streams= endpoint");
+    println!("  --candles <interval> Build and log local OHLCV candles from trades (e.g. 1s, 1m)");
+    println!("  --metrics-port <n>  Serve Prometheus metrics on http://0.0.0.0:<n>/metrics");
+    println!("  --stats-mode <mode> Periodic stats as a log line (log, default) or in-place table (table)");
+    println!("  --quiet             Skip per-event logs; keep stats, warnings and reconnects");
+    println!("  --format <f>        Trade lines as human (default) log lines, or csv or json rows on stdout");
+    println!("  --min-qty <q>       Log trades smaller than quantity <q> at debug instead of info");
+    println!("  --vwap              Log per-symbol traded volume and VWAP every stats interval");
+    println!("  --validate          Log the typed variant each payload matched, or why it fell through to Other");
+    println!("  --max-streams <n>   Reject addsub/load past <n> desired streams per connection (default 1024)");
+    println!("  --shards <n>        Spread the streams across <n> connections, rebalanced on addsub/delsub (default 1)");
+    println!("  --streams-from <f>  Subscribe to the streams listed in <f> (one per line, - for stdin), then exit once confirmed");
+    println!("  --keep-open         With --streams-from, keep streaming after the subscriptions are confirmed");
+    println!("  --reconnect <p>     Reconnect policy: fixed (3s, default), fixed:<secs>, exponential (1s-60s, 10 tries) or none");
+    cli::print_common_help();
+}
+
+/// Raw `/ws` payloads don't name their stream, so key them by `<symbol>@<event>`
+/// (or just the symbol for untagged payloads like bookTicker).
+fn raw_stream_key(value: &Value) -> Option<String> {
+    let symbol = value.get("s").and_then(Value::as_str)?.to_lowercase();
+    match value.get("e").and_then(Value::as_str) {
+        Some(event_type) => Some(format!("{}@{}", symbol, event_type)),
+        None => Some(symbol),
+    }
 }
 
 fn print_dynamic_help() {
     info!("Dynamic mode commands:");
     info!("  addsub <stream>    - subscribe to a stream, e.g. btcusdt@trade");
+    info!("  addsub <symbol> <kind> [args] - compose the stream, e.g. btcusdt kline 1m, btcusdt depth 20 100ms");
     info!("  delsub <stream>    - unsubscribe from a stream");
     info!("  list               - show local desired/active subscriptions");
     info!("  listserver         - query server-side active subscriptions");
+    info!("  pending            - show in-flight request ids and their ages");
+    info!("  save <file>        - write desired subscriptions to a JSON file");
+    info!("  load <file>        - subscribe to streams from a saved file not already desired");
+    info!("  reset stats        - restart the --vwap volume and VWAP totals");
     info!("  help               - show command help");
     info!("  quit               - close websocket and exit");
 }
 
+/// Writes `streams` to `path` as a sorted JSON array of stream names (`save`).
+fn write_saved_streams(path: &str, mut streams: Vec<String>) {
+    streams.sort();
+
+    let result = serde_json::to_string_pretty(&streams)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(path, json + "\n").map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => info!("Saved {} subscription(s) to {}", streams.len(), path),
+        Err(e) => error!("Failed to save subscriptions to {}: {}", path, e),
+    }
+}
+
+/// Reads a `save` file, skipping invalid entries with a warning. Logs the error
+/// and returns `None` when the file can't be read or isn't a JSON array of strings.
+fn read_saved_streams(path: &str) -> Option<Vec<String>> {
+    let streams = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str::<Vec<String>>(&json).map_err(|e| e.to_string()))
+    {
+        Ok(streams) => streams,
+        Err(e) => {
+            error!("Failed to load subscriptions from {}: {}", path, e);
+            return None;
+        }
+    };
+
+    Some(
+        streams
+            .into_iter()
+            .filter_map(|stream| match streams::normalize_stream(&stream) {
+                Ok(stream) => Some(stream),
+                Err(e) => {
+                    warn!("Skipping entry in {}: {}", path, e);
+                    None
+                }
+            })
+            .collect(),
+    )
+}
+
+/// `addsub <stream>`; prints the reason and returns `None` when invalid.
+fn normalized_stream(stream: &str) -> Option<String> {
+    match streams::normalize_stream(stream) {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
+}
+
+/// `addsub <symbol> <kind> [args]`; prints the reason and returns `None` when invalid.
+fn composed_stream(symbol: &str, kind: &str, args: &[&str]) -> Option<String> {
+    match streams::compose_stream(symbol, kind, args) {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            println!("{}", e);
+            None
+        }
+    }
+}
+
+/// Reads the `--streams-from` list: one stream per line, either a full name or
+/// `<symbol> <kind> [args]` as for `addsub`. Blank lines and `#` comments are skipped.
+fn read_stream_list(source: &str) -> Result<Vec<String>, String> {
+    let text = if source == "-" {
+        io::read_to_string(io::stdin()).map_err(|e| format!("Failed to read stdin: {}", e))?
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source, e))?
+    };
+
+    let mut streams = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let stream = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [stream] => streams::normalize_stream(stream)
+                .map_err(|e| format!("{} line {}: {}", source, index + 1, e))?,
+            [symbol, kind, args @ ..] => streams::compose_stream(symbol, kind, args)
+                .map_err(|e| format!("{} line {}: {}", source, index + 1, e))?,
+            [] => continue,
+        };
+        if !streams.contains(&stream) {
+            streams.push(stream);
+        }
+    }
+    Ok(streams)
+}
+
 fn spawn_stdin_command_reader(command_tx: mpsc::Sender<WebSocketCommand>) {
     std::thread::spawn(move || {
         let stdin = io::stdin();
@@ -511,16 +1496,26 @@ fn spawn_stdin_command_reader(command_tx: mpsc::Sender<WebSocketCommand>) {
 
             let parts = input.split_whitespace().collect::<Vec<_>>();
             let cmd = match parts.as_slice() {
-                ["addsub", stream] => Some(WebSocketCommand::Subscribe((*stream).to_string())),
-                ["delsub", stream] => Some(WebSocketCommand::Unsubscribe((*stream).to_string())),
+                ["addsub", stream] => normalized_stream(stream).map(WebSocketCommand::Subscribe),
+                ["delsub", stream] => normalized_stream(stream).map(WebSocketCommand::Unsubscribe),
+                ["addsub", symbol, kind, args @ ..] => {
+                    composed_stream(symbol, kind, args).map(WebSocketCommand::Subscribe)
+                }
+                ["delsub", symbol, kind, args @ ..] => {
+                    composed_stream(symbol, kind, args).map(WebSocketCommand::Unsubscribe)
+                }
                 ["list"] => Some(WebSocketCommand::ListLocal),
                 ["listserver"] => Some(WebSocketCommand::ListServer),
+                ["pending"] => Some(WebSocketCommand::ListPending),
+                ["save", path] => Some(WebSocketCommand::Save((*path).to_string())),
+                ["load", path] => Some(WebSocketCommand::Load((*path).to_string())),
+                ["reset", "stats"] => Some(WebSocketCommand::ResetStats),
                 ["help"] => Some(WebSocketCommand::Help),
                 ["quit"] => Some(WebSocketCommand::Quit),
                 [] => None,
                 _ => {
                     println!(
-                        "Unknown command. Try: addsub <stream>, delsub <stream>, list, listserver, help, quit"
+                        "Unknown command. Try: addsub <stream>, delsub <stream>, list, listserver, pending, save <file>, load <file>, help, quit"
                     );
                     None
                 }
@@ -542,18 +1537,254 @@ fn spawn_stdin_command_reader(command_tx: mpsc::Sender<WebSocketCommand>) {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    settings::init_logging();
+    // A bad argument is still logged, to the default console and file.
+    let config = parse_args();
+    let (format, log_file) = match &config {
+        Ok(config) => (config.format, config.common.log_file.as_deref()),
+        Err(_) => (TradeFormat::default(), None),
+    };
+    // Keep stdout clean for CSV/JSON rows by moving the terminal log to stderr.
+    settings::init_logging_to(format.log_console(), log_file);
+
+    let config = match config {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("{}", err);
+            print_help();
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = proxy::init(config.common.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.common.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+    deflate::init(config.common.compress);
 
     info!("Starting Binance Public WebSocket Client (dynamic subscriptions)...");
 
-    let use_testnet = false;
-    let initial_streams = vec!["ethusdt@trade".to_string()];
+    let initial_streams = match config.streams_from.as_deref() {
+        Some(source) => match read_stream_list(source) {
+            Ok(streams) if streams.is_empty() => {
+                error!("No streams listed in {}", source);
+                return Ok(());
+            }
+            Ok(streams) if streams.len() > config.max_streams * config.shards => {
+                error!(
+                    "{} lists {} streams; the limit is {} per connection (--max-streams) across {} connection(s) (--shards)",
+                    source,
+                    streams.len(),
+                    config.max_streams,
+                    config.shards
+                );
+                return Ok(());
+            }
+            Ok(streams) => {
+                info!("Subscribing to {} stream(s) from {}", streams.len(), source);
+                streams
+            }
+            Err(e) => {
+                error!("{}", e);
+                return Ok(());
+            }
+        },
+        None => vec!["ethusdt@trade".to_string()],
+    };
 
     let (command_tx, command_rx) = mpsc::channel(100);
-    spawn_stdin_command_reader(command_tx.clone());
+    // Piped stream lists use up stdin, so there is nothing left to read commands from.
+    if config.streams_from.as_deref() != Some("-") {
+        spawn_stdin_command_reader(command_tx.clone());
+        print_dynamic_help();
+    }
     let _command_tx_guard = command_tx;
-    print_dynamic_help();
 
-    let mut ws_client = DynamicWebSocket::new(use_testnet, initial_streams, command_rx);
-    ws_client.connect_and_listen().await
+    let ws_root_url = config.common.ws_root_url();
+
+    let metrics = config.metrics_port.map(|port| {
+        let metrics = Metrics::new();
+        metrics::spawn_server(port, metrics.clone());
+        metrics
+    });
+    let run_deadline = session::run_deadline(config.common.duration_secs);
+
+    if config.shards > 1 {
+        return run_shards(
+            config,
+            ws_root_url,
+            initial_streams,
+            command_rx,
+            metrics,
+            run_deadline,
+        )
+        .await;
+    }
+
+    let mut ws_client = new_client(
+        &config,
+        ws_root_url,
+        initial_streams,
+        command_rx,
+        metrics,
+        run_deadline,
+    );
+    let mut handler = new_handler(&config);
+    ws_client.connect_and_listen(&mut handler).await
+}
+
+fn new_client(
+    config: &DynamicConfig,
+    ws_root_url: String,
+    initial_streams: Vec<String>,
+    command_rx: mpsc::Receiver<WebSocketCommand>,
+    metrics: Option<Metrics>,
+    run_deadline: Option<Instant>,
+) -> DynamicWebSocket {
+    let mut ws_client = DynamicWebSocket::new(
+        ws_root_url,
+        config.use_combined_stream,
+        initial_streams,
+        config.max_streams,
+        run_deadline,
+        command_rx,
+        SessionOptions {
+            metrics,
+            stale_timeout_secs: config.common.stale_timeout_secs,
+            pong_interval_secs: session::resolve_pong_interval_secs(
+                config.common.pong_interval_secs,
+            ),
+            stats_mode: config.stats_mode,
+            event_sink: None,
+            validate: config.validate,
+        },
+    );
+    ws_client =
+        ws_client.with_exit_when_subscribed(config.streams_from.is_some() && !config.keep_open);
+    if let Some(reconnect_policy) = config
+        .reconnect_policy
+        .as_deref()
+        .and_then(reconnect::parse_policy)
+    {
+        ws_client = ws_client.with_reconnect_policy(reconnect_policy);
+    }
+    ws_client
+}
+
+fn new_handler(config: &DynamicConfig) -> WithCandles<LoggingHandler> {
+    WithCandles::new(
+        LoggingHandler::new(config.quiet)
+            .with_format(config.format)
+            .with_min_qty(config.min_qty)
+            .with_vwap(config.vwap),
+        config.candle_interval_ms.map(CandleAggregator::new),
+    )
+}
+
+/// `--shards`: one `DynamicWebSocket` per shard, each with its own command channel,
+/// feeding a single consumer task that runs the real handler. Stdin commands go
+/// through a `ShardRouter` instead of straight to a client.
+async fn run_shards(
+    config: DynamicConfig,
+    ws_root_url: String,
+    initial_streams: Vec<String>,
+    mut command_rx: mpsc::Receiver<WebSocketCommand>,
+    metrics: Option<Metrics>,
+    run_deadline: Option<Instant>,
+) -> Result<(), Box<dyn Error>> {
+    // An empty shard would never finish subscribing, so don't start any.
+    let shard_count = if config.streams_from.is_some() && !config.keep_open {
+        config.shards.min(initial_streams.len())
+    } else {
+        config.shards
+    };
+    info!(
+        "Spreading {} stream(s) across {} connection(s)",
+        initial_streams.len(),
+        shard_count
+    );
+
+    let (output_tx, mut output_rx) = mpsc::channel(SHARD_OUTPUT_CAPACITY);
+    let mut handler = new_handler(&config);
+    let consumer = tokio::spawn(async move {
+        while let Some(output) = output_rx.recv().await {
+            match output {
+                ShardOutput::Message(message) => handler::dispatch(&mut handler, message),
+                ShardOutput::ResetStats => handler.reset_stats(),
+            }
+        }
+    });
+
+    let mut router = ShardRouter::new(shard_count, config.max_streams, output_tx.clone());
+    let mut shard_streams = vec![Vec::new(); shard_count];
+    for stream in initial_streams {
+        if let Some(shard) = router.assign(stream.clone()) {
+            shard_streams[shard].push(stream);
+        }
+    }
+
+    let dropped = Arc::new(AtomicU64::new(0));
+    let (rejected_tx, mut rejected_rx) = mpsc::unbounded_channel();
+    let mut shards = Vec::with_capacity(shard_count);
+    for (shard, streams) in shard_streams.into_iter().enumerate() {
+        info!("Shard {}: {} initial stream(s)", shard, streams.len());
+        let (shard_tx, shard_rx) = mpsc::channel(100);
+        router.shard_txs.push(shard_tx);
+        let mut ws_client = new_client(
+            &config,
+            ws_root_url.clone(),
+            streams,
+            shard_rx,
+            metrics.clone(),
+            run_deadline,
+        )
+        .with_shard(shard, rejected_tx.clone());
+        let mut forwarder = ForwardingHandler {
+            output_tx: output_tx.clone(),
+            dropped: Arc::clone(&dropped),
+        };
+        shards.push(async move {
+            let result = ws_client.connect_and_listen(&mut forwarder).await;
+            if let Err(e) = &result {
+                error!("Shard {} stopped: {}", shard, e);
+            }
+            result
+        });
+    }
+    drop(output_tx);
+    drop(rejected_tx);
+
+    let shards = future::join_all(shards);
+    tokio::pin!(shards);
+    loop {
+        tokio::select! {
+            _ = &mut shards => break,
+            Some(rejected) = rejected_rx.recv() => router.release(rejected).await,
+            cmd = command_rx.recv() => match cmd {
+                Some(cmd) => router.handle_command(cmd).await,
+                None => {
+                    warn!("Command channel closed; shutting down.");
+                    // Closing every shard's channel shuts the shards down too.
+                    router.shard_txs.clear();
+                    (&mut shards).await;
+                    break;
+                }
+            },
+        }
+    }
+
+    // The router holds the last sender; dropping it lets the consumer drain and exit.
+    drop(router);
+    let _ = consumer.await;
+    let dropped = dropped.load(Ordering::Relaxed);
+    if dropped > 0 {
+        warn!(
+            "Dropped {} message(s) because the handler fell behind the shards.",
+            dropped
+        );
+    }
+    Ok(())
 }
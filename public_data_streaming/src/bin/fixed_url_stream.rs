@@ -1,52 +1,220 @@
-use futures::stream::SplitSink;
-use futures::{SinkExt, StreamExt};
+use public_data_streaming::candles::{CandleAggregator, WithCandles};
+use public_data_streaming::cli::{self, CommonArgs};
+use public_data_streaming::deflate;
+use public_data_streaming::handler::{self, LoggingHandler, TradeFormat, TradeHandler};
+use public_data_streaming::metrics::{self, Metrics};
 use public_data_streaming::models;
+use public_data_streaming::persistence::{CsvTradeWriter, FrameRecorder};
+use public_data_streaming::proxy;
+use public_data_streaming::reconnect::{self, Backoff, ErrorClass};
+use public_data_streaming::replay;
+use public_data_streaming::session::{self, WsSession};
 use public_data_streaming::settings;
+use public_data_streaming::sink::{self, DropPolicy, EventSink, FrameReceiver};
+use public_data_streaming::sqlite;
+use public_data_streaming::state::{ConnectionState, ConnectionStateMachine};
+use public_data_streaming::stats::{RunSummary, StatsMode};
+use public_data_streaming::streams::{
+    self, DEPTH_UPDATE_SPEEDS, EVENT_KINDS, FUTURES_ONLY_EVENT_KINDS, KLINE_INTERVALS,
+};
+use public_data_streaming::tls;
+use public_data_streaming::validate;
+use rust_decimal::Decimal;
+use serde_json::Value;
 use std::env;
 use std::error::Error;
-use std::time::{Duration, Instant};
-use tokio::time::interval;
-use tokio_tungstenite::tungstenite::protocol::Message;
-use url::Url;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
-use tokio_tungstenite::MaybeTlsStream;
-use tokio_tungstenite::WebSocketStream;
 
-const TESTNET_WS_BASE_URL: &str = "wss://testnet.binance.vision/ws";
-const MAINNET_WS_BASE_URL: &str = "wss://stream.binance.com:9443/ws";
-const STATS_INTERVAL_SECS: u64 = 5;
-const UNSOLICITED_PONG_INTERVAL_SECS: u64 = 180;
-
-type WsStream = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+// Trades buffered for the SQLite writer before new ones are dropped.
+const SQLITE_EVENT_BUFFER: usize = 10_000;
+// Raw frames queued between the socket loop and the processing task before new
+// ones are dropped.
+const FRAME_BUFFER: usize = 10_000;
 
 struct FixedConfig {
-    use_testnet: bool,
-    symbol: String,
+    common: CommonArgs,
+    streams: Vec<String>,
+    csv_path: Option<String>,
+    sqlite_path: Option<String>,
+    candle_interval_ms: Option<u64>,
+    metrics_port: Option<u16>,
+    stats_mode: StatsMode,
+    quiet: bool,
+    /// How trade lines are rendered (`--format`).
+    format: TradeFormat,
+    /// Smaller trades are logged at debug (`--min-qty`).
+    min_qty: Option<Decimal>,
+    /// Log per-symbol volume and VWAP each stats interval (`--vwap`).
+    vwap: bool,
+    /// Log which typed variant each frame matched, and why unmatched ones fell through.
+    validate: bool,
+    replay_path: Option<String>,
+    replay_realtime: bool,
+    record_path: Option<String>,
+    record_append: bool,
 }
 
-fn parse_args() -> Result<FixedConfig, String> {
-    let mut use_testnet = false;
-    let mut symbol = "ethusdt".to_string();
-
-    let args = env::args().collect::<Vec<_>>();
-    let mut i = 1usize;
+/// Logs every message and optionally mirrors trades into a CSV file.
+struct FixedHandler {
+    logging: LoggingHandler,
+    csv: Option<CsvTradeWriter>,
+}
 
-    while i < args.len() {
-        match args[i].as_str() {
-            "--testnet" => {
-                use_testnet = true;
+impl FixedHandler {
+    fn flush(&mut self) {
+        if let Some(csv) = self.csv.as_mut() {
+            if let Err(e) = csv.flush() {
+                error!("Failed to flush CSV writer: {}", e);
             }
-            "--mainnet" => {
-                use_testnet = false;
+        }
+    }
+}
+
+impl TradeHandler for FixedHandler {
+    fn on_trade(&mut self, trade: &models::TradeEvent) {
+        self.logging.on_trade(trade);
+        if let Some(csv) = self.csv.as_mut() {
+            if let Err(e) = csv.write_trade(trade) {
+                error!("Failed to write trade to CSV: {}", e);
             }
+        }
+    }
+
+    fn on_event(&mut self, event: &models::BinanceEvent) {
+        self.logging.on_event(event);
+    }
+
+    fn on_book_ticker(&mut self, ticker: &models::BookTickerEvent) {
+        self.logging.on_book_ticker(ticker);
+    }
+
+    fn on_partial_depth(&mut self, depth: &models::PartialDepthEvent) {
+        self.logging.on_partial_depth(depth);
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        self.logging.on_other(value);
+    }
+
+    fn reset_stats(&mut self) {
+        self.logging.reset_stats();
+    }
+}
+
+fn parse_args() -> Result<FixedConfig, String> {
+    let mut common = CommonArgs::default();
+    let mut streams = Vec::new();
+    let mut symbols = Vec::new();
+    let mut event_kind = "trade".to_string();
+    let mut csv_path = None;
+    let mut sqlite_path = None;
+    let mut candle_interval_ms = None;
+    let mut metrics_port = None;
+    let mut stats_mode = StatsMode::default();
+    let mut quiet = false;
+    let mut format = TradeFormat::default();
+    let mut min_qty = None;
+    let mut vwap = false;
+    let mut validate = false;
+    let mut replay_path = None;
+    let mut replay_realtime = false;
+    let mut record_path = None;
+    let mut record_append = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if common.parse_flag(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
             "--symbol" => {
-                i += 1;
-                let Some(value) = args.get(i) else {
-                    return Err("Missing value for --symbol".to_string());
-                };
-                symbol = value.to_lowercase();
+                let value = cli::next_value(&mut args, &arg)?;
+                symbols.push(
+                    streams::normalize_symbol(&value).map_err(|e| format!("--symbol: {}", e))?,
+                );
+            }
+            "--event" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                event_kind =
+                    streams::parse_event_kind(&value).map_err(|e| format!("--event: {}", e))?;
+            }
+            "--stream" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                streams.push(
+                    streams::normalize_stream(&value).map_err(|e| format!("--stream: {}", e))?,
+                );
+            }
+            "--csv" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                csv_path = Some(value);
+            }
+            "--metrics-port" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                metrics_port = Some(
+                    value
+                        .parse::<u16>()
+                        .map_err(|_| format!("Invalid --metrics-port: {}", value))?,
+                );
+            }
+            "--quiet" => {
+                quiet = true;
+            }
+            "--vwap" => {
+                vwap = true;
+            }
+            "--format" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                format = TradeFormat::parse(&value).ok_or_else(|| {
+                    format!("Invalid --format: {} (expected human, csv or json)", value)
+                })?;
+            }
+            "--min-qty" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                min_qty = Some(
+                    value
+                        .parse::<Decimal>()
+                        .ok()
+                        .filter(|qty| *qty >= Decimal::ZERO)
+                        .ok_or_else(|| format!("Invalid --min-qty: {}", value))?,
+                );
+            }
+            "--validate" => {
+                validate = true;
+            }
+            "--stats-mode" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                stats_mode = StatsMode::parse(&value).ok_or_else(|| {
+                    format!("Invalid --stats-mode: {} (expected log or table)", value)
+                })?;
+            }
+            "--replay" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                replay_path = Some(value);
+            }
+            "--replay-realtime" => {
+                replay_realtime = true;
+            }
+            "--record" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                record_path = Some(value);
+            }
+            "--record-append" => {
+                record_append = true;
+            }
+            "--sqlite" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                sqlite_path = Some(value);
+            }
+            "--candles" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                candle_interval_ms = Some(
+                    CandleAggregator::parse_interval(&value)
+                        .ok_or_else(|| format!("Invalid --candles interval: {}", value))?,
+                );
             }
             "-h" | "--help" => {
                 print_help();
@@ -56,12 +224,41 @@ fn parse_args() -> Result<FixedConfig, String> {
                 return Err(format!("Unknown option: {}", other));
             }
         }
-        i += 1;
+    }
+
+    if replay_realtime && replay_path.is_none() {
+        return Err("--replay-realtime requires --replay <file>".to_string());
+    }
+    if record_append && record_path.is_none() {
+        return Err("--record-append requires --record <file>".to_string());
+    }
+    // Composed after parsing so `--event` applies regardless of flag order.
+    let symbol_streams = symbols
+        .iter()
+        .map(|symbol| format!("{}@{}", symbol, event_kind));
+    streams.splice(0..0, symbol_streams);
+
+    if streams.is_empty() && replay_path.is_none() {
+        return Err("At least one --symbol or --stream is required".to_string());
     }
 
     Ok(FixedConfig {
-        use_testnet,
-        symbol,
+        common,
+        streams,
+        csv_path,
+        sqlite_path,
+        candle_interval_ms,
+        metrics_port,
+        stats_mode,
+        quiet,
+        format,
+        min_qty,
+        vwap,
+        validate,
+        replay_path,
+        replay_realtime,
+        record_path,
+        record_append,
     })
 }
 
@@ -70,16 +267,59 @@ fn print_help() {
     println!("  cargo run -p public_data_streaming --bin fixed_url_stream -- [options]");
     println!();
     println!("Options:");
-    println!("  --symbol <symbol>   Stream symbol (default: ethusdt)");
-    println!("  --testnet           Use spot testnet endpoint");
-    println!("  --mainnet           Use spot mainnet endpoint (default)");
+    println!("  --symbol <symbol>   Subscribe to <symbol>@<event> (repeatable)");
+    println!("  --event <kind>      Stream kind for --symbol (default: trade)");
+    println!(
+        "  --stream <stream>   Subscribe to a full stream name, e.g. btcusdt@kline_1m (repeatable)"
+    );
+    println!("  --csv <path>        Append parsed trades to a CSV file");
+    println!(
+        "  --sqlite <path>     Insert parsed trades into a SQLite database (batched, off-thread)"
+    );
+    println!("  --candles <interval> Build and log local OHLCV candles from trades (e.g. 1s, 1m)");
+    println!("  --metrics-port <n>  Serve Prometheus metrics on http://0.0.0.0:<n>/metrics");
+    println!("  --stats-mode <mode> Periodic stats as a log line (log, default) or in-place table (table)");
+    println!("  --quiet             Skip per-event logs; keep stats, warnings and reconnects");
+    println!("  --format <f>        Trade lines as human (default) log lines, or csv or json rows on stdout");
+    println!("  --min-qty <q>       Log trades smaller than quantity <q> at debug instead of info");
+    println!("  --vwap              Log per-symbol traded volume and VWAP every stats interval");
+    println!("  --validate          Log the typed variant each frame matched, or why it fell through to Other");
+    println!(
+        "  --replay <file>     Feed recorded JSON-lines through the handlers instead of connecting"
+    );
+    println!("  --replay-realtime   With --replay, wait out the event-time gaps between messages");
+    println!("  --record <file>     Write every raw frame to <file> as JSON-lines (existing file -> <file>.1)");
+    println!(
+        "  --record-append     With --record, append to an existing capture instead of rotating it"
+    );
+    cli::print_common_help();
+    println!();
+    println!("Event kinds:");
+    println!("  {}", EVENT_KINDS.join(", "));
+    println!(
+        "  kline_<interval>    Interval: {}",
+        KLINE_INTERVALS.join(", ")
+    );
+    println!(
+        "  depth<N>@<speed>    Depth kinds with update speed: {}",
+        DEPTH_UPDATE_SPEEDS.join(", ")
+    );
+    println!();
+    println!("More than one stream switches to the combined /stream?streams= endpoint.");
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    settings::init_logging();
+    // A bad argument is still logged, to the default console and file.
+    let config = parse_args();
+    let (format, log_file) = match &config {
+        Ok(config) => (config.format, config.common.log_file.as_deref()),
+        Err(_) => (TradeFormat::default(), None),
+    };
+    // Keep stdout clean for CSV/JSON rows by moving the terminal log to stderr.
+    settings::init_logging_to(format.log_console(), log_file);
 
-    let config = match parse_args() {
+    let config = match config {
         Ok(cfg) => cfg,
         Err(err) => {
             error!("{}", err);
@@ -88,113 +328,328 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
-    let ws_base = if config.use_testnet {
-        TESTNET_WS_BASE_URL
-    } else {
-        MAINNET_WS_BASE_URL
+    if let Err(err) = proxy::init(config.common.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.common.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+    deflate::init(config.common.compress);
+
+    let csv = match config.csv_path.as_deref() {
+        Some(path) => {
+            info!("Writing trades to CSV: {}", path);
+            Some(CsvTradeWriter::open(path)?)
+        }
+        None => None,
     };
+    let mut handler = WithCandles::new(
+        FixedHandler {
+            logging: LoggingHandler::new(config.quiet)
+                .with_format(config.format)
+                .with_min_qty(config.min_qty)
+                .with_vwap(config.vwap),
+            csv,
+        },
+        config.candle_interval_ms.map(CandleAggregator::new),
+    );
 
-    let stream = format!("{}@trade", config.symbol);
-    let url = format!("{}/{}", ws_base, stream);
-    let url = Url::parse(&url)?;
+    // Typed events for a consumer task running off the websocket loop.
+    let (event_sink, sqlite_writer) = match config.sqlite_path.as_deref() {
+        Some(path) => {
+            info!("Writing trades to SQLite: {}", path);
+            let conn = sqlite::open_trade_db(path)?;
+            let (sink, events) = sink::event_channel(SQLITE_EVENT_BUFFER, DropPolicy::DropNewest);
+            (Some(sink), Some(sqlite::spawn_trade_writer(conn, events)))
+        }
+        None => (None, None),
+    };
 
+    if let Some(path) = config.replay_path.as_deref() {
+        info!("Replaying messages from {}", path);
+        let count = replay::replay_file(path, config.replay_realtime, |line| {
+            if config.validate {
+                validate::log_frame(line);
+            }
+            handle_text_message(
+                &mut handler,
+                line,
+                replay::is_combined_frame(line),
+                event_sink.as_ref(),
+            );
+        })
+        .await?;
+        info!("Replay finished: {} message(s).", count);
+        handler.inner.flush();
+        finish_event_consumer(event_sink, sqlite_writer).await;
+        return Ok(());
+    }
+
+    if !config.common.market().is_futures() {
+        for stream in config.streams.iter().filter(|stream| {
+            let stream = stream.to_lowercase();
+            FUTURES_ONLY_EVENT_KINDS
+                .iter()
+                .any(|kind| stream.contains(&format!("@{}", kind.to_lowercase())))
+        }) {
+            warn!(
+                "{} is only published on futures, not {}; try --market futures",
+                stream,
+                config.common.market().name()
+            );
+        }
+    }
+
+    let use_combined_stream = config.streams.len() > 1;
+    let ws_root = config.common.ws_root_url();
+    let url = if use_combined_stream {
+        format!("{}/stream?streams={}", ws_root, config.streams.join("/"))
+    } else {
+        format!("{}/ws/{}", ws_root, config.streams[0])
+    };
     info!("Starting fixed URL stream demo: {}", url);
 
-    let (ws_stream, _) = tokio_tungstenite::connect_async(url).await?;
-    info!("WebSocket handshake successful.");
+    let mut recorder = match config.record_path.as_deref() {
+        Some(path) => {
+            info!("Recording raw frames to: {}", path);
+            Some(FrameRecorder::open(path, config.record_append)?)
+        }
+        None => None,
+    };
+
+    let metrics = config.metrics_port.map(|port| {
+        let metrics = Metrics::new();
+        metrics::spawn_server(port, metrics.clone());
+        metrics
+    });
+
+    let pong_interval_secs = session::resolve_pong_interval_secs(config.common.pong_interval_secs);
 
-    let (mut write, mut read) = ws_stream.split();
-    let start_time = Instant::now();
-    let mut message_count = 0usize;
-    let mut last_message_time = Instant::now();
-    let mut print_stats_interval = interval(Duration::from_secs(STATS_INTERVAL_SECS));
-    let mut pong_interval = interval(Duration::from_secs(UNSOLICITED_PONG_INTERVAL_SECS));
+    // The socket loop records each frame (a buffered append, so a capture never
+    // has gaps) and queues it; parsing and the handlers run on a worker task so a
+    // slow consumer sheds frames instead of stalling reads.
+    let dropped_events = event_sink.as_ref().map(EventSink::dropped_counter);
+    let (frame_sink, frames) = sink::frame_channel(FRAME_BUFFER, DropPolicy::DropNewest);
+    let worker = tokio::spawn(process_frames(
+        frames,
+        handler,
+        use_combined_stream,
+        config.validate,
+        event_sink,
+    ));
+    let mut warned_queue_full = false;
+
+    // The stream is baked into the URL, so reconnecting to it is all the resubscribe needed.
+    let mut backoff = Backoff::new();
+    let mut summary = RunSummary::new();
+    let deadline = session::run_deadline(config.common.duration_secs);
+    let mut state = ConnectionStateMachine::new();
+    if let Some(metrics) = &metrics {
+        state = state.with_callback(metrics.state_callback());
+    }
 
     loop {
-        tokio::select! {
-            msg = read.next() => {
-                if !handle_message(msg, &mut write, &mut message_count, &mut last_message_time).await? {
+        let connected_at = Instant::now();
+        let mut last_error = None;
+        state.transition(ConnectionState::Connecting);
+        match WsSession::connect(&url).await {
+            Ok(session) => {
+                state.transition(ConnectionState::Connected);
+                // There is no subscribe step: the streams come from the URL.
+                state.transition(ConnectionState::Live);
+                let mut session = session
+                    .with_metrics(metrics.clone())
+                    .with_frame_sink(Some(&frame_sink))
+                    .with_dropped_events(dropped_events.clone())
+                    .with_stale_timeout(config.common.stale_timeout_secs)
+                    .with_pong_interval(pong_interval_secs)
+                    .with_stats_mode(config.stats_mode)
+                    .with_reconnects(backoff.total_reconnects());
+                let interrupted = tokio::select! {
+                    result = session.run(|text| {
+                        if let Some(recorder) = recorder.as_mut() {
+                            if let Err(e) = recorder.record(text) {
+                                error!("Failed to record frame: {}", e);
+                            }
+                        }
+                        frame_sink.send(text.to_string());
+                        if !warned_queue_full && frame_sink.dropped_count() > 0 {
+                            warned_queue_full = true;
+                            warn!(
+                                "Processing queue is full; dropping frames until it catches up{}.",
+                                if config.sqlite_path.is_some() {
+                                    " (their trades will be missing from SQLite)"
+                                } else {
+                                    ""
+                                }
+                            );
+                        }
+                        true
+                    }) => {
+                        if let Err(e) = result {
+                            let class = ErrorClass::of(e.as_ref());
+                            error!("WebSocket error ({}): {}", class, e);
+                            last_error = Some(class);
+                        }
+                        false
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("Received Ctrl+C, shutting down.");
+                        true
+                    }
+                    _ = session::sleep_until(deadline) => {
+                        info!("Run duration of {}s elapsed, shutting down.", config.common.duration_secs);
+                        true
+                    }
+                };
+
+                summary.add_session(session.stats());
+                last_error = last_error.or(session.error_class());
+                if interrupted {
+                    session.print_stats();
+                    session.shutdown().await?;
                     break;
                 }
             }
-            _ = print_stats_interval.tick() => {
-                let elapsed = start_time.elapsed().as_secs_f64();
-                info!(
-                    "Messages received: {}, Frequency: {:.2} msg/s",
-                    message_count,
-                    message_count as f64 / elapsed
-                );
+            Err(e) => {
+                let class = ErrorClass::of(e.as_ref());
+                error!("WebSocket connect error ({}): {}", class, e);
+                last_error = Some(class);
             }
-            _ = pong_interval.tick() => {
-                debug!("Sending unsolicited pong heartbeat.");
-                write.send(Message::Pong(vec![])).await?;
+        }
+
+        let Some(delay) = backoff.next_delay(connected_at.elapsed()) else {
+            error!(
+                "Giving up after {} consecutive reconnect attempts.",
+                backoff.max_attempts()
+            );
+            break;
+        };
+        let delay = reconnect::delay_after(last_error, delay);
+
+        warn!(
+            "Disconnected; reconnect attempt {}/{} in {:?}...",
+            backoff.attempt(),
+            backoff.max_attempts(),
+            delay
+        );
+        state.transition(ConnectionState::Reconnecting);
+        if let Some(metrics) = &metrics {
+            metrics.record_reconnect();
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C while disconnected, shutting down.");
+                break;
+            }
+            _ = session::sleep_until(deadline) => {
+                info!("Run duration of {}s elapsed while disconnected, shutting down.", config.common.duration_secs);
+                break;
             }
         }
     }
 
-    Ok(())
-}
+    state.transition(ConnectionState::Closed);
 
-async fn handle_message(
-    msg: Option<Result<Message, tokio_tungstenite::tungstenite::Error>>,
-    write: &mut SplitSink<WsStream, Message>,
-    message_count: &mut usize,
-    last_message_time: &mut Instant,
-) -> Result<bool, Box<dyn Error>> {
-    match msg {
-        Some(Ok(Message::Text(text))) => {
-            handle_text_message(&text, message_count, last_message_time);
-            Ok(true)
-        }
-        Some(Ok(Message::Ping(payload))) => {
-            info!("Received Ping, sending Pong.");
-            write.send(Message::Pong(payload)).await?;
-            Ok(true)
-        }
-        Some(Ok(Message::Pong(_))) => Ok(true),
-        Some(Ok(Message::Close(frame))) => {
-            if let Some(cf) = frame {
-                info!("WebSocket closed: {:?}", cf);
+    if let Some(recorder) = recorder.as_mut() {
+        if let Err(e) = recorder.flush() {
+            error!("Failed to flush frame recording: {}", e);
+        }
+    }
+    if frame_sink.dropped_count() > 0 {
+        warn!(
+            "Dropped {} frame(s) while the processing queue was full{}.",
+            frame_sink.dropped_count(),
+            if config.sqlite_path.is_some() {
+                "; any trades they carried are missing from SQLite"
             } else {
-                info!("WebSocket closed without a close frame.");
+                ""
             }
-            Ok(false)
+        );
+    }
+    if let Some(dropped) = dropped_events.filter(|dropped| dropped.load(Ordering::Relaxed) > 0) {
+        warn!(
+            "The SQLite writer fell behind and dropped {} trade(s).",
+            dropped.load(Ordering::Relaxed)
+        );
+    }
+    // Closing the frame sink lets the worker drain its backlog and hand back the
+    // event sink, which in turn lets the SQLite writer finish.
+    drop(frame_sink);
+    let event_sink = match worker.await {
+        Ok(event_sink) => event_sink,
+        Err(e) => {
+            error!("Frame processing task failed: {}", e);
+            None
         }
-        Some(Err(e)) => {
-            error!("WebSocket error: {}", e);
-            Ok(false)
+    };
+    finish_event_consumer(event_sink, sqlite_writer).await;
+    summary.log(backoff.total_reconnects());
+    Ok(())
+}
+
+/// Handles queued frames until the frame sink is dropped, then flushes the
+/// handler's outputs and returns the event sink for `finish_event_consumer`.
+async fn process_frames(
+    mut frames: FrameReceiver,
+    mut handler: WithCandles<FixedHandler>,
+    combined: bool,
+    validate: bool,
+    event_sink: Option<EventSink>,
+) -> Option<EventSink> {
+    while let Some(text) = frames.recv().await {
+        if validate {
+            validate::log_frame(&text);
         }
-        None => {
-            warn!("WebSocket stream ended.");
-            Ok(false)
+        handle_text_message(&mut handler, &text, combined, event_sink.as_ref());
+    }
+
+    handler.inner.flush();
+    event_sink
+}
+
+/// Closes the sink so the consumer drains its backlog, then waits for it.
+async fn finish_event_consumer(
+    event_sink: Option<EventSink>,
+    writer: Option<tokio::task::JoinHandle<()>>,
+) {
+    drop(event_sink);
+    if let Some(writer) = writer {
+        if let Err(e) = writer.await {
+            error!("Event consumer task failed: {}", e);
         }
-        _ => Ok(true),
     }
 }
 
-fn handle_text_message(message: &str, message_count: &mut usize, last_message_time: &mut Instant) {
-    let now = Instant::now();
-    let time_since_last = now.duration_since(*last_message_time);
-    *last_message_time = now;
-    *message_count += 1;
-    debug!("Time since last message: {:?}", time_since_last);
+fn handle_text_message<H: TradeHandler>(
+    handler: &mut H,
+    message: &str,
+    combined: bool,
+    event_sink: Option<&EventSink>,
+) {
+    if combined {
+        match serde_json::from_str::<models::CombinedStreamEnvelope>(message) {
+            Ok(envelope) => match serde_json::from_value::<models::BinanceMessage>(envelope.data) {
+                Ok(msg) => handler::dispatch_with_sink(handler, msg, event_sink),
+                Err(e) => {
+                    warn!("Failed to parse {} payload, error: {}", envelope.stream, e);
+                }
+            },
+            Err(e) => {
+                warn!(
+                    "Failed to unwrap combined stream envelope: {}, error: {}",
+                    message, e
+                );
+            }
+        }
+        return;
+    }
 
     match serde_json::from_str::<models::BinanceMessage>(message) {
-        Ok(models::BinanceMessage::Event(models::BinanceEvent::Trade(trade))) => {
-            info!(
-                "Trade - Symbol: {}, Price: {}, Quantity: {}, Trade Time: {}",
-                trade.symbol, trade.price, trade.quantity, trade.trade_time
-            );
-        }
-        Ok(models::BinanceMessage::SubscriptionResponse { result, id }) => {
-            debug!("Subscription response: result={:?}, id={}", result, id);
-        }
-        Ok(models::BinanceMessage::Event(other)) => {
-            debug!("Non-trade event: {:?}", other);
-        }
-        Ok(models::BinanceMessage::Other(other)) => {
-            debug!("Other message: {:?}", other);
-        }
+        Ok(msg) => handler::dispatch_with_sink(handler, msg, event_sink),
         Err(e) => {
             warn!("Failed to deserialize message: {}, error: {}", message, e);
         }
@@ -0,0 +1,296 @@
+use public_data_streaming::cli::{self, CommonArgs};
+use public_data_streaming::deflate;
+use public_data_streaming::handler::{self, TradeHandler};
+use public_data_streaming::models::{self, BinanceEvent, Kline, TradeEvent};
+use public_data_streaming::proxy;
+use public_data_streaming::reconnect::{self, Backoff, ErrorClass};
+use public_data_streaming::session::{self, WsSession};
+use public_data_streaming::settings;
+use public_data_streaming::stats::RunSummary;
+use public_data_streaming::streams::{self, KLINE_INTERVALS};
+use public_data_streaming::tls;
+use serde_json::Value;
+use std::env;
+use std::error::Error;
+use std::time::Instant;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const DEFAULT_SYMBOL: &str = "btcusdt";
+const DEFAULT_INTERVAL: &str = "1m";
+
+struct KlineTradesConfig {
+    common: CommonArgs,
+    symbol: String,
+    interval: String,
+}
+
+/// Counts trades per candle and logs each closed kline next to that count.
+///
+/// The closed kline arrives just after its boundary, so trades stamped past the
+/// open candle's close time are held in `next_candle_trades` rather than being
+/// counted toward the candle that is about to close.
+#[derive(Default)]
+struct KlineTradeCounter {
+    candle_trades: u64,
+    next_candle_trades: u64,
+    /// Close time of the open candle, from the latest kline update.
+    candle_close_time: Option<u64>,
+    /// False until the first closed candle, and again after a reconnect, since
+    /// trades before connecting (or during the gap) were never seen.
+    complete: bool,
+    closed_candles: u64,
+}
+
+impl TradeHandler for KlineTradeCounter {
+    fn on_trade(&mut self, trade: &TradeEvent) {
+        match self.candle_close_time {
+            Some(close_time) if trade.trade_time > close_time => self.next_candle_trades += 1,
+            _ => self.candle_trades += 1,
+        }
+    }
+
+    fn on_event(&mut self, event: &BinanceEvent) {
+        if let BinanceEvent::Kline(kline_event) = event {
+            self.on_kline(&kline_event.kline);
+        }
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        debug!("Other message: {:?}", value);
+    }
+}
+
+impl KlineTradeCounter {
+    fn on_kline(&mut self, kline: &Kline) {
+        if !kline.is_closed {
+            self.candle_close_time = Some(kline.close_time);
+            return;
+        }
+
+        self.closed_candles += 1;
+        info!(
+            "Kline closed - Symbol: {}, Interval: {}, Open: {}, High: {}, Low: {}, Close: {}, Volume: {}, Trades seen: {}{} (Binance: {})",
+            kline.symbol,
+            kline.interval,
+            kline.open_price,
+            kline.high_price,
+            kline.low_price,
+            kline.close_price,
+            kline.base_asset_volume,
+            self.candle_trades,
+            if self.complete { "" } else { " (partial)" },
+            kline.number_of_trades
+        );
+
+        // Start the next candle with the trades already stamped past this boundary.
+        self.candle_trades = std::mem::take(&mut self.next_candle_trades);
+        self.candle_close_time = None;
+        self.complete = true;
+    }
+
+    fn reset_after_reconnect(&mut self) {
+        self.candle_trades = 0;
+        self.next_candle_trades = 0;
+        self.candle_close_time = None;
+        self.complete = false;
+    }
+}
+
+fn parse_args() -> Result<KlineTradesConfig, String> {
+    let mut common = CommonArgs::default();
+    let mut symbol = DEFAULT_SYMBOL.to_string();
+    let mut interval = DEFAULT_INTERVAL.to_string();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if common.parse_flag(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--symbol" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                symbol =
+                    streams::normalize_symbol(&value).map_err(|e| format!("--symbol: {}", e))?;
+            }
+            "--interval" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                if !KLINE_INTERVALS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "Invalid --interval: {}; expected one of: {}",
+                        value,
+                        KLINE_INTERVALS.join(", ")
+                    ));
+                }
+                interval = value;
+            }
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            other => {
+                return Err(format!("Unknown option: {}", other));
+            }
+        }
+    }
+
+    Ok(KlineTradesConfig {
+        common,
+        symbol,
+        interval,
+    })
+}
+
+fn print_help() {
+    println!("Usage:");
+    println!("  cargo run -p public_data_streaming --bin kline_trades -- [options]");
+    println!();
+    println!("Options:");
+    println!("  --symbol <symbol>   Symbol to follow (default: btcusdt)");
+    println!("  --interval <i>      Kline interval, e.g. 1m, 5m, 1h (default: 1m)");
+    cli::print_common_help();
+}
+
+fn handle_text_message(counter: &mut KlineTradeCounter, message: &str) {
+    match serde_json::from_str::<models::CombinedStreamEnvelope>(message) {
+        Ok(envelope) => match serde_json::from_value::<models::BinanceMessage>(envelope.data) {
+            Ok(msg) => handler::dispatch(counter, msg),
+            Err(e) => {
+                warn!("Failed to parse {} payload, error: {}", envelope.stream, e);
+            }
+        },
+        Err(e) => {
+            warn!(
+                "Failed to unwrap combined stream envelope: {}, error: {}",
+                message, e
+            );
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config = parse_args();
+    settings::init_logging(
+        config
+            .as_ref()
+            .ok()
+            .and_then(|config| config.common.log_file.as_deref()),
+    );
+
+    let config = match config {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("{}", err);
+            print_help();
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = proxy::init(config.common.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.common.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+    deflate::init(config.common.compress);
+
+    let ws_root = config.common.ws_root_url();
+    let url = format!(
+        "{root}/stream?streams={symbol}@trade/{symbol}@kline_{interval}",
+        root = ws_root,
+        symbol = config.symbol,
+        interval = config.interval
+    );
+    info!("Starting kline + trade stream: {}", url);
+
+    let pong_interval_secs = session::resolve_pong_interval_secs(config.common.pong_interval_secs);
+    let mut counter = KlineTradeCounter::default();
+    let mut backoff = Backoff::new();
+    let mut summary = RunSummary::new();
+    let deadline = session::run_deadline(config.common.duration_secs);
+
+    loop {
+        let connected_at = Instant::now();
+        let mut last_error = None;
+        match WsSession::connect(&url).await {
+            Ok(session) => {
+                let mut session = session
+                    .with_stale_timeout(config.common.stale_timeout_secs)
+                    .with_pong_interval(pong_interval_secs)
+                    .with_reconnects(backoff.total_reconnects());
+                let interrupted = tokio::select! {
+                    result = session.run(|text| {
+                        handle_text_message(&mut counter, text);
+                        true
+                    }) => {
+                        if let Err(e) = result {
+                            let class = ErrorClass::of(e.as_ref());
+                            error!("WebSocket error ({}): {}", class, e);
+                            last_error = Some(class);
+                        }
+                        false
+                    }
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("Received Ctrl+C, shutting down.");
+                        true
+                    }
+                    _ = session::sleep_until(deadline) => {
+                        info!("Run duration of {}s elapsed, shutting down.", config.common.duration_secs);
+                        true
+                    }
+                };
+
+                summary.add_session(session.stats());
+                last_error = last_error.or(session.error_class());
+                if interrupted {
+                    session.print_stats();
+                    session.shutdown().await?;
+                    break;
+                }
+            }
+            Err(e) => {
+                let class = ErrorClass::of(e.as_ref());
+                error!("WebSocket connect error ({}): {}", class, e);
+                last_error = Some(class);
+            }
+        }
+
+        // Trades sent while disconnected are gone, so the open candle's count is partial.
+        counter.reset_after_reconnect();
+
+        let Some(delay) = backoff.next_delay(connected_at.elapsed()) else {
+            error!(
+                "Giving up after {} consecutive reconnect attempts.",
+                backoff.max_attempts()
+            );
+            break;
+        };
+        let delay = reconnect::delay_after(last_error, delay);
+
+        warn!(
+            "Disconnected; reconnect attempt {}/{} in {:?}...",
+            backoff.attempt(),
+            backoff.max_attempts(),
+            delay
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C while disconnected, shutting down.");
+                break;
+            }
+            _ = session::sleep_until(deadline) => {
+                info!("Run duration of {}s elapsed while disconnected, shutting down.", config.common.duration_secs);
+                break;
+            }
+        }
+    }
+
+    summary.log(backoff.total_reconnects());
+    info!("Closed {} candle(s).", counter.closed_candles);
+    Ok(())
+}
@@ -0,0 +1,374 @@
+use public_data_streaming::cli::{self, CommonArgs};
+use public_data_streaming::deflate;
+use public_data_streaming::endpoints::{self, REST_URL_ENV};
+use public_data_streaming::models::{self, DepthSnapshot};
+use public_data_streaming::order_book::OrderBookSync;
+use public_data_streaming::proxy;
+use public_data_streaming::reconnect::{self, Backoff, ErrorClass};
+use public_data_streaming::rest;
+use public_data_streaming::session::{self, WsSession};
+use public_data_streaming::settings;
+use public_data_streaming::stats::RunSummary;
+use public_data_streaming::streams;
+use public_data_streaming::tls;
+use std::env;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const DEFAULT_SNAPSHOT_LIMIT: u16 = 1000;
+/// Delay before refetching a snapshot that failed or didn't line up with the
+/// buffered diffs; doubles on each consecutive miss up to `SNAPSHOT_RETRY_MAX`.
+const SNAPSHOT_RETRY_INITIAL: Duration = Duration::from_millis(250);
+const SNAPSHOT_RETRY_MAX: Duration = Duration::from_secs(10);
+
+type SnapshotFuture<'a> = dyn Future<Output = Result<DepthSnapshot, Box<dyn Error>>> + 'a;
+
+struct OrderBookConfig {
+    common: CommonArgs,
+    rest_url: Option<String>,
+    symbol: String,
+    limit: u16,
+    /// `@depth` cadence such as `100ms`; `None` keeps the market's default.
+    update_speed: Option<String>,
+}
+
+fn parse_args() -> Result<OrderBookConfig, String> {
+    let mut common = CommonArgs::default();
+    let mut rest_url = None;
+    let mut symbol = "btcusdt".to_string();
+    let mut limit = DEFAULT_SNAPSHOT_LIMIT;
+    let mut update_speed = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if common.parse_flag(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--rest-url" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                rest_url = Some(value);
+            }
+            "--limit" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                limit = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value for --limit: {}", value))?;
+                if !rest::DEPTH_SNAPSHOT_LIMITS.contains(&limit) {
+                    return Err(format!(
+                        "Unsupported --limit {}; expected one of {:?}",
+                        limit,
+                        rest::DEPTH_SNAPSHOT_LIMITS
+                    ));
+                }
+            }
+            "--speed" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                update_speed = Some(value);
+            }
+            "--symbol" => {
+                let value = cli::next_value(&mut args, &arg)?;
+                symbol =
+                    streams::normalize_symbol(&value).map_err(|e| format!("--symbol: {}", e))?;
+            }
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            other => {
+                return Err(format!("Unknown option: {}", other));
+            }
+        }
+    }
+
+    // Speeds differ per market, so validate once the market is settled.
+    streams::depth_diff_stream(&symbol, common.market(), update_speed.as_deref())?;
+
+    Ok(OrderBookConfig {
+        common,
+        rest_url,
+        symbol,
+        limit,
+        update_speed,
+    })
+}
+
+fn print_help() {
+    println!("Usage:");
+    println!("  cargo run -p public_data_streaming --bin order_book -- [options]");
+    println!();
+    println!("Options:");
+    println!("  --symbol <symbol>   Order book symbol (default: btcusdt)");
+    println!("  --limit <n>         Snapshot depth: 5, 10, 20, 50, 100, 500, 1000 (default: 1000)");
+    println!("  --speed <ms>        Depth update speed: spot 1000ms (default) or 100ms; futures 250ms (default), 500ms or 100ms");
+    println!("  --rest-url <url>    REST root URL (overrides BINANCE_REST_URL and --market)");
+    cli::print_common_help();
+}
+
+fn log_top_of_book(symbol: &str, sync: &OrderBookSync) {
+    let Some(book) = sync.book() else {
+        return;
+    };
+
+    match (book.best_bid(), book.best_ask()) {
+        (Some((bid, bid_qty)), Some((ask, ask_qty))) => {
+            info!(
+                "Top of book - Symbol: {}, Bid: {} ({}), Ask: {} ({}), Spread: {}, Update ID: {}",
+                symbol,
+                bid,
+                bid_qty,
+                ask,
+                ask_qty,
+                ask - bid,
+                book.last_update_id
+            );
+        }
+        _ => {
+            warn!("Order book for {} has an empty side.", symbol);
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config = parse_args();
+    settings::init_logging(
+        config
+            .as_ref()
+            .ok()
+            .and_then(|config| config.common.log_file.as_deref()),
+    );
+
+    let config = match config {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("{}", err);
+            print_help();
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = proxy::init(config.common.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.common.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+    deflate::init(config.common.compress);
+
+    let ws_root = config.common.ws_root_url();
+    let rest_base = endpoints::resolve_url(
+        config.rest_url.as_deref(),
+        REST_URL_ENV,
+        config.common.market().rest_base(),
+    );
+
+    let (stream, update_speed) = streams::depth_diff_stream(
+        &config.symbol,
+        config.common.market(),
+        config.update_speed.as_deref(),
+    )?;
+    let url = format!("{}/ws/{}", ws_root, stream);
+    info!("Starting order book demo: {}", url);
+    info!(
+        "Depth updates every {} on {}{}",
+        update_speed,
+        config.common.market().name(),
+        if config.update_speed.is_none() {
+            " (default)"
+        } else {
+            ""
+        }
+    );
+
+    let pong_interval_secs = session::resolve_pong_interval_secs(config.common.pong_interval_secs);
+    let mut sync = OrderBookSync::new();
+    let mut backoff = Backoff::new();
+    let mut summary = RunSummary::new();
+    let deadline = session::run_deadline(config.common.duration_secs);
+
+    loop {
+        let connected_at = Instant::now();
+        let mut last_error = None;
+        match WsSession::connect(&url).await {
+            Ok(session) => {
+                let mut session = session
+                    .with_stale_timeout(config.common.stale_timeout_secs)
+                    .with_pong_interval(pong_interval_secs)
+                    .with_reconnects(backoff.total_reconnects());
+                let interrupted =
+                    match follow_book(&mut session, &mut sync, &config, &rest_base, deadline).await
+                    {
+                        Ok(interrupted) => interrupted,
+                        Err(e) => {
+                            let class = ErrorClass::of(e.as_ref());
+                            error!("Order book error ({}): {}", class, e);
+                            last_error = Some(class);
+                            false
+                        }
+                    };
+
+                summary.add_session(session.stats());
+                last_error = last_error.or(session.error_class());
+                if interrupted {
+                    session.print_stats();
+                    session.shutdown().await?;
+                    break;
+                }
+            }
+            Err(e) => {
+                let class = ErrorClass::of(e.as_ref());
+                error!("WebSocket connect error ({}): {}", class, e);
+                last_error = Some(class);
+            }
+        }
+
+        // Diffs sent while disconnected are gone, so the book and any buffered diffs
+        // are stale; the next connection buffers afresh and fetches a new snapshot.
+        if sync.is_synced() {
+            info!(
+                "Discarding the order book for {}; it will be rebuilt from a new snapshot.",
+                config.symbol
+            );
+        }
+        sync.reset();
+
+        let Some(delay) = backoff.next_delay(connected_at.elapsed()) else {
+            error!(
+                "Giving up after {} consecutive reconnect attempts.",
+                backoff.max_attempts()
+            );
+            break;
+        };
+        let delay = reconnect::delay_after(last_error, delay);
+
+        warn!(
+            "Disconnected; reconnect attempt {}/{} in {:?}...",
+            backoff.attempt(),
+            backoff.max_attempts(),
+            delay
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C while disconnected, shutting down.");
+                break;
+            }
+            _ = session::sleep_until(deadline) => {
+                info!("Run duration of {}s elapsed while disconnected, shutting down.", config.common.duration_secs);
+                break;
+            }
+        }
+    }
+
+    summary.log(backoff.total_reconnects());
+    info!(
+        "Order book resyncs after depth gaps: {}",
+        sync.resyncs_total()
+    );
+    Ok(())
+}
+
+/// Reads depth diffs from one connection into `sync`, fetching a snapshot whenever
+/// it isn't synced. At most one snapshot request is in flight, and diffs keep
+/// being buffered while it is; a snapshot that fails or doesn't line up with the
+/// buffer is retried after a doubling delay rather than on the next diff.
+/// Returns `true` when the run was stopped (Ctrl+C or `--duration`) and `false`
+/// when the connection ended.
+async fn follow_book(
+    session: &mut WsSession,
+    sync: &mut OrderBookSync,
+    config: &OrderBookConfig,
+    rest_base: &str,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<bool, Box<dyn Error>> {
+    let mut snapshot_request: Option<Pin<Box<SnapshotFuture<'_>>>> = None;
+    let mut retry_delay = SNAPSHOT_RETRY_INITIAL;
+    let mut next_attempt = tokio::time::Instant::now();
+
+    loop {
+        let update = tokio::select! {
+            text = session.next_text() => {
+                let Some(text) = text? else {
+                    return Ok(false);
+                };
+                match serde_json::from_str::<models::BinanceMessage>(&text) {
+                    Ok(models::BinanceMessage::Event(models::BinanceEvent::DepthUpdate(update))) => Some(update),
+                    Ok(other) => {
+                        debug!("Ignoring non-depth message: {:?}", other);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("Failed to deserialize message: {}, error: {}", text, e);
+                        None
+                    }
+                }
+            }
+            result = async { snapshot_request.as_mut().unwrap().await }, if snapshot_request.is_some() => {
+                snapshot_request = None;
+                let synced = match result {
+                    Ok(snapshot) => {
+                        info!(
+                            "Fetched depth snapshot lastUpdateId={}",
+                            snapshot.last_update_id
+                        );
+                        let synced = sync.on_snapshot(&snapshot);
+                        if !synced {
+                            warn!("Snapshot doesn't line up with the buffered diffs.");
+                        }
+                        synced
+                    }
+                    Err(e) => {
+                        warn!("Depth snapshot request failed: {}", e);
+                        false
+                    }
+                };
+                if synced {
+                    info!("Order book synced for {}.", config.symbol);
+                    log_top_of_book(&config.symbol, sync);
+                    retry_delay = SNAPSHOT_RETRY_INITIAL;
+                } else {
+                    info!("Fetching another depth snapshot in {:?}.", retry_delay);
+                    next_attempt = tokio::time::Instant::now() + retry_delay;
+                    retry_delay = (retry_delay * 2).min(SNAPSHOT_RETRY_MAX);
+                }
+                None
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("Received Ctrl+C, shutting down.");
+                return Ok(true);
+            }
+            _ = session::sleep_until(deadline) => {
+                info!("Run duration of {}s elapsed, shutting down.", config.common.duration_secs);
+                return Ok(true);
+            }
+        };
+
+        // Until synced, `on_diff` only buffers; frames keep arriving while a
+        // snapshot request is in flight and are replayed once it lands.
+        if let Some(update) = update {
+            if sync.on_diff(update) {
+                log_top_of_book(&config.symbol, sync);
+            }
+        }
+
+        if !sync.is_synced()
+            && snapshot_request.is_none()
+            && tokio::time::Instant::now() >= next_attempt
+        {
+            snapshot_request = Some(Box::pin(rest::fetch_depth_snapshot(
+                rest_base,
+                config.common.market(),
+                &config.symbol,
+                config.limit,
+            )));
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use public_data_streaming::candles::CandleAggregator;
+use public_data_streaming::endpoints::{self, Market, MARKET_NAMES, REST_URL_ENV};
+use public_data_streaming::handler::{LoggingHandler, TradeFormat, TradeHandler};
+use public_data_streaming::models::{BinanceEvent, KlineEvent};
+use public_data_streaming::proxy;
+use public_data_streaming::rest;
+use public_data_streaming::settings;
+use public_data_streaming::streams::{self, KLINE_INTERVALS};
+use public_data_streaming::tls;
+use std::env;
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+const DEFAULT_SYMBOL: &str = "btcusdt";
+const DEFAULT_TRADES_LIMIT: u16 = 500;
+const DEFAULT_KLINES_LIMIT: usize = 500;
+const DEFAULT_AGG_TRADES_LIMIT: usize = 500;
+// Range fetched by --agg-trades without --start.
+const DEFAULT_AGG_TRADES_SPAN_MS: u64 = 60 * 60 * 1000;
+
+struct SnapshotConfig {
+    market: Market,
+    rest_url: Option<String>,
+    proxy: Option<String>,
+    native_tls: bool,
+    symbol: String,
+    /// How trade lines are rendered (`--format`).
+    format: TradeFormat,
+    /// Fetch klines of this interval instead of recent trades.
+    kline_interval: Option<String>,
+    /// Fetch aggregate trades over `start_ms..=end_ms` instead of recent trades.
+    agg_trades: bool,
+    /// Range in epoch ms. For klines, no start means `limit` candles back from `end_ms`;
+    /// for aggregate trades, the hour before `end_ms`.
+    start_ms: Option<u64>,
+    end_ms: Option<u64>,
+    limit: Option<usize>,
+    /// `None` leaves it to `LOG_FILE`, then `output.log`.
+    log_file: Option<String>,
+}
+
+fn parse_args() -> Result<SnapshotConfig, String> {
+    let mut market = Market::default();
+    let mut testnet = None;
+    let mut rest_url = None;
+    let mut proxy = None;
+    let mut native_tls = false;
+    let mut symbol = DEFAULT_SYMBOL.to_string();
+    let mut format = TradeFormat::default();
+    let mut kline_interval = None;
+    let mut agg_trades = false;
+    let mut start_ms = None;
+    let mut end_ms = None;
+    let mut limit = None;
+    let mut log_file = None;
+
+    let args = env::args().collect::<Vec<_>>();
+    let mut i = 1usize;
+
+    while i < args.len() {
+        match args[i].as_str() {
+            "--testnet" => {
+                testnet = Some(true);
+            }
+            "--mainnet" => {
+                testnet = Some(false);
+            }
+            "--market" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --market".to_string());
+                };
+                market = Market::parse(value).ok_or_else(|| {
+                    format!(
+                        "Invalid --market: {}; expected one of {}",
+                        value,
+                        MARKET_NAMES.join(", ")
+                    )
+                })?;
+            }
+            "--symbol" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --symbol".to_string());
+                };
+                symbol =
+                    streams::normalize_symbol(value).map_err(|e| format!("--symbol: {}", e))?;
+            }
+            "--limit" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --limit".to_string());
+                };
+                limit = Some(
+                    value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or_else(|| format!("Invalid value for --limit: {}", value))?,
+                );
+            }
+            "--klines" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --klines".to_string());
+                };
+                if !KLINE_INTERVALS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "Invalid --klines interval: {}; expected one of: {}",
+                        value,
+                        KLINE_INTERVALS.join(", ")
+                    ));
+                }
+                kline_interval = Some(value.clone());
+            }
+            "--agg-trades" => {
+                agg_trades = true;
+            }
+            "--start" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --start".to_string());
+                };
+                start_ms = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("Invalid --start: {}", value))?,
+                );
+            }
+            "--end" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --end".to_string());
+                };
+                end_ms = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("Invalid --end: {}", value))?,
+                );
+            }
+            "--format" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --format".to_string());
+                };
+                format = TradeFormat::parse(value).ok_or_else(|| {
+                    format!("Invalid --format: {} (expected human, csv or json)", value)
+                })?;
+            }
+            "--native-tls" => {
+                native_tls = true;
+            }
+            "--proxy" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --proxy".to_string());
+                };
+                proxy = Some(value.clone());
+            }
+            "--rest-url" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --rest-url".to_string());
+                };
+                rest_url = Some(value.clone());
+            }
+            "--log-file" => {
+                i += 1;
+                let Some(value) = args.get(i) else {
+                    return Err("Missing value for --log-file".to_string());
+                };
+                log_file = Some(value.clone());
+            }
+            "-h" | "--help" => {
+                print_help();
+                std::process::exit(0);
+            }
+            other => {
+                return Err(format!("Unknown option: {}", other));
+            }
+        }
+        i += 1;
+    }
+
+    if let Some(testnet) = testnet {
+        market = market.with_testnet(testnet);
+    }
+
+    if agg_trades && kline_interval.is_some() {
+        return Err("--agg-trades and --klines can't be combined".to_string());
+    }
+    if start_ms.zip(end_ms).is_some_and(|(start, end)| start > end) {
+        return Err("--start must not be after --end".to_string());
+    }
+    if kline_interval.is_none() && !agg_trades {
+        if start_ms.is_some() || end_ms.is_some() {
+            return Err("--start and --end only apply with --klines or --agg-trades".to_string());
+        }
+        if limit.is_some_and(|limit| limit > usize::from(rest::MAX_RECENT_TRADES)) {
+            return Err(format!(
+                "Unsupported --limit for trades; expected 1-{}",
+                rest::MAX_RECENT_TRADES
+            ));
+        }
+    }
+
+    Ok(SnapshotConfig {
+        market,
+        rest_url,
+        proxy,
+        native_tls,
+        symbol,
+        format,
+        kline_interval,
+        agg_trades,
+        start_ms,
+        end_ms,
+        limit,
+        log_file,
+    })
+}
+
+fn print_help() {
+    println!("Usage:");
+    println!("  cargo run -p public_data_streaming --bin snapshot -- [options]");
+    println!();
+    println!("Options:");
+    println!("  --symbol <symbol>   Symbol to fetch (default: btcusdt)");
+    println!("  --limit <n>         Number of trades, 1-1000, or klines/aggregate trades, any count (default: 500)");
+    println!("  --klines <i>        Fetch klines of interval <i>, e.g. 1m, 1h, instead of trades");
+    println!("  --agg-trades        Fetch aggregate trades between --start and --end instead");
+    println!("  --start <ms>        First kline open or trade time, epoch ms");
+    println!("                      (default: <limit> klines or one hour before --end)");
+    println!("  --end <ms>          Last kline open or trade time, epoch ms (default: now)");
+    println!("  --format <f>        Trade lines as human (default) log lines, or csv or json rows on stdout");
+    println!("  --market <market>   spot (default), spot-testnet, futures or futures-testnet");
+    println!("  --testnet           Switch the chosen market to its testnet");
+    println!("  --mainnet           Switch the chosen market to its mainnet");
+    println!("  --rest-url <url>    REST root URL (overrides BINANCE_REST_URL and --market)");
+    println!("  --proxy <url>       HTTP proxy URL (overrides HTTPS_PROXY/ALL_PROXY)");
+    println!("  --native-tls        Trust the OS certificate store (also BINANCE_NATIVE_TLS=1)");
+    println!("  --log-file <path>   Append the plain-text log here (overrides LOG_FILE, default output.log)");
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // A bad argument is still logged, to the default console and file.
+    let config = parse_args();
+    let (format, log_file) = match &config {
+        Ok(config) => (config.format, config.log_file.as_deref()),
+        Err(_) => (TradeFormat::default(), None),
+    };
+    // Keep stdout clean for CSV/JSON rows by moving the terminal log to stderr.
+    settings::init_logging_to(format.log_console(), log_file);
+
+    let config = match config {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            error!("{}", err);
+            print_help();
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = proxy::init(config.proxy.as_deref()) {
+        error!("{}", err);
+        return Ok(());
+    }
+    if let Err(err) = tls::init(config.native_tls) {
+        error!("{}", err);
+        return Ok(());
+    }
+
+    let rest_base = endpoints::resolve_url(
+        config.rest_url.as_deref(),
+        REST_URL_ENV,
+        config.market.rest_base(),
+    );
+
+    if let Some(interval) = &config.kline_interval {
+        return fetch_klines(&config, &rest_base, interval).await;
+    }
+    if config.agg_trades {
+        return fetch_agg_trades(&config, &rest_base).await;
+    }
+
+    let limit = config
+        .limit
+        .map_or(DEFAULT_TRADES_LIMIT, |limit| limit as u16);
+    info!(
+        "Fetching the last {} trade(s) for {} from {}",
+        limit, config.symbol, rest_base
+    );
+
+    let trades =
+        match rest::fetch_recent_trades(&rest_base, config.market, &config.symbol, limit).await {
+            Ok(trades) => trades,
+            Err(e) => {
+                error!("Failed to fetch recent trades: {}", e);
+                return Ok(());
+            }
+        };
+
+    let count = trades.len();
+    let mut handler = LoggingHandler::new(false).with_format(config.format);
+    for trade in trades {
+        handler.on_trade(&trade.into_trade_event(&config.symbol));
+    }
+    info!("Fetched {} trade(s) for {}.", count, config.symbol);
+    Ok(())
+}
+
+async fn fetch_klines(
+    config: &SnapshotConfig,
+    rest_base: &str,
+    interval: &str,
+) -> Result<(), Box<dyn Error>> {
+    let limit = config.limit.unwrap_or(DEFAULT_KLINES_LIMIT);
+    let end_ms = config.end_ms.unwrap_or_else(unix_ms);
+    let start_ms = match config.start_ms {
+        Some(start_ms) => start_ms,
+        None => {
+            // Weeks and months have no fixed length here; those need an explicit --start.
+            let Some(interval_ms) = CandleAggregator::parse_interval(interval) else {
+                error!("Pass --start with --klines {}", interval);
+                return Ok(());
+            };
+            let last_open = end_ms - end_ms % interval_ms;
+            last_open.saturating_sub(interval_ms.saturating_mul(limit as u64 - 1))
+        }
+    };
+    info!(
+        "Fetching up to {} {} kline(s) for {} between {} and {} from {}",
+        limit, interval, config.symbol, start_ms, end_ms, rest_base
+    );
+
+    let klines = match rest::fetch_klines(
+        rest_base,
+        config.market,
+        &config.symbol,
+        interval,
+        start_ms,
+        end_ms,
+        limit,
+    )
+    .await
+    {
+        Ok(klines) => klines,
+        Err(e) => {
+            error!("Failed to fetch klines: {}", e);
+            return Ok(());
+        }
+    };
+
+    let count = klines.len();
+    let mut handler = LoggingHandler::new(false);
+    for kline in klines {
+        let event = KlineEvent {
+            event_time: kline.close_time,
+            symbol: kline.symbol.clone(),
+            kline,
+        };
+        handler.on_event(&BinanceEvent::Kline(event));
+    }
+    info!("Fetched {} kline(s) for {}.", count, config.symbol);
+    Ok(())
+}
+
+async fn fetch_agg_trades(config: &SnapshotConfig, rest_base: &str) -> Result<(), Box<dyn Error>> {
+    let limit = config.limit.unwrap_or(DEFAULT_AGG_TRADES_LIMIT);
+    let end_ms = config.end_ms.unwrap_or_else(unix_ms);
+    let start_ms = config
+        .start_ms
+        .unwrap_or_else(|| end_ms.saturating_sub(DEFAULT_AGG_TRADES_SPAN_MS));
+    info!(
+        "Fetching up to {} aggregate trade(s) for {} between {} and {} from {}",
+        limit, config.symbol, start_ms, end_ms, rest_base
+    );
+
+    let trades = match rest::fetch_agg_trades(
+        rest_base,
+        config.market,
+        &config.symbol,
+        start_ms,
+        end_ms,
+        limit,
+    )
+    .await
+    {
+        Ok(trades) => trades,
+        Err(e) => {
+            error!("Failed to fetch aggregate trades: {}", e);
+            return Ok(());
+        }
+    };
+
+    let count = trades.len();
+    let mut handler = LoggingHandler::new(false).with_format(config.format);
+    for trade in trades {
+        handler.on_event(&BinanceEvent::AggTrade(trade));
+    }
+    info!(
+        "Fetched {} aggregate trade(s) for {}.",
+        count, config.symbol
+    );
+    Ok(())
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
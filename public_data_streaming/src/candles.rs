@@ -0,0 +1,196 @@
+use crate::handler::TradeHandler;
+use crate::models::{BinanceEvent, BookTickerEvent, PartialDepthEvent, TradeEvent};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Upper bound on flat candles synthesized for one gap, so a long outage on a
+/// short interval cannot flood the consumer.
+pub const MAX_GAP_CANDLES: u64 = 1000;
+
+/// OHLCV candle built locally from trades. Times are epoch milliseconds;
+/// `close_time` is inclusive, matching Binance klines.
+#[derive(Debug, Clone)]
+pub struct Candle {
+    pub symbol: String,
+    pub open_time: u64,
+    pub close_time: u64,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: Decimal,
+    pub trade_count: u64,
+}
+
+impl Candle {
+    fn from_trade(trade: &TradeEvent, open_time: u64, interval_ms: u64) -> Self {
+        Self {
+            symbol: trade.symbol.clone(),
+            open_time,
+            close_time: open_time + interval_ms - 1,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.quantity,
+            trade_count: 1,
+        }
+    }
+
+    /// No trades in the interval: a zero-volume candle pinned at `previous.close`.
+    fn flat_after(previous: &Candle, open_time: u64, interval_ms: u64) -> Self {
+        Self {
+            symbol: previous.symbol.clone(),
+            open_time,
+            close_time: open_time + interval_ms - 1,
+            open: previous.close,
+            high: previous.close,
+            low: previous.close,
+            close: previous.close,
+            volume: Decimal::ZERO,
+            trade_count: 0,
+        }
+    }
+
+    fn apply(&mut self, trade: &TradeEvent) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.quantity;
+        self.trade_count += 1;
+    }
+}
+
+/// Buckets trades per symbol into fixed intervals aligned to the epoch.
+pub struct CandleAggregator {
+    interval_ms: u64,
+    open_candles: HashMap<String, Candle>,
+}
+
+impl CandleAggregator {
+    pub fn new(interval_ms: u64) -> Self {
+        Self {
+            interval_ms: interval_ms.max(1),
+            open_candles: HashMap::new(),
+        }
+    }
+
+    /// Parses intervals like `1s`, `15s`, `1m`, `5m`, `1h`.
+    pub fn parse_interval(value: &str) -> Option<u64> {
+        let value = value.trim();
+        let split = value.len().checked_sub(1)?;
+        let (amount, unit) = value.split_at(split);
+        let amount = amount.parse::<u64>().ok().filter(|amount| *amount > 0)?;
+        let unit_ms = match unit {
+            "s" => 1_000,
+            "m" => 60_000,
+            "h" => 3_600_000,
+            "d" => 86_400_000,
+            _ => return None,
+        };
+        Some(amount * unit_ms)
+    }
+
+    /// Folds `trade` into its symbol's open candle. Returns every candle that was
+    /// completed by crossing an interval boundary, including flat gap candles.
+    pub fn on_trade(&mut self, trade: &TradeEvent) -> Vec<Candle> {
+        let open_time = trade.trade_time - trade.trade_time % self.interval_ms;
+        let mut completed = Vec::new();
+
+        let Some(current) = self.open_candles.get_mut(&trade.symbol) else {
+            self.open_candles.insert(
+                trade.symbol.clone(),
+                Candle::from_trade(trade, open_time, self.interval_ms),
+            );
+            return completed;
+        };
+
+        // Late trades for an already-closed bucket are folded into the open one.
+        if open_time <= current.open_time {
+            current.apply(trade);
+            return completed;
+        }
+
+        let finished = std::mem::replace(
+            current,
+            Candle::from_trade(trade, open_time, self.interval_ms),
+        );
+        let missing = (open_time - finished.open_time) / self.interval_ms - 1;
+        if missing > MAX_GAP_CANDLES {
+            warn!(
+                "{}: {} empty intervals since last trade; emitting the last {} flat candles",
+                finished.symbol, missing, MAX_GAP_CANDLES
+            );
+        }
+
+        let first_gap = open_time - missing.min(MAX_GAP_CANDLES) * self.interval_ms;
+        let mut gap_open = first_gap;
+        completed.push(finished);
+        while gap_open < open_time {
+            let flat = Candle::flat_after(completed.last().unwrap(), gap_open, self.interval_ms);
+            completed.push(flat);
+            gap_open += self.interval_ms;
+        }
+
+        completed
+    }
+}
+
+/// Wraps another handler and, when an aggregator is attached, logs every
+/// completed local candle alongside the inner handler's output.
+pub struct WithCandles<H> {
+    pub inner: H,
+    aggregator: Option<CandleAggregator>,
+}
+
+impl<H> WithCandles<H> {
+    pub fn new(inner: H, aggregator: Option<CandleAggregator>) -> Self {
+        Self { inner, aggregator }
+    }
+}
+
+impl<H: TradeHandler> TradeHandler for WithCandles<H> {
+    fn on_trade(&mut self, trade: &TradeEvent) {
+        self.inner.on_trade(trade);
+        let Some(aggregator) = self.aggregator.as_mut() else {
+            return;
+        };
+        for candle in aggregator.on_trade(trade) {
+            info!(
+                "Candle - Symbol: {}, Open Time: {}, Open: {}, High: {}, Low: {}, Close: {}, Volume: {}, Trades: {}",
+                candle.symbol,
+                candle.open_time,
+                candle.open,
+                candle.high,
+                candle.low,
+                candle.close,
+                candle.volume,
+                candle.trade_count
+            );
+        }
+    }
+
+    fn on_event(&mut self, event: &BinanceEvent) {
+        self.inner.on_event(event);
+    }
+
+    fn on_book_ticker(&mut self, ticker: &BookTickerEvent) {
+        self.inner.on_book_ticker(ticker);
+    }
+
+    fn on_partial_depth(&mut self, depth: &PartialDepthEvent) {
+        self.inner.on_partial_depth(depth);
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        self.inner.on_other(value);
+    }
+
+    fn reset_stats(&mut self) {
+        self.inner.reset_stats();
+    }
+}
@@ -0,0 +1,192 @@
+//! Command-line flags shared by the streaming bins. Each bin offers every argument
+//! to `CommonArgs::parse_flag` first and only matches its own flags on the rest.
+
+use crate::endpoints::{self, Market, MARKET_NAMES, WS_URL_ENV};
+use crate::session::DEFAULT_STALE_TIMEOUT_SECS;
+use std::str::FromStr;
+
+/// `--help` lines for the flags `CommonArgs` takes, in the bins' column layout.
+pub const COMMON_HELP: &[&str] = &[
+    "  --stale-timeout <s> Reconnect after <s> seconds without messages (default 30, 0 = off)",
+    "  --pong-interval <s> Unsolicited pong period (default 180, 0 = off; pings are always answered)",
+    "  --duration <s>      Close cleanly and exit after <s> seconds (default 0 = run until Ctrl+C)",
+    "  --market <market>   spot (default), spot-testnet, futures or futures-testnet",
+    "  --testnet           Switch the chosen market to its testnet",
+    "  --mainnet           Switch the chosen market to its mainnet",
+    "  --ws-url <url>      Websocket root URL (overrides BINANCE_WS_URL and --market)",
+    "  --proxy <url>       HTTP proxy URL (overrides HTTPS_PROXY/ALL_PROXY)",
+    "  --native-tls        Trust the OS certificate store (also BINANCE_NATIVE_TLS=1)",
+    "  --compress          Offer permessage-deflate and log the compression ratio (uncompressed if refused)",
+    "  --log-file <path>   Append the plain-text log here (overrides LOG_FILE, default output.log)",
+];
+
+#[derive(Debug, Clone)]
+pub struct CommonArgs {
+    market: Market,
+    /// `--testnet`/`--mainnet`, applied to `market` whichever came first.
+    testnet: Option<bool>,
+    pub ws_url: Option<String>,
+    pub proxy: Option<String>,
+    pub native_tls: bool,
+    /// Offer `permessage-deflate` on every websocket connection.
+    pub compress: bool,
+    /// `None` leaves it to `session::resolve_pong_interval_secs`.
+    pub pong_interval_secs: Option<u64>,
+    /// `0` runs until Ctrl+C.
+    pub duration_secs: u64,
+    /// `0` turns the stale watchdog off.
+    pub stale_timeout_secs: u64,
+    /// `None` leaves it to `LOG_FILE`, then `output.log`.
+    pub log_file: Option<String>,
+}
+
+impl Default for CommonArgs {
+    fn default() -> Self {
+        Self {
+            market: Market::default(),
+            testnet: None,
+            ws_url: None,
+            proxy: None,
+            native_tls: false,
+            compress: false,
+            pong_interval_secs: None,
+            duration_secs: 0,
+            stale_timeout_secs: DEFAULT_STALE_TIMEOUT_SECS,
+            log_file: None,
+        }
+    }
+}
+
+impl CommonArgs {
+    /// Takes `arg` if it is a common flag, reading its value from `args`.
+    /// Returns `false` for anything else, leaving it to the bin.
+    pub fn parse_flag(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, String> {
+        match arg {
+            "--testnet" => self.testnet = Some(true),
+            "--mainnet" => self.testnet = Some(false),
+            "--market" => {
+                let value = next_value(args, arg)?;
+                self.market = Market::parse(&value).ok_or_else(|| {
+                    format!(
+                        "Invalid --market: {}; expected one of {}",
+                        value,
+                        MARKET_NAMES.join(", ")
+                    )
+                })?;
+            }
+            "--ws-url" => self.ws_url = Some(next_value(args, arg)?),
+            "--proxy" => self.proxy = Some(next_value(args, arg)?),
+            "--native-tls" => self.native_tls = true,
+            "--compress" => self.compress = true,
+            "--pong-interval" => self.pong_interval_secs = Some(parse_value(args, arg)?),
+            "--duration" => self.duration_secs = parse_value(args, arg)?,
+            "--stale-timeout" => self.stale_timeout_secs = parse_value(args, arg)?,
+            "--log-file" => self.log_file = Some(next_value(args, arg)?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// `--market`, switched to its testnet or mainnet by `--testnet`/`--mainnet`.
+    pub fn market(&self) -> Market {
+        match self.testnet {
+            Some(testnet) => self.market.with_testnet(testnet),
+            None => self.market,
+        }
+    }
+
+    /// Websocket root URL: `--ws-url` > `BINANCE_WS_URL` > the market's.
+    pub fn ws_root_url(&self) -> String {
+        endpoints::resolve_url(self.ws_url.as_deref(), WS_URL_ENV, self.market().ws_base())
+    }
+}
+
+pub fn print_common_help() {
+    for line in COMMON_HELP {
+        println!("{}", line);
+    }
+}
+
+/// The value following `flag`.
+pub fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for {}", flag))
+}
+
+/// The value following `flag`, parsed as a `T`.
+pub fn parse_value<T: FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    flag: &str,
+) -> Result<T, String> {
+    let value = next_value(args, flag)?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {}: {}", flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `line` through `parse_flag` and returns what it took, plus the
+    /// arguments it left for the bin.
+    fn parse(line: &str) -> Result<(CommonArgs, Vec<String>), String> {
+        let mut common = CommonArgs::default();
+        let mut rest = Vec::new();
+        let mut args = line.split_whitespace().map(str::to_string);
+        while let Some(arg) = args.next() {
+            if !common.parse_flag(&arg, &mut args)? {
+                rest.push(arg);
+            }
+        }
+        Ok((common, rest))
+    }
+
+    #[test]
+    fn takes_common_flags_and_leaves_the_rest() {
+        let (common, rest) = parse(
+            "--symbol btcusdt --duration 5 --ws-url ws://127.0.0.1:9000 --native-tls \
+             --log-file out.log --stale-timeout 0 --quiet --pong-interval 60 --compress",
+        )
+        .unwrap();
+        assert_eq!(rest, ["--symbol", "btcusdt", "--quiet"]);
+        assert_eq!(common.duration_secs, 5);
+        assert_eq!(common.stale_timeout_secs, 0);
+        assert_eq!(common.pong_interval_secs, Some(60));
+        assert!(common.native_tls);
+        assert!(common.compress);
+        assert_eq!(common.ws_root_url(), "ws://127.0.0.1:9000");
+        assert_eq!(common.proxy, None);
+        assert_eq!(common.log_file.as_deref(), Some("out.log"));
+    }
+
+    #[test]
+    fn testnet_applies_to_the_market_in_either_order() {
+        let (before, _) = parse("--testnet --market futures").unwrap();
+        let (after, _) = parse("--market futures --testnet").unwrap();
+        assert_eq!(before.market(), Market::FuturesTestnet);
+        assert_eq!(after.market(), Market::FuturesTestnet);
+        let (mainnet, _) = parse("--market spot-testnet --mainnet").unwrap();
+        assert_eq!(mainnet.market(), Market::SpotMainnet);
+        assert_eq!(CommonArgs::default().market(), Market::SpotMainnet);
+    }
+
+    #[test]
+    fn bad_values_name_the_flag() {
+        assert_eq!(
+            parse("--duration").unwrap_err(),
+            "Missing value for --duration"
+        );
+        assert_eq!(
+            parse("--stale-timeout soon").unwrap_err(),
+            "Invalid --stale-timeout: soon"
+        );
+        assert!(parse("--market moon")
+            .unwrap_err()
+            .starts_with("Invalid --market: moon; expected one of"));
+    }
+}
@@ -0,0 +1,75 @@
+//! Time source behind the stats counters, so message rates, gaps and the stale
+//! watchdog can run off a hand-advanced clock instead of the wall clock.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time in Unix milliseconds, for comparing against event times.
+    fn now_unix_ms(&self) -> u64;
+}
+
+/// `Instant::now()`; what every bin uses.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn now_unix_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// Stands still until `advance`d. Clones share the same time, so a copy handed to
+/// `SessionStats` can be moved forward from outside. Its wall clock starts at
+/// `start_unix_ms` and moves with it.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    start: Instant,
+    start_unix_ms: u64,
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::starting_at_unix_ms(SystemClock.now_unix_ms())
+    }
+
+    pub fn starting_at_unix_ms(start_unix_ms: u64) -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            start_unix_ms,
+            now: Arc::new(Mutex::new(start)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+
+    fn now_unix_ms(&self) -> u64 {
+        self.start_unix_ms + (self.now() - self.start).as_millis() as u64
+    }
+}
@@ -0,0 +1,426 @@
+use crate::models::{
+    AggTradeEvent, BinanceEvent, BinanceMessage, BookTickerEvent, PartialDepthEvent, TradeEvent,
+};
+use crate::sink::EventSink;
+use crate::vwap::VolumeTracker;
+use log::Level;
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use std::io::{self, Write};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Plug-in point for consuming parsed public stream messages.
+///
+/// Only `on_trade` and `on_other` are required; the remaining typed callbacks
+/// default to no-ops so a handler can focus on the streams it cares about.
+pub trait TradeHandler {
+    fn on_trade(&mut self, trade: &TradeEvent);
+
+    /// Typed events other than trades (aggTrade, ticker, kline, depth, markPrice, forceOrder).
+    fn on_event(&mut self, _event: &BinanceEvent) {}
+
+    fn on_book_ticker(&mut self, _ticker: &BookTickerEvent) {}
+
+    fn on_partial_depth(&mut self, _depth: &PartialDepthEvent) {}
+
+    /// Anything that did not match a typed variant.
+    fn on_other(&mut self, value: &Value);
+
+    /// Clears running statistics such as VWAP totals (`reset stats` in dynamic mode).
+    fn reset_stats(&mut self) {}
+}
+
+/// Routes a parsed message to the matching handler callback.
+pub fn dispatch<H: TradeHandler + ?Sized>(handler: &mut H, message: BinanceMessage) {
+    dispatch_ref(handler, &message);
+}
+
+/// Like `dispatch`, then hands the owned typed events to `sink` (if attached)
+/// for consumers running in another task.
+pub fn dispatch_with_sink<H: TradeHandler + ?Sized>(
+    handler: &mut H,
+    message: BinanceMessage,
+    sink: Option<&EventSink>,
+) {
+    dispatch_ref(handler, &message);
+    if let Some(sink) = sink {
+        sink.send_message(message);
+    }
+}
+
+fn dispatch_ref<H: TradeHandler + ?Sized>(handler: &mut H, message: &BinanceMessage) {
+    match message {
+        BinanceMessage::Event(event) => dispatch_event(handler, event),
+        BinanceMessage::ArrayEvent(events) => {
+            for event in events {
+                dispatch_event(handler, event);
+            }
+        }
+        BinanceMessage::BookTicker(ticker) => handler.on_book_ticker(ticker),
+        BinanceMessage::PartialDepth(depth) => handler.on_partial_depth(depth),
+        BinanceMessage::ErrorResponse { error, id } => {
+            warn!(
+                "Request id={:?} failed: code {}, {}",
+                id, error.code, error.msg
+            );
+        }
+        BinanceMessage::SubscriptionResponse { result, id } => {
+            debug!(
+                "Unmatched subscription response: id={}, result={:?}",
+                id, result
+            );
+        }
+        BinanceMessage::Other(other) => handler.on_other(other),
+    }
+}
+
+fn dispatch_event<H: TradeHandler + ?Sized>(handler: &mut H, event: &BinanceEvent) {
+    match event {
+        BinanceEvent::Trade(trade) => handler.on_trade(trade),
+        event => handler.on_event(event),
+    }
+}
+
+/// Column order of `TradeFormat::Csv` rows; also the JSON keys.
+pub const TRADE_COLUMNS: &str = "kind,symbol,id,price,quantity,buyer_maker,trade_time";
+
+/// How `LoggingHandler` renders trades and aggTrades (`--format`). Other events
+/// are always logged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TradeFormat {
+    /// The `Trade - ...` info log line.
+    #[default]
+    Human,
+    /// A `TRADE_COLUMNS` row on stdout, after a header row.
+    Csv,
+    /// A compact JSON object per line on stdout.
+    Json,
+}
+
+impl TradeFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "human" => Some(TradeFormat::Human),
+            "csv" => Some(TradeFormat::Csv),
+            "json" => Some(TradeFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Whether trades go to stdout as rows, in which case the log belongs on stderr.
+    pub fn prints_rows(self) -> bool {
+        self != TradeFormat::Human
+    }
+
+    /// Where the terminal copy of the log goes, keeping stdout clean for rows.
+    pub fn log_console(self) -> Box<dyn Write + Send> {
+        if self.prints_rows() {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        }
+    }
+}
+
+/// The fields shared by trades and aggTrades, in `TRADE_COLUMNS` order.
+struct TradeRow<'a> {
+    kind: &'static str,
+    symbol: &'a str,
+    id: u64,
+    price: Decimal,
+    quantity: Decimal,
+    buyer_maker: bool,
+    trade_time: u64,
+}
+
+impl<'a> From<&'a TradeEvent> for TradeRow<'a> {
+    fn from(trade: &'a TradeEvent) -> Self {
+        Self {
+            kind: "trade",
+            symbol: &trade.symbol,
+            id: trade.trade_id,
+            price: trade.price,
+            quantity: trade.quantity,
+            buyer_maker: trade.is_buyer_market_maker,
+            trade_time: trade.trade_time,
+        }
+    }
+}
+
+impl<'a> From<&'a AggTradeEvent> for TradeRow<'a> {
+    fn from(agg: &'a AggTradeEvent) -> Self {
+        Self {
+            kind: "aggTrade",
+            symbol: &agg.symbol,
+            id: agg.agg_trade_id,
+            price: agg.price,
+            quantity: agg.quantity,
+            buyer_maker: agg.is_buyer_market_maker,
+            trade_time: agg.trade_time,
+        }
+    }
+}
+
+impl TradeRow<'_> {
+    fn csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{}",
+            self.kind,
+            self.symbol,
+            self.id,
+            self.price,
+            self.quantity,
+            self.buyer_maker,
+            self.trade_time
+        )
+    }
+
+    /// Prices and quantities stay strings, as Binance sends them, to keep every digit.
+    fn json(&self) -> Value {
+        json!({
+            "kind": self.kind,
+            "symbol": self.symbol,
+            "id": self.id,
+            "price": self.price.to_string(),
+            "quantity": self.quantity.to_string(),
+            "buyer_maker": self.buyer_maker,
+            "trade_time": self.trade_time,
+        })
+    }
+}
+
+/// Default handler that logs every message, matching the demo bins' output.
+#[derive(Debug, Default)]
+pub struct LoggingHandler {
+    quiet: bool,
+    format: TradeFormat,
+    /// Whether the CSV header row has been printed yet.
+    csv_header_printed: bool,
+    /// Trades and aggTrades below this quantity are logged at `debug` (`--min-qty`).
+    min_qty: Option<Decimal>,
+    /// Per-symbol volume and VWAP, logged each stats interval (`--vwap`).
+    vwap: Option<VolumeTracker>,
+}
+
+impl LoggingHandler {
+    /// `quiet` drops the per-event lines (`--quiet`) independently of the log level,
+    /// leaving stats, warnings and reconnects visible.
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            format: TradeFormat::Human,
+            csv_header_printed: false,
+            min_qty: None,
+            vwap: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: TradeFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_min_qty(mut self, min_qty: Option<Decimal>) -> Self {
+        self.min_qty = min_qty;
+        self
+    }
+
+    pub fn with_vwap(mut self, enabled: bool) -> Self {
+        self.vwap = enabled.then(VolumeTracker::new);
+        self
+    }
+
+    /// `info` for trades at or above `min_qty`, `debug` for the smaller ones.
+    fn trade_level(&self, quantity: Decimal) -> Level {
+        match self.min_qty {
+            Some(min_qty) if quantity < min_qty => Level::Debug,
+            _ => Level::Info,
+        }
+    }
+
+    /// Prints a CSV or JSON row to stdout. Rows are output rather than log records,
+    /// so the log level never hides them; only rows below `--min-qty` are skipped.
+    fn print_row(&mut self, row: TradeRow) {
+        if self.min_qty.is_some_and(|min_qty| row.quantity < min_qty) {
+            return;
+        }
+        match self.format {
+            TradeFormat::Human => {}
+            TradeFormat::Csv => {
+                if !self.csv_header_printed {
+                    self.csv_header_printed = true;
+                    println!("{}", TRADE_COLUMNS);
+                }
+                println!("{}", row.csv());
+            }
+            TradeFormat::Json => println!("{}", row.json()),
+        }
+    }
+}
+
+impl TradeHandler for LoggingHandler {
+    fn on_trade(&mut self, trade: &TradeEvent) {
+        // Like the stats line, the VWAP summary is kept under --quiet.
+        if let Some(vwap) = self.vwap.as_mut() {
+            vwap.record(trade);
+            vwap.log_if_due();
+        }
+        if self.quiet {
+            return;
+        }
+        if self.format.prints_rows() {
+            self.print_row(TradeRow::from(trade));
+            return;
+        }
+        let level = self.trade_level(trade.quantity);
+        log::log!(
+            level,
+            "Trade - Symbol: {}, Price: {}, Quantity: {}, Trade Time: {}",
+            trade.symbol,
+            trade.price,
+            trade.quantity,
+            trade.trade_time
+        );
+    }
+
+    fn on_event(&mut self, event: &BinanceEvent) {
+        if self.quiet {
+            return;
+        }
+        match event {
+            BinanceEvent::Trade(trade) => self.on_trade(trade),
+            BinanceEvent::AggTrade(agg) => {
+                if self.format.prints_rows() {
+                    self.print_row(TradeRow::from(agg));
+                    return;
+                }
+                let level = self.trade_level(agg.quantity);
+                log::log!(
+                    level,
+                    "AggTrade - Symbol: {}, Side: {}, Price: {}, Quantity: {}, Buyer Maker: {}",
+                    agg.symbol,
+                    agg.aggressor_side(),
+                    agg.price,
+                    agg.quantity,
+                    agg.is_buyer_market_maker
+                );
+            }
+            BinanceEvent::Ticker(ticker) => {
+                info!(
+                    "Ticker - Symbol: {}, Last: {}, Change: {}%, High: {}, Low: {}, Volume: {}",
+                    ticker.symbol,
+                    ticker.last_price,
+                    ticker.price_change_percent,
+                    ticker.high_price,
+                    ticker.low_price,
+                    ticker.total_traded_base_asset_volume
+                );
+            }
+            BinanceEvent::Kline(kline_event) => {
+                let kline = &kline_event.kline;
+                if kline.is_closed {
+                    info!(
+                        "Kline closed - Symbol: {}, Interval: {}, Open: {}, High: {}, Low: {}, Close: {}, Volume: {}, Quote Volume: {}",
+                        kline.symbol,
+                        kline.interval,
+                        kline.open_price,
+                        kline.high_price,
+                        kline.low_price,
+                        kline.close_price,
+                        kline.base_asset_volume,
+                        kline.quote_asset_volume
+                    );
+                } else {
+                    debug!(
+                        "Kline update - Symbol: {}, Interval: {}, Close: {}, Volume: {}",
+                        kline.symbol, kline.interval, kline.close_price, kline.base_asset_volume
+                    );
+                }
+            }
+            BinanceEvent::DepthUpdate(depth) => {
+                debug!(
+                    "Depth update - Symbol: {}, Update IDs: {}..={}, Bids: {}, Asks: {}",
+                    depth.symbol,
+                    depth.first_update_id,
+                    depth.final_update_id,
+                    depth.bids.len(),
+                    depth.asks.len()
+                );
+            }
+            BinanceEvent::MarkPrice(mark) => {
+                let next_funding =
+                    chrono::DateTime::from_timestamp_millis(mark.next_funding_time as i64)
+                        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                        .unwrap_or_else(|| mark.next_funding_time.to_string());
+                info!(
+                    "MarkPrice - Symbol: {}, Mark: {}, Index: {}, Funding Rate: {}, Next Funding: {}",
+                    mark.symbol, mark.mark_price, mark.index_price, mark.funding_rate, next_funding
+                );
+            }
+            BinanceEvent::ForceOrder(liquidation) => {
+                let order = &liquidation.order;
+                info!(
+                    "Liquidation - Symbol: {}, Side: {}, Quantity: {}, Avg Price: {}, Notional: {}, Status: {}",
+                    order.symbol,
+                    order.side,
+                    order.quantity,
+                    order.average_price,
+                    order.notional(),
+                    order.order_status
+                );
+            }
+        }
+    }
+
+    fn on_book_ticker(&mut self, ticker: &BookTickerEvent) {
+        if self.quiet {
+            return;
+        }
+        info!(
+            "BookTicker - Symbol: {}, Bid: {} ({}), Ask: {} ({})",
+            ticker.symbol,
+            ticker.best_bid_price,
+            ticker.best_bid_qty,
+            ticker.best_ask_price,
+            ticker.best_ask_qty
+        );
+    }
+
+    fn on_partial_depth(&mut self, depth: &PartialDepthEvent) {
+        if self.quiet {
+            return;
+        }
+        match (depth.best_bid(), depth.best_ask(), depth.spread()) {
+            (Some(bid), Some(ask), Some(spread)) => info!(
+                "PartialDepth - Last Update ID: {}, Bid: {} ({}), Ask: {} ({}), Spread: {}, Levels: {}/{}",
+                depth.last_update_id,
+                bid.0,
+                bid.1,
+                ask.0,
+                ask.1,
+                spread,
+                depth.bids.len(),
+                depth.asks.len()
+            ),
+            _ => debug!(
+                "PartialDepth - Last Update ID: {}, one side empty (Bids: {}, Asks: {})",
+                depth.last_update_id,
+                depth.bids.len(),
+                depth.asks.len()
+            ),
+        }
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        debug!("Other message: {:?}", value);
+    }
+
+    fn reset_stats(&mut self) {
+        if let Some(vwap) = self.vwap.as_mut() {
+            vwap.reset();
+            info!("VWAP totals reset.");
+        }
+    }
+}
@@ -1,2 +1,22 @@
+pub use binance_common::{deflate, endpoints, proxy, settings, tls};
+
+pub mod candles;
+pub mod cli;
+pub mod clock;
+pub mod handler;
+pub mod metrics;
 pub mod models;
-pub mod settings;
+pub mod order_book;
+pub mod persistence;
+pub mod reconnect;
+pub mod replay;
+pub mod requests;
+pub mod rest;
+pub mod session;
+pub mod sink;
+pub mod sqlite;
+pub mod state;
+pub mod stats;
+pub mod streams;
+pub mod validate;
+pub mod vwap;
@@ -4,4 +4,6 @@ fn main() {
     println!("     cargo run -p public_data_streaming --bin dynamic_subscriptions");
     println!("  2) Fixed URL stream subscription:");
     println!("     cargo run -p public_data_streaming --bin fixed_url_stream -- --symbol ethusdt");
+    println!("  3) Local order book from depth diffs:");
+    println!("     cargo run -p public_data_streaming --bin order_book -- --symbol btcusdt");
 }
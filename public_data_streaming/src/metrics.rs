@@ -0,0 +1,145 @@
+use crate::state::{ConnectionState, StateCallback, CONNECTION_STATES};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Counters shared between the websocket loop and the `/metrics` server.
+/// Cloning is cheap; every clone points at the same atomics.
+#[derive(Clone)]
+pub struct Metrics {
+    pub messages_total: Arc<AtomicU64>,
+    pub reconnects_total: Arc<AtomicU64>,
+    last_message_unix_ms: Arc<AtomicU64>,
+    /// `ConnectionState::index` of the latest state.
+    connection_state: Arc<AtomicU64>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            messages_total: Arc::new(AtomicU64::new(0)),
+            reconnects_total: Arc::new(AtomicU64::new(0)),
+            last_message_unix_ms: Arc::new(AtomicU64::new(unix_ms())),
+            connection_state: Arc::new(AtomicU64::new(ConnectionState::Closed.index() as u64)),
+        }
+    }
+
+    pub fn record_message(&self) {
+        self.messages_total.fetch_add(1, Ordering::Relaxed);
+        self.last_message_unix_ms
+            .store(unix_ms(), Ordering::Relaxed);
+    }
+
+    pub fn record_reconnect(&self) {
+        self.reconnects_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_state(&self, state: ConnectionState) {
+        self.connection_state
+            .store(state.index() as u64, Ordering::Relaxed);
+    }
+
+    /// A `ConnectionStateMachine` callback that keeps `binance_connection_state` current.
+    pub fn state_callback(&self) -> StateCallback {
+        let metrics = self.clone();
+        Box::new(move |_, state| metrics.record_state(state))
+    }
+
+    /// Seconds since the last text frame (or since startup if none arrived yet).
+    pub fn message_gap_seconds(&self) -> f64 {
+        let last = self.last_message_unix_ms.load(Ordering::Relaxed);
+        unix_ms().saturating_sub(last) as f64 / 1000.0
+    }
+
+    /// Renders the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let current_state = self.connection_state.load(Ordering::Relaxed) as usize;
+        let states = CONNECTION_STATES
+            .iter()
+            .map(|state| {
+                format!(
+                    "binance_connection_state{{state=\"{}\"}} {}\n",
+                    state,
+                    u8::from(state.index() == current_state)
+                )
+            })
+            .collect::<String>();
+        format!(
+            "# HELP binance_messages_total Text frames received from Binance.\n\
+             # TYPE binance_messages_total counter\n\
+             binance_messages_total {}\n\
+             # HELP binance_reconnects_total Websocket reconnect attempts.\n\
+             # TYPE binance_reconnects_total counter\n\
+             binance_reconnects_total {}\n\
+             # HELP binance_message_gap_seconds Seconds since the last received message.\n\
+             # TYPE binance_message_gap_seconds gauge\n\
+             binance_message_gap_seconds {:.3}\n\
+             # HELP binance_connection_state Current connection state (1 for the active one).\n\
+             # TYPE binance_connection_state gauge\n\
+             {}",
+            self.messages_total.load(Ordering::Relaxed),
+            self.reconnects_total.load(Ordering::Relaxed),
+            self.message_gap_seconds(),
+            states
+        )
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Serves `metrics.render()` on `0.0.0.0:<port>` from its own tokio task.
+pub fn spawn_server(port: u16, metrics: Metrics) {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let metrics = metrics.clone();
+                async move {
+                    let response = if request.uri().path() == "/metrics" {
+                        Response::builder()
+                            .header("Content-Type", "text/plain; version=0.0.4")
+                            .body(Body::from(metrics.render()))
+                    } else {
+                        Response::builder().status(404).body(Body::empty())
+                    };
+                    response
+                }
+            }))
+        }
+    });
+
+    tokio::spawn(async move {
+        let server = match Server::try_bind(&addr) {
+            Ok(builder) => builder.serve(make_service),
+            Err(e) => {
+                error!("Failed to bind metrics server on {}: {}", addr, e);
+                return;
+            }
+        };
+
+        info!("Serving Prometheus metrics on http://{}/metrics", addr);
+        if let Err(e) = server.await {
+            error!("Metrics server error: {}", e);
+        }
+    });
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
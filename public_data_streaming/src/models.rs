@@ -1,15 +1,48 @@
+//! Prices and quantities arrive as JSON strings (e.g. `"0.00000100"`) and are
+//! deserialized straight into `Decimal`, so consumers get exact arithmetic
+//! without re-parsing.
+
+use rust_decimal::Decimal;
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde::Deserialize;
 use serde_json::Value;
+use std::fmt;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum BinanceMessage {
     Event(BinanceEvent),
+    // All-market streams such as `!ticker@arr` send one top-level JSON array per frame.
+    // A Vec only deserializes from a sequence, so objects never land here.
+    ArrayEvent(Vec<BinanceEvent>),
+    // bookTicker frames carry no "e" tag, so they are matched by shape after typed events.
+    BookTicker(BookTickerEvent),
+    // Partial book depth (`<symbol>@depth<N>`) is also untagged; it is tried after
+    // bookTicker and needs all of `lastUpdateId`, `bids` and `asks` to match.
+    PartialDepth(PartialDepthEvent),
+    // Must precede SubscriptionResponse: its `result` is optional, so an error reply
+    // (`{"error": {...}, "id": 1}`) would otherwise match it with `result: None`.
+    ErrorResponse { error: ApiError, id: Option<i64> },
     SubscriptionResponse { result: Option<Value>, id: i64 },
     Other(Value),
 }
 
+/// `error` object of a rejected websocket API request, e.g. `{"code": 2, "msg": "Invalid request"}`.
+#[derive(Debug, Deserialize)]
+pub struct ApiError {
+    pub code: i64,
+    pub msg: String,
+}
+
+/// Wrapper applied to every payload on the combined `/stream?streams=` endpoint.
 #[derive(Debug, Deserialize)]
+pub struct CombinedStreamEnvelope {
+    pub stream: String,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(tag = "e")]
 pub enum BinanceEvent {
     #[serde(rename = "aggTrade")]
@@ -20,50 +53,92 @@ pub enum BinanceEvent {
     Kline(KlineEvent),
     #[serde(rename = "trade")]
     Trade(TradeEvent),
+    #[serde(rename = "depthUpdate")]
+    DepthUpdate(DepthUpdateEvent),
+    /// Futures only (`<symbol>@markPrice` on fstream).
+    #[serde(rename = "markPriceUpdate")]
+    MarkPrice(MarkPriceEvent),
+    /// Futures only (`<symbol>@forceOrder` / `!forceOrder@arr` on fstream).
+    #[serde(rename = "forceOrder")]
+    ForceOrder(ForceOrderEvent),
+}
+
+impl BinanceEvent {
+    /// Variant name, for diagnostics such as `--validate`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BinanceEvent::AggTrade(_) => "AggTrade",
+            BinanceEvent::Ticker(_) => "Ticker",
+            BinanceEvent::Kline(_) => "Kline",
+            BinanceEvent::Trade(_) => "Trade",
+            BinanceEvent::DepthUpdate(_) => "DepthUpdate",
+            BinanceEvent::MarkPrice(_) => "MarkPrice",
+            BinanceEvent::ForceOrder(_) => "ForceOrder",
+        }
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AggTradeEvent {
     #[serde(rename = "E")]
     pub event_time: u64,
     #[serde(rename = "s")]
     pub symbol: String,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
     #[serde(rename = "p")]
-    pub price: String,
+    pub price: Decimal,
     #[serde(rename = "q")]
-    pub quantity: String,
+    pub quantity: Decimal,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_market_maker: bool,
+}
+
+impl AggTradeEvent {
+    /// Side of the aggressor: a buyer-maker trade was hit by a seller.
+    pub fn aggressor_side(&self) -> &'static str {
+        if self.is_buyer_market_maker {
+            "SELL"
+        } else {
+            "BUY"
+        }
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct TickerEvent {
-    #[serde(rename = "e")]
-    pub event_type: String,
     #[serde(rename = "E")]
     pub event_time: u64,
     #[serde(rename = "s")]
     pub symbol: String,
     #[serde(rename = "p")]
-    pub price_change: String,
+    pub price_change: Decimal,
     #[serde(rename = "P")]
-    pub price_change_percent: String,
+    pub price_change_percent: Decimal,
     #[serde(rename = "w")]
-    pub weighted_avg_price: String,
+    pub weighted_avg_price: Decimal,
     #[serde(rename = "c")]
-    pub last_price: String,
+    pub last_price: Decimal,
     #[serde(rename = "Q")]
-    pub last_quantity: String,
+    pub last_quantity: Decimal,
     #[serde(rename = "o")]
-    pub open_price: String,
+    pub open_price: Decimal,
     #[serde(rename = "h")]
-    pub high_price: String,
+    pub high_price: Decimal,
     #[serde(rename = "l")]
-    pub low_price: String,
+    pub low_price: Decimal,
     #[serde(rename = "v")]
-    pub total_traded_base_asset_volume: String,
+    pub total_traded_base_asset_volume: Decimal,
     #[serde(rename = "q")]
-    pub total_traded_quote_asset_volume: String,
+    pub total_traded_quote_asset_volume: Decimal,
     #[serde(rename = "O")]
     pub statistics_open_time: u64,
     #[serde(rename = "C")]
@@ -77,7 +152,7 @@ pub struct TickerEvent {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct KlineEvent {
     #[serde(rename = "E")]
     pub event_time: u64,
@@ -88,7 +163,7 @@ pub struct KlineEvent {
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Kline {
     #[serde(rename = "t")]
     pub start_time: u64,
@@ -103,30 +178,30 @@ pub struct Kline {
     #[serde(rename = "L")]
     pub last_trade_id: u64,
     #[serde(rename = "o")]
-    pub open_price: String,
+    pub open_price: Decimal,
     #[serde(rename = "c")]
-    pub close_price: String,
+    pub close_price: Decimal,
     #[serde(rename = "h")]
-    pub high_price: String,
+    pub high_price: Decimal,
     #[serde(rename = "l")]
-    pub low_price: String,
+    pub low_price: Decimal,
     #[serde(rename = "v")]
-    pub base_asset_volume: String,
+    pub base_asset_volume: Decimal,
     #[serde(rename = "n")]
     pub number_of_trades: u64,
     #[serde(rename = "x")]
     pub is_closed: bool,
     #[serde(rename = "q")]
-    pub quote_asset_volume: String,
+    pub quote_asset_volume: Decimal,
     #[serde(rename = "V")]
-    pub taker_buy_base_asset_volume: String,
+    pub taker_buy_base_asset_volume: Decimal,
     #[serde(rename = "Q")]
-    pub taker_buy_quote_asset_volume: String,
+    pub taker_buy_quote_asset_volume: Decimal,
     #[serde(rename = "B")]
     pub ignore: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct TradeEvent {
     #[serde(rename = "E")]
     pub event_time: u64,
@@ -135,9 +210,9 @@ pub struct TradeEvent {
     #[serde(rename = "t")]
     pub trade_id: u64,
     #[serde(rename = "p")]
-    pub price: String,
+    pub price: Decimal,
     #[serde(rename = "q")]
-    pub quantity: String,
+    pub quantity: Decimal,
     #[serde(rename = "T")]
     pub trade_time: u64,
     #[serde(rename = "m")]
@@ -145,3 +220,474 @@ pub struct TradeEvent {
     #[serde(rename = "X")]
     pub trade_type: Option<String>,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarkPriceEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p")]
+    pub mark_price: Decimal,
+    #[serde(rename = "i")]
+    pub index_price: Decimal,
+    #[serde(rename = "P")]
+    pub estimated_settle_price: Decimal,
+    #[serde(rename = "r")]
+    pub funding_rate: Decimal,
+    #[serde(rename = "T")]
+    pub next_funding_time: u64,
+}
+
+/// Liquidation order; unlike trades the order fields are nested under `o`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForceOrderEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "o")]
+    pub order: ForceOrder,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForceOrder {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "S")]
+    pub side: String,
+    #[serde(rename = "o")]
+    pub order_type: String,
+    #[serde(rename = "q")]
+    pub quantity: Decimal,
+    #[serde(rename = "p")]
+    pub price: Decimal,
+    #[serde(rename = "ap")]
+    pub average_price: Decimal,
+    #[serde(rename = "X")]
+    pub order_status: String,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+}
+
+impl ForceOrder {
+    /// Quote-asset size of the liquidation at its average fill price.
+    pub fn notional(&self) -> Decimal {
+        self.average_price * self.quantity
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookTickerEvent {
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "b")]
+    pub best_bid_price: Decimal,
+    #[serde(rename = "B")]
+    pub best_bid_qty: Decimal,
+    #[serde(rename = "a")]
+    pub best_ask_price: Decimal,
+    #[serde(rename = "A")]
+    pub best_ask_qty: Decimal,
+}
+
+/// Price level as `(price, quantity)`; a zero quantity removes the level.
+pub type PriceLevel = (Decimal, Decimal);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepthUpdateEvent {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    /// Futures only: the previous event's `u`, for checking continuity.
+    #[serde(rename = "pu", default)]
+    pub previous_final_update_id: Option<u64>,
+    #[serde(rename = "b")]
+    pub bids: Vec<PriceLevel>,
+    #[serde(rename = "a")]
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Top-N book pushed by `<symbol>@depth5/10/20[@100ms]`: a full snapshot each
+/// tick rather than a diff, so it carries no `"e"` tag or symbol.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PartialDepthEvent {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+impl PartialDepthEvent {
+    pub fn best_bid(&self) -> Option<&PriceLevel> {
+        self.bids.first()
+    }
+
+    pub fn best_ask(&self) -> Option<&PriceLevel> {
+        self.asks.first()
+    }
+
+    /// Best ask minus best bid, when both sides have at least one level.
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.best_ask()?.0 - self.best_bid()?.0)
+    }
+}
+
+/// REST order book snapshot used to bootstrap a local book. Parsed directly
+/// from the REST response, never through `BinanceMessage`.
+#[derive(Debug, Deserialize)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// One entry of the REST recent trades list (`GET /api/v3/trades`). Parsed
+/// directly from the REST response, never through `BinanceMessage`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentTrade {
+    pub id: u64,
+    pub price: Decimal,
+    pub qty: Decimal,
+    pub time: u64,
+    pub is_buyer_maker: bool,
+}
+
+impl RecentTrade {
+    /// The `@trade` event shape, so REST trades go through the same handlers.
+    /// REST has no event time, so the trade time stands in for it.
+    pub fn into_trade_event(self, symbol: &str) -> TradeEvent {
+        TradeEvent {
+            event_time: self.time,
+            symbol: symbol.to_uppercase(),
+            trade_id: self.id,
+            price: self.price,
+            quantity: self.qty,
+            trade_time: self.time,
+            is_buyer_market_maker: self.is_buyer_maker,
+            trade_type: None,
+        }
+    }
+}
+
+/// One entry of the REST aggregate trades list (`GET /api/v3/aggTrades`), which
+/// uses the stream's field names minus the event time and symbol.
+#[derive(Debug, Deserialize)]
+pub struct AggTrade {
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p")]
+    pub price: Decimal,
+    #[serde(rename = "q")]
+    pub quantity: Decimal,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_market_maker: bool,
+}
+
+impl AggTrade {
+    /// The `@aggTrade` event shape, so backfilled trades go through the same handlers.
+    /// The trade time stands in for the missing event time.
+    pub fn into_agg_trade_event(self, symbol: &str) -> AggTradeEvent {
+        AggTradeEvent {
+            event_time: self.trade_time,
+            symbol: symbol.to_uppercase(),
+            agg_trade_id: self.agg_trade_id,
+            price: self.price,
+            quantity: self.quantity,
+            first_trade_id: self.first_trade_id,
+            last_trade_id: self.last_trade_id,
+            trade_time: self.trade_time,
+            is_buyer_market_maker: self.is_buyer_market_maker,
+        }
+    }
+}
+
+/// Event time (`E`, ms) of a raw frame, looking inside combined-stream envelopes
+/// and taking the first element of array streams.
+pub fn frame_event_time_ms(frame: &str) -> Option<u64> {
+    FrameHeader::parse(frame)?.event_time_ms
+}
+
+/// The few top-level fields the session's stats need from every frame, read in a
+/// single pass without building a `Value`: the `data` of a combined-stream
+/// envelope is read instead of the envelope, and the first element of an array
+/// stream stands in for the whole array. Every other field is skipped unparsed.
+/// Strings are borrowed from the frame.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FrameHeader<'a> {
+    pub event_type: Option<&'a str>,
+    pub event_time_ms: Option<u64>,
+    pub symbol: Option<&'a str>,
+    pub trade_id: Option<u64>,
+}
+
+impl<'a> FrameHeader<'a> {
+    /// `None` when `frame` is not a JSON object or array.
+    pub fn parse(frame: &'a str) -> Option<Self> {
+        serde_json::from_str(frame).ok()
+    }
+
+    /// Symbol and trade id (`s`, `t`) of a `@trade` event.
+    pub fn trade_id(&self) -> Option<(&'a str, u64)> {
+        if self.event_type? != "trade" {
+            return None;
+        }
+        Some((self.symbol?, self.trade_id?))
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameHeader<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FrameHeaderVisitor)
+    }
+}
+
+struct FrameHeaderVisitor;
+
+impl<'de> Visitor<'de> for FrameHeaderVisitor {
+    type Value = FrameHeader<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON object or array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let first = seq.next_element::<FrameHeader>()?.unwrap_or_default();
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(first)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut header = FrameHeader::default();
+        while let Some(key) = map.next_key::<&str>()? {
+            match key {
+                "data" => header = map.next_value()?,
+                "e" => header.event_type = map.next_value()?,
+                "E" => header.event_time_ms = map.next_value()?,
+                "s" => header.symbol = map.next_value()?,
+                "t" => header.trade_id = map.next_value()?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRADE: &str = r#"{"e":"trade","E":1672515782136,"s":"BNBBTC","t":12345,"p":"0.001","q":"100","T":1672515782136,"m":true,"M":true}"#;
+
+    fn variant(message: &BinanceMessage) -> &'static str {
+        match message {
+            BinanceMessage::Event(_) => "Event",
+            BinanceMessage::ArrayEvent(_) => "ArrayEvent",
+            BinanceMessage::BookTicker(_) => "BookTicker",
+            BinanceMessage::PartialDepth(_) => "PartialDepth",
+            BinanceMessage::ErrorResponse { .. } => "ErrorResponse",
+            BinanceMessage::SubscriptionResponse { .. } => "SubscriptionResponse",
+            BinanceMessage::Other(_) => "Other",
+        }
+    }
+
+    fn parse(json: &str) -> BinanceMessage {
+        serde_json::from_str(json).unwrap_or_else(|e| panic!("{}: {}", json, e))
+    }
+
+    /// The untagged enum tries variants in declaration order, so each fixture pins
+    /// the variant it must land in.
+    #[test]
+    fn each_frame_shape_parses_to_its_variant() {
+        let array = format!("[{},{}]", TRADE, TRADE);
+        let cases = [
+            ("trade event", TRADE, "Event"),
+            ("array stream", array.as_str(), "ArrayEvent"),
+            (
+                "bookTicker",
+                r#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#,
+                "BookTicker",
+            ),
+            (
+                "partial depth",
+                r#"{"lastUpdateId":160,"bids":[["0.0024","10"]],"asks":[["0.0026","100"]]}"#,
+                "PartialDepth",
+            ),
+            (
+                "error reply",
+                r#"{"error":{"code":2,"msg":"Invalid request: unknown variable"},"id":1}"#,
+                "ErrorResponse",
+            ),
+            (
+                "error reply without id",
+                r#"{"error":{"code":3,"msg":"Invalid JSON"},"id":null}"#,
+                "ErrorResponse",
+            ),
+            (
+                "subscribe ack",
+                r#"{"result":null,"id":1}"#,
+                "SubscriptionResponse",
+            ),
+            (
+                "list subscriptions reply",
+                r#"{"result":["btcusdt@trade"],"id":3}"#,
+                "SubscriptionResponse",
+            ),
+            (
+                "unknown event type",
+                r#"{"e":"somethingNew","E":1,"s":"BTCUSDT"}"#,
+                "Other",
+            ),
+            ("array of non-events", "[1,2,3]", "Other"),
+            ("empty object", "{}", "Other"),
+        ];
+
+        for (name, json, expected) in cases {
+            assert_eq!(variant(&parse(json)), expected, "{}", name);
+        }
+    }
+
+    fn book_ticker(json: &str) -> BookTickerEvent {
+        match parse(json) {
+            BinanceMessage::BookTicker(ticker) => ticker,
+            other => panic!("not a BookTicker: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spot_book_ticker_parses() {
+        let ticker = book_ticker(
+            r#"{"u":400900217,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#,
+        );
+        assert_eq!(ticker.update_id, 400900217);
+        assert_eq!(ticker.symbol, "BNBUSDT");
+        assert_eq!(ticker.best_bid_price.to_string(), "25.35190000");
+        assert_eq!(ticker.best_bid_qty.to_string(), "31.21000000");
+        assert_eq!(ticker.best_ask_price.to_string(), "25.36520000");
+        assert_eq!(ticker.best_ask_qty.to_string(), "40.66000000");
+    }
+
+    /// Futures adds `e`, `E` and `T`; `"e":"bookTicker"` has no `BinanceEvent`
+    /// variant, so the frame must still fall through to `BookTicker`.
+    #[test]
+    fn futures_book_ticker_parses() {
+        let ticker = book_ticker(
+            r#"{"e":"bookTicker","u":400900217,"E":1568014460893,"T":1568014460891,"s":"BNBUSDT","b":"25.35190000","B":"31.21000000","a":"25.36520000","A":"40.66000000"}"#,
+        );
+        assert_eq!(ticker.update_id, 400900217);
+        assert_eq!(ticker.symbol, "BNBUSDT");
+        assert_eq!(ticker.best_bid_price.to_string(), "25.35190000");
+        assert_eq!(ticker.best_ask_qty.to_string(), "40.66000000");
+    }
+
+    #[test]
+    fn force_order_parses_the_nested_order() {
+        let message = parse(
+            r#"{"e":"forceOrder","E":1568014460893,"o":{"s":"BTCUSDT","S":"SELL","o":"LIMIT","f":"IOC","q":"0.014","p":"9910","ap":"9910","X":"FILLED","l":"0.014","z":"0.014","T":1568014460893}}"#,
+        );
+        let BinanceMessage::Event(BinanceEvent::ForceOrder(liquidation)) = message else {
+            panic!("not a ForceOrder: {:?}", message);
+        };
+        assert_eq!(liquidation.event_time, 1568014460893);
+        let order = &liquidation.order;
+        assert_eq!(order.symbol, "BTCUSDT");
+        assert_eq!(order.side, "SELL");
+        assert_eq!(order.order_type, "LIMIT");
+        assert_eq!(order.quantity.to_string(), "0.014");
+        assert_eq!(order.price.to_string(), "9910");
+        assert_eq!(order.average_price.to_string(), "9910");
+        assert_eq!(order.order_status, "FILLED");
+        assert_eq!(order.trade_time, 1568014460893);
+        assert_eq!(order.notional().to_string(), "138.740");
+    }
+
+    /// Decimals keep Binance's string exactly: scale, trailing zeros and every digit.
+    #[test]
+    fn decimal_fields_round_trip_exactly() {
+        let trade = r#"{"e":"trade","E":1,"s":"SHIBUSDT","t":1,"p":"0.00000100","q":"98765432109876.54321000","T":1,"m":false}"#;
+        let BinanceMessage::Event(BinanceEvent::Trade(trade)) = parse(trade) else {
+            panic!("not a trade");
+        };
+        assert_eq!(trade.price.to_string(), "0.00000100");
+        assert_eq!(trade.price.scale(), 8);
+        assert_eq!(trade.quantity.to_string(), "98765432109876.54321000");
+        assert_eq!(
+            (trade.price * trade.quantity).normalize().to_string(),
+            "98765432.10987654321"
+        );
+
+        let depth: DepthSnapshot = serde_json::from_str(
+            r#"{"lastUpdateId":1,"bids":[["0.00000001","79228162514.26433759"]],"asks":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(depth.bids[0].0.to_string(), "0.00000001");
+        assert_eq!(depth.bids[0].1.to_string(), "79228162514.26433759");
+    }
+
+    #[test]
+    fn frame_header_reads_event_time_through_envelopes_and_arrays() {
+        let time = |frame: &str| FrameHeader::parse(frame).and_then(|h| h.event_time_ms);
+        assert_eq!(time(TRADE), Some(1672515782136));
+        assert_eq!(
+            time(&format!(r#"{{"stream":"bnbbtc@trade","data":{}}}"#, TRADE)),
+            Some(1672515782136)
+        );
+        assert_eq!(
+            time(r#"[{"e":"24hrMiniTicker","E":7,"s":"A"},{"e":"24hrMiniTicker","E":8,"s":"B"}]"#),
+            Some(7)
+        );
+        assert_eq!(
+            time(r#"{"stream":"!miniTicker@arr","data":[{"e":"24hrMiniTicker","E":9}]}"#),
+            Some(9)
+        );
+        assert_eq!(time(r#"{"result":null,"id":1}"#), None);
+        assert_eq!(FrameHeader::parse("not json"), None);
+    }
+
+    #[test]
+    fn frame_header_picks_out_trade_ids_only_for_trades() {
+        let header = FrameHeader::parse(TRADE).unwrap();
+        assert_eq!(header.trade_id(), Some(("BNBBTC", 12345)));
+        let envelope = format!(r#"{{"stream":"bnbbtc@trade","data":{}}}"#, TRADE);
+        assert_eq!(
+            FrameHeader::parse(&envelope).unwrap().trade_id(),
+            Some(("BNBBTC", 12345))
+        );
+
+        let agg_trade = r#"{"e":"aggTrade","E":1,"s":"BNBBTC","a":5,"p":"1","q":"1","f":1,"l":2,"T":1,"m":true}"#;
+        assert_eq!(FrameHeader::parse(agg_trade).unwrap().trade_id(), None);
+        // Nested objects such as a kline's `k` are skipped, not searched.
+        let kline = r#"{"e":"kline","E":1,"s":"BNBBTC","k":{"t":123,"s":"BNBBTC"}}"#;
+        let header = FrameHeader::parse(kline).unwrap();
+        assert_eq!(header.trade_id, None);
+        assert_eq!(header.trade_id(), None);
+    }
+
+    #[test]
+    fn error_reply_keeps_code_and_message() {
+        let BinanceMessage::ErrorResponse { error, id } =
+            parse(r#"{"error":{"code":2,"msg":"Invalid request"},"id":7}"#)
+        else {
+            panic!("not an ErrorResponse");
+        };
+        assert_eq!(
+            (error.code, error.msg.as_str(), id),
+            (2, "Invalid request", Some(7))
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use crate::models::{DepthSnapshot, DepthUpdateEvent, PriceLevel};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Local L2 order book rebuilt from a REST snapshot plus the depth diff stream.
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    pub last_update_id: u64,
+    pub bids: BTreeMap<Decimal, Decimal>,
+    pub asks: BTreeMap<Decimal, Decimal>,
+}
+
+impl OrderBook {
+    pub fn from_snapshot(snapshot: &DepthSnapshot) -> Self {
+        let mut book = Self {
+            last_update_id: snapshot.last_update_id,
+            ..Self::default()
+        };
+        apply_levels(&mut book.bids, &snapshot.bids);
+        apply_levels(&mut book.asks, &snapshot.asks);
+        book
+    }
+
+    /// Applies a diff event, returning `false` if it was stale (already covered by the book).
+    pub fn apply_diff(&mut self, update: &DepthUpdateEvent) -> bool {
+        if update.final_update_id <= self.last_update_id {
+            return false;
+        }
+
+        apply_levels(&mut self.bids, &update.bids);
+        apply_levels(&mut self.asks, &update.asks);
+        self.last_update_id = update.final_update_id;
+        true
+    }
+
+    /// Whether diffs were missed between the book and `update`: futures events
+    /// must chain through `pu`, spot ones must start at `lastUpdateId + 1` or earlier.
+    pub fn has_gap(&self, update: &DepthUpdateEvent) -> bool {
+        match update.previous_final_update_id {
+            Some(previous) => previous != self.last_update_id,
+            None => update.first_update_id > self.last_update_id + 1,
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<PriceLevel> {
+        self.bids.iter().next_back().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn best_ask(&self) -> Option<PriceLevel> {
+        self.asks.iter().next().map(|(p, q)| (*p, *q))
+    }
+}
+
+fn apply_levels(side: &mut BTreeMap<Decimal, Decimal>, levels: &[PriceLevel]) {
+    for (price, quantity) in levels {
+        if quantity.is_zero() {
+            side.remove(price);
+        } else {
+            side.insert(*price, *quantity);
+        }
+    }
+}
+
+/// Implements Binance's snapshot-then-diff sync: diffs are buffered until a
+/// snapshot arrives, then replayed starting from the first event that straddles
+/// `lastUpdateId + 1`. A gap in the live diffs discards the book and starts over.
+#[derive(Debug, Default)]
+pub struct OrderBookSync {
+    buffer: Vec<DepthUpdateEvent>,
+    book: Option<OrderBook>,
+    resyncs_total: u64,
+}
+
+impl OrderBookSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn book(&self) -> Option<&OrderBook> {
+        self.book.as_ref()
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.book.is_some()
+    }
+
+    /// Times the book was discarded because of a gap in the diff stream.
+    pub fn resyncs_total(&self) -> u64 {
+        self.resyncs_total
+    }
+
+    /// Drops the book and any buffered diffs, e.g. after a reconnect: the diffs sent
+    /// while disconnected are gone, so sync has to start over from a new snapshot.
+    pub fn reset(&mut self) {
+        self.book = None;
+        self.buffer.clear();
+    }
+
+    /// Buffers the diff until synced; afterwards applies it and returns whether it changed the book.
+    /// A diff that doesn't continue the book means updates were missed: the book is
+    /// dropped and the diff buffered, so `is_synced` turns false until a new snapshot.
+    pub fn on_diff(&mut self, update: DepthUpdateEvent) -> bool {
+        let Some(book) = self.book.as_mut() else {
+            self.buffer.push(update);
+            return false;
+        };
+        if update.final_update_id > book.last_update_id && book.has_gap(&update) {
+            warn!(
+                "Depth gap for {}: book is at update {}, next diff covers {}-{}{}; discarding the book and resyncing.",
+                update.symbol,
+                book.last_update_id,
+                update.first_update_id,
+                update.final_update_id,
+                update
+                    .previous_final_update_id
+                    .map_or(String::new(), |previous| format!(" (pu {})", previous))
+            );
+            self.book = None;
+            self.resyncs_total += 1;
+            self.buffer.push(update);
+            return false;
+        }
+        book.apply_diff(&update)
+    }
+
+    /// Seeds the book from `snapshot` and replays buffered diffs. Returns `false` when
+    /// the snapshot predates the buffered diffs, or when the buffered diffs themselves
+    /// have a gap; either way a newer snapshot is needed.
+    pub fn on_snapshot(&mut self, snapshot: &DepthSnapshot) -> bool {
+        let next_id = snapshot.last_update_id + 1;
+        self.buffer
+            .retain(|update| update.final_update_id >= next_id);
+
+        if let Some(first) = self.buffer.first() {
+            if first.first_update_id > next_id {
+                return false;
+            }
+        }
+
+        // The first diff straddles the snapshot; every later one must chain onto
+        // the book exactly as live diffs do in `on_diff`.
+        let mut book = OrderBook::from_snapshot(snapshot);
+        let mut pending = std::mem::take(&mut self.buffer).into_iter();
+        if let Some(first) = pending.next() {
+            book.apply_diff(&first);
+        }
+        while let Some(update) = pending.next() {
+            if book.has_gap(&update) {
+                warn!(
+                    "Depth gap for {} while replaying buffered diffs: book is at update {}, next diff covers {}-{}; fetching a newer snapshot.",
+                    update.symbol, book.last_update_id, update.first_update_id, update.final_update_id
+                );
+                self.resyncs_total += 1;
+                self.buffer.push(update);
+                self.buffer.extend(pending);
+                return false;
+            }
+            book.apply_diff(&update);
+        }
+        self.book = Some(book);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(last_update_id: u64) -> DepthSnapshot {
+        DepthSnapshot {
+            last_update_id,
+            bids: vec![(Decimal::from(100), Decimal::ONE)],
+            asks: vec![(Decimal::from(101), Decimal::ONE)],
+        }
+    }
+
+    fn diff(first: u64, last: u64, previous: Option<u64>) -> DepthUpdateEvent {
+        DepthUpdateEvent {
+            event_time: 0,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            previous_final_update_id: previous,
+            bids: vec![(Decimal::from(100), Decimal::from(last))],
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replays_contiguous_buffered_diffs() {
+        let mut sync = OrderBookSync::new();
+        sync.on_diff(diff(5, 12, None));
+        sync.on_diff(diff(13, 20, None));
+        assert!(sync.on_snapshot(&snapshot(10)));
+        assert_eq!(sync.book().unwrap().last_update_id, 20);
+        assert_eq!(sync.resyncs_total(), 0);
+    }
+
+    #[test]
+    fn spot_gap_in_buffered_diffs_needs_a_new_snapshot() {
+        let mut sync = OrderBookSync::new();
+        sync.on_diff(diff(5, 12, None));
+        sync.on_diff(diff(15, 20, None));
+        assert!(!sync.on_snapshot(&snapshot(10)));
+        assert!(!sync.is_synced());
+        assert_eq!(sync.resyncs_total(), 1);
+
+        // The diff after the gap stays buffered, so a newer snapshot syncs.
+        assert!(sync.on_snapshot(&snapshot(16)));
+        assert_eq!(sync.book().unwrap().last_update_id, 20);
+    }
+
+    #[test]
+    fn futures_gap_in_buffered_diffs_needs_a_new_snapshot() {
+        let mut sync = OrderBookSync::new();
+        sync.on_diff(diff(5, 12, Some(4)));
+        sync.on_diff(diff(14, 20, Some(13)));
+        assert!(!sync.on_snapshot(&snapshot(10)));
+        assert!(!sync.is_synced());
+        assert_eq!(sync.resyncs_total(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::models::TradeEvent;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const CSV_HEADER: &str = "trade_time,symbol,price,quantity,is_maker";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Appends trades to a CSV file, flushing on a timer instead of per row.
+pub struct CsvTradeWriter {
+    writer: BufWriter<File>,
+    last_flush: Instant,
+}
+
+impl CsvTradeWriter {
+    /// Opens `path` in append mode, writing the header only when the file is new or empty.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_new = file.metadata()?.len() == 0;
+
+        let mut writer = BufWriter::new(file);
+        if is_new {
+            writeln!(writer, "{}", CSV_HEADER)?;
+        }
+
+        Ok(Self {
+            writer,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn write_trade(&mut self, trade: &TradeEvent) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{},{},{},{},{}",
+            trade.trade_time,
+            trade.symbol,
+            trade.price,
+            trade.quantity,
+            trade.is_buyer_market_maker
+        )?;
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+}
+
+/// Writes raw text frames verbatim, one per line, before any parsing, so the
+/// capture keeps fields the typed structs drop. Pairs with `replay::replay_file`.
+pub struct FrameRecorder {
+    writer: BufWriter<File>,
+    last_flush: Instant,
+}
+
+impl FrameRecorder {
+    /// With `append`, continues an existing capture; otherwise an existing file is
+    /// rotated to `<path>.1` (replacing any previous `.1`) and a fresh one started.
+    pub fn open(path: impl AsRef<Path>, append: bool) -> io::Result<Self> {
+        let path = path.as_ref();
+        if !append && path.exists() {
+            let mut rotated = OsString::from(path.as_os_str());
+            rotated.push(".1");
+            fs::rename(path, PathBuf::from(rotated))?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, frame: &str) -> io::Result<()> {
+        // Frames are single-line JSON; guard anyway so one frame is always one line.
+        if frame.contains('\n') {
+            writeln!(self.writer, "{}", frame.replace('\n', " "))?;
+        } else {
+            writeln!(self.writer, "{}", frame)?;
+        }
+
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+}
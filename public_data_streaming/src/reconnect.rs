@@ -0,0 +1,271 @@
+use binance_common::jitter::with_jitter;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
+use tokio_tungstenite::tungstenite::Error as WsError;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+pub const RECONNECT_INITIAL_BACKOFF_SECS: u64 = 1;
+pub const RECONNECT_MAX_BACKOFF_SECS: u64 = 60;
+/// A connection that stayed up this long resets the backoff and attempt count.
+pub const RECONNECT_STABLE_CONNECTION_SECS: u64 = 30;
+/// Consecutive failed attempts before giving up; resets after a stable connection.
+pub const RECONNECT_MAX_ATTEMPTS: u32 = 10;
+/// Delay of the `fixed` reconnect policy when none is given.
+pub const DEFAULT_FIXED_RECONNECT_DELAY_SECS: u64 = 3;
+/// After a fatal error the next reconnect waits at least this long, whatever the policy says.
+pub const FATAL_ERROR_RECONNECT_DELAY_SECS: u64 = 30;
+/// Values accepted by `parse_policy`.
+pub const RECONNECT_POLICY_NAMES: [&str; 4] = ["fixed", "fixed:<secs>", "exponential", "none"];
+
+/// Exponential reconnect backoff shared by the fixed-URL bins: 1s doubling to
+/// 60s, giving up after `RECONNECT_MAX_ATTEMPTS` consecutive short-lived connections.
+/// Each returned delay carries `with_jitter`.
+#[derive(Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    stable_threshold: Duration,
+    max_attempts: u32,
+    current: Duration,
+    attempt: u32,
+    total_reconnects: u64,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        let initial = Duration::from_secs(RECONNECT_INITIAL_BACKOFF_SECS);
+        Self {
+            initial,
+            max: Duration::from_secs(RECONNECT_MAX_BACKOFF_SECS),
+            stable_threshold: Duration::from_secs(RECONNECT_STABLE_CONNECTION_SECS),
+            max_attempts: RECONNECT_MAX_ATTEMPTS,
+            current: initial,
+            attempt: 0,
+            total_reconnects: 0,
+        }
+    }
+
+    /// Call after a connection ends (or fails to open) having lasted `connected_for`.
+    /// Returns the delay before the next attempt, or `None` once attempts are exhausted.
+    pub fn next_delay(&mut self, connected_for: Duration) -> Option<Duration> {
+        if connected_for > self.stable_threshold {
+            self.current = self.initial;
+            self.attempt = 0;
+        }
+
+        self.attempt += 1;
+        if self.attempt > self.max_attempts {
+            return None;
+        }
+
+        let delay = with_jitter(self.current);
+        self.current = (self.current * 2).min(self.max);
+        self.total_reconnects += 1;
+        Some(delay)
+    }
+
+    /// Attempt number of the pending reconnect (1-based).
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Reconnects scheduled over the whole run.
+    pub fn total_reconnects(&self) -> u64 {
+        self.total_reconnects
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decides whether and how long to wait before reconnecting. `attempt` counts
+/// consecutive reconnects (1-based) and restarts after a stable connection;
+/// returning `None` stops retrying.
+pub trait ReconnectPolicy: Send {
+    fn next_delay(&mut self, attempt: u32) -> Option<Duration>;
+}
+
+/// The same delay before every attempt, optionally giving up after `max_attempts`.
+#[derive(Debug)]
+pub struct FixedDelay {
+    delay: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl FixedDelay {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            max_attempts: None,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+}
+
+impl ReconnectPolicy for FixedDelay {
+    fn next_delay(&mut self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt > max) {
+            return None;
+        }
+        Some(with_jitter(self.delay))
+    }
+}
+
+/// `initial` doubling per attempt up to `max`, optionally giving up after
+/// `max_attempts`. Defaults match `Backoff`: 1s to 60s, 10 attempts.
+#[derive(Debug)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+    max_attempts: Option<u32>,
+}
+
+impl ExponentialBackoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            max_attempts: None,
+        }
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(RECONNECT_INITIAL_BACKOFF_SECS),
+            Duration::from_secs(RECONNECT_MAX_BACKOFF_SECS),
+        )
+        .with_max_attempts(RECONNECT_MAX_ATTEMPTS)
+    }
+}
+
+impl ReconnectPolicy for ExponentialBackoff {
+    fn next_delay(&mut self, attempt: u32) -> Option<Duration> {
+        if self.max_attempts.is_some_and(|max| attempt > max) {
+            return None;
+        }
+        let doublings = attempt.saturating_sub(1).min(31);
+        let delay = self.initial.saturating_mul(1 << doublings).min(self.max);
+        Some(with_jitter(delay))
+    }
+}
+
+/// Never reconnects: the first disconnect ends the run.
+#[derive(Debug, Default)]
+pub struct NoReconnect;
+
+impl ReconnectPolicy for NoReconnect {
+    fn next_delay(&mut self, _attempt: u32) -> Option<Duration> {
+        None
+    }
+}
+
+/// Parses a `--reconnect` value: `fixed` (3s), `fixed:<secs>`, `exponential` or `none`.
+pub fn parse_policy(value: &str) -> Option<Box<dyn ReconnectPolicy>> {
+    match value.to_ascii_lowercase().as_str() {
+        "fixed" => Some(Box::new(FixedDelay::new(Duration::from_secs(
+            DEFAULT_FIXED_RECONNECT_DELAY_SECS,
+        )))),
+        "exponential" => Some(Box::new(ExponentialBackoff::default())),
+        "none" => Some(Box::new(NoReconnect)),
+        other => {
+            let secs = other.strip_prefix("fixed:")?.parse::<u64>().ok()?;
+            Some(Box::new(FixedDelay::new(Duration::from_secs(secs))))
+        }
+    }
+}
+
+/// Whether the error that ended a connection is likely to clear up on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Dropped connections and network failures; reconnect on the normal schedule.
+    Transient,
+    /// Protocol violations, malformed or oversized frames, TLS failures and rejected
+    /// handshakes. An immediate retry would most likely fail the same way.
+    Fatal,
+}
+
+impl ErrorClass {
+    /// Classifies an error from connecting or running a session. Anything that
+    /// isn't a websocket error is transient, except an unparsable URL.
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<WsError>() {
+            return Self::of_ws(error);
+        }
+        if error.is::<url::ParseError>() {
+            return ErrorClass::Fatal;
+        }
+        ErrorClass::Transient
+    }
+
+    pub fn of_ws(error: &WsError) -> Self {
+        match error {
+            // A reset without a close frame is just a dropped TCP connection.
+            WsError::Protocol(
+                ProtocolError::ResetWithoutClosingHandshake
+                | ProtocolError::SendAfterClosing
+                | ProtocolError::ReceivedAfterClosing
+                | ProtocolError::HandshakeIncomplete,
+            ) => ErrorClass::Transient,
+            WsError::Protocol(_)
+            | WsError::Capacity(_)
+            | WsError::Utf8
+            | WsError::AttackAttempt
+            | WsError::Url(_)
+            | WsError::Tls(_)
+            | WsError::HttpFormat(_) => ErrorClass::Fatal,
+            // 429 and 418 (IP ban) also want the longer wait.
+            WsError::Http(response) if !response.status().is_server_error() => ErrorClass::Fatal,
+            _ => ErrorClass::Transient,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorClass::Transient => "transient",
+            ErrorClass::Fatal => "fatal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The reconnect delay to use after a connection ended on `last_error`: `delay` as
+/// is for transient errors (or none), stretched to `FATAL_ERROR_RECONNECT_DELAY_SECS`
+/// for fatal ones, logging the change.
+pub fn delay_after(last_error: Option<ErrorClass>, delay: Duration) -> Duration {
+    let fatal_delay = Duration::from_secs(FATAL_ERROR_RECONNECT_DELAY_SECS);
+    if last_error != Some(ErrorClass::Fatal) || delay >= fatal_delay {
+        return delay;
+    }
+    warn!(
+        "Last error was fatal; waiting {:?} instead of {:?} before reconnecting.",
+        fatal_delay, delay
+    );
+    fatal_delay
+}
@@ -0,0 +1,58 @@
+use crate::models::frame_event_time_ms;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Feeds each non-empty line of a JSON-lines capture to `on_message`.
+///
+/// With `realtime`, waits between lines for the gap between their event times
+/// (`E`, looked up inside combined-stream envelopes and arrays too); lines
+/// without an event time are delivered immediately. Returns the number of lines fed.
+pub async fn replay_file<F>(
+    path: impl AsRef<Path>,
+    realtime: bool,
+    mut on_message: F,
+) -> io::Result<usize>
+where
+    F: FnMut(&str),
+{
+    let reader = BufReader::new(File::open(path)?);
+    let mut previous_event_time = None;
+    let mut count = 0;
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if realtime {
+            if let Some(event_time) = frame_event_time_ms(line) {
+                if let Some(previous) = previous_event_time {
+                    if event_time > previous {
+                        tokio::time::sleep(Duration::from_millis(event_time - previous)).await;
+                    }
+                }
+                previous_event_time = Some(event_time);
+            }
+        }
+
+        on_message(line);
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// True for payloads wrapped in the combined-stream `{"stream", "data"}` envelope.
+pub fn is_combined_frame(line: &str) -> bool {
+    serde_json::from_str::<Value>(line)
+        .map(|value| value.get("stream").is_some() && value.get("data").is_some())
+        .unwrap_or(false)
+}
@@ -0,0 +1,105 @@
+//! Request/response correlation for websocket APIs that answer requests by id
+//! (SUBSCRIBE/UNSUBSCRIBE/LIST_SUBSCRIPTIONS, the WebSocket trading API, ...).
+
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A request awaiting its response, with the time it was sent for timeout checks.
+#[derive(Debug)]
+struct InFlight<T> {
+    request: T,
+    sent_at: Instant,
+}
+
+/// Hands out request ids and holds each sent request's context until its
+/// response arrives or it times out. Ids keep counting across `clear`, so a
+/// late response from a dropped connection can never match a new request.
+#[derive(Debug)]
+pub struct RequestTracker<T> {
+    next_id: i64,
+    in_flight: HashMap<i64, InFlight<T>>,
+    timeout: Duration,
+}
+
+impl<T> RequestTracker<T> {
+    /// Requests unanswered for `timeout` are returned by `expire`.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            next_id: 1,
+            in_flight: HashMap::new(),
+            timeout,
+        }
+    }
+
+    pub fn next_id(&mut self) -> i64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Starts the timeout clock for `id`; call once the request is actually sent.
+    pub fn register(&mut self, id: i64, request: T) {
+        self.in_flight.insert(
+            id,
+            InFlight {
+                request,
+                sent_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Removes and returns the request answered by `id`; `None` for unknown
+    /// (or already expired) ids.
+    pub fn resolve(&mut self, id: i64) -> Option<T> {
+        self.in_flight
+            .remove(&id)
+            .map(|in_flight| in_flight.request)
+    }
+
+    /// Removes and returns the requests older than the timeout, oldest id first.
+    pub fn expire(&mut self) -> Vec<(i64, T)> {
+        let mut expired = self
+            .in_flight
+            .iter()
+            .filter(|(_, in_flight)| in_flight.sent_at.elapsed() >= self.timeout)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        expired.sort_unstable();
+
+        expired
+            .into_iter()
+            .filter_map(|id| self.resolve(id).map(|request| (id, request)))
+            .collect()
+    }
+
+    /// Forgets every request in flight (e.g. after a reconnect), returning how many were dropped.
+    pub fn clear(&mut self) -> usize {
+        let dropped = self.in_flight.len();
+        self.in_flight.clear();
+        dropped
+    }
+
+    /// In-flight requests as `(id, age, request)`, oldest id first.
+    pub fn in_flight(&self) -> Vec<(i64, Duration, &T)> {
+        let mut requests = self
+            .in_flight
+            .iter()
+            .map(|(id, in_flight)| (*id, in_flight.sent_at.elapsed(), &in_flight.request))
+            .collect::<Vec<_>>();
+        requests.sort_unstable_by_key(|(id, _, _)| *id);
+        requests
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+}
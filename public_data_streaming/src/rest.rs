@@ -0,0 +1,328 @@
+use crate::endpoints::Market;
+use crate::models::{AggTrade, AggTradeEvent, DepthSnapshot, Kline, RecentTrade};
+use crate::proxy;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Limits accepted by both the spot and futures depth endpoints.
+pub const DEPTH_SNAPSHOT_LIMITS: [u16; 7] = [5, 10, 20, 50, 100, 500, 1000];
+/// Largest `limit` of the recent trades endpoints (both default to 500).
+pub const MAX_RECENT_TRADES: u16 = 1000;
+/// Most candles the klines endpoints return per request; longer ranges are paged.
+pub const KLINES_PAGE_LIMIT: usize = 1000;
+/// Most aggregate trades the aggTrades endpoints return per request.
+pub const AGG_TRADES_PAGE_LIMIT: usize = 1000;
+// A time-ranged aggTrades request must span less than an hour.
+const AGG_TRADES_MAX_WINDOW_MS: u64 = 60 * 60 * 1000 - 1;
+// Pause between kline or aggTrades pages. A page costs up to 10 request weight,
+// so this stays well inside the per-minute weight budget even for long ranges.
+const PAGE_DELAY_MS: u64 = 250;
+// A 429 without a usable Retry-After header waits this long before retrying.
+const DEFAULT_RETRY_AFTER_SECS: u64 = 5;
+const MAX_RATE_LIMITED_RETRIES: u32 = 3;
+
+/// One row of the klines endpoints: a JSON array rather than an object.
+#[derive(Debug, Deserialize)]
+struct KlineRow(
+    u64,     // Open time
+    Decimal, // Open
+    Decimal, // High
+    Decimal, // Low
+    Decimal, // Close
+    Decimal, // Volume
+    u64,     // Close time
+    Decimal, // Quote asset volume
+    u64,     // Number of trades
+    Decimal, // Taker buy base asset volume
+    Decimal, // Taker buy quote asset volume
+    String,  // Unused field
+);
+
+impl KlineRow {
+    /// REST klines carry no trade ids, so `first_trade_id`/`last_trade_id` are 0.
+    fn into_kline(self, symbol: &str, interval: &str, now_ms: u64) -> Kline {
+        Kline {
+            start_time: self.0,
+            close_time: self.6,
+            symbol: symbol.to_string(),
+            interval: interval.to_string(),
+            first_trade_id: 0,
+            last_trade_id: 0,
+            open_price: self.1,
+            close_price: self.4,
+            high_price: self.2,
+            low_price: self.3,
+            base_asset_volume: self.5,
+            number_of_trades: self.8,
+            is_closed: self.6 < now_ms,
+            quote_asset_volume: self.7,
+            taker_buy_base_asset_volume: self.9,
+            taker_buy_quote_asset_volume: self.10,
+            ignore: self.11,
+        }
+    }
+}
+
+fn depth_path(market: Market) -> &'static str {
+    if market.is_futures() {
+        "/fapi/v1/depth"
+    } else {
+        "/api/v3/depth"
+    }
+}
+
+fn klines_path(market: Market) -> &'static str {
+    if market.is_futures() {
+        "/fapi/v1/klines"
+    } else {
+        "/api/v3/klines"
+    }
+}
+
+fn agg_trades_path(market: Market) -> &'static str {
+    if market.is_futures() {
+        "/fapi/v1/aggTrades"
+    } else {
+        "/api/v3/aggTrades"
+    }
+}
+
+fn trades_path(market: Market) -> &'static str {
+    if market.is_futures() {
+        "/fapi/v1/trades"
+    } else {
+        "/api/v3/trades"
+    }
+}
+
+/// Fetches an order book snapshot from `GET /api/v3/depth` (spot) or `GET /fapi/v1/depth` (futures).
+pub async fn fetch_depth_snapshot(
+    rest_base_url: &str,
+    market: Market,
+    symbol: &str,
+    limit: u16,
+) -> Result<DepthSnapshot, Box<dyn Error>> {
+    if !DEPTH_SNAPSHOT_LIMITS.contains(&limit) {
+        return Err(format!(
+            "Unsupported depth limit {}; expected one of {:?}",
+            limit, DEPTH_SNAPSHOT_LIMITS
+        )
+        .into());
+    }
+
+    let url = format!(
+        "{}{}?symbol={}&limit={}",
+        rest_base_url,
+        depth_path(market),
+        symbol.to_uppercase(),
+        limit
+    );
+
+    get_json(&url).await
+}
+
+/// Fetches the latest `limit` trades, oldest first, from `GET /api/v3/trades`
+/// (spot) or `GET /fapi/v1/trades` (futures).
+pub async fn fetch_recent_trades(
+    rest_base_url: &str,
+    market: Market,
+    symbol: &str,
+    limit: u16,
+) -> Result<Vec<RecentTrade>, Box<dyn Error>> {
+    if limit == 0 || limit > MAX_RECENT_TRADES {
+        return Err(format!(
+            "Unsupported trades limit {}; expected 1-{}",
+            limit, MAX_RECENT_TRADES
+        )
+        .into());
+    }
+
+    let url = format!(
+        "{}{}?symbol={}&limit={}",
+        rest_base_url,
+        trades_path(market),
+        symbol.to_uppercase(),
+        limit
+    );
+
+    get_json(&url).await
+}
+
+/// Fetches up to `limit` klines opening within `[start_ms, end_ms]`, oldest
+/// first, from `GET /api/v3/klines` (spot) or `GET /fapi/v1/klines` (futures).
+/// Ranges past the 1000-candle cap are fetched page by page, each starting
+/// after the previous page's last close time.
+pub async fn fetch_klines(
+    rest_base_url: &str,
+    market: Market,
+    symbol: &str,
+    interval: &str,
+    start_ms: u64,
+    end_ms: u64,
+    limit: usize,
+) -> Result<Vec<Kline>, Box<dyn Error>> {
+    let symbol = symbol.to_uppercase();
+    let mut klines = Vec::new();
+    let mut cursor = start_ms;
+
+    while cursor <= end_ms && klines.len() < limit {
+        if !klines.is_empty() {
+            tokio::time::sleep(Duration::from_millis(PAGE_DELAY_MS)).await;
+        }
+
+        let page_limit = (limit - klines.len()).min(KLINES_PAGE_LIMIT);
+        let url = format!(
+            "{}{}?symbol={}&interval={}&startTime={}&endTime={}&limit={}",
+            rest_base_url,
+            klines_path(market),
+            symbol,
+            interval,
+            cursor,
+            end_ms,
+            page_limit
+        );
+        let rows = get_json::<Vec<KlineRow>>(&url).await?;
+        let page_len = rows.len();
+        debug!("Fetched {} kline(s) starting at {}", page_len, cursor);
+
+        let now_ms = unix_ms();
+        klines.extend(
+            rows.into_iter()
+                .map(|row| row.into_kline(&symbol, interval, now_ms)),
+        );
+        // A short page means the range is exhausted.
+        if page_len < page_limit {
+            break;
+        }
+        cursor = match klines.last() {
+            Some(last) => last.close_time + 1,
+            None => break,
+        };
+    }
+
+    Ok(klines)
+}
+
+/// Fetches up to `limit` aggregate trades made within `[start_ms, end_ms]`, oldest
+/// first, from `GET /api/v3/aggTrades` (spot) or `GET /fapi/v1/aggTrades` (futures).
+///
+/// A time-ranged request may span at most an hour, so the range is walked in
+/// hour-long windows. A window holding more than a page of trades is continued
+/// by `fromId` instead, since Binance rejects `fromId` combined with a time range;
+/// those pages run until a trade falls past `end_ms`.
+pub async fn fetch_agg_trades(
+    rest_base_url: &str,
+    market: Market,
+    symbol: &str,
+    start_ms: u64,
+    end_ms: u64,
+    limit: usize,
+) -> Result<Vec<AggTradeEvent>, Box<dyn Error>> {
+    if start_ms > end_ms {
+        return Err(format!("Start time {} is after end time {}", start_ms, end_ms).into());
+    }
+
+    let symbol = symbol.to_uppercase();
+    let mut trades = Vec::<AggTradeEvent>::new();
+    let mut window_start = start_ms;
+    let mut from_id = None;
+    let mut first_page = true;
+
+    while trades.len() < limit {
+        if !first_page {
+            tokio::time::sleep(Duration::from_millis(PAGE_DELAY_MS)).await;
+        }
+        first_page = false;
+
+        let page_limit = (limit - trades.len()).min(AGG_TRADES_PAGE_LIMIT);
+        let window_end = window_start
+            .saturating_add(AGG_TRADES_MAX_WINDOW_MS)
+            .min(end_ms);
+        let range = match from_id {
+            Some(from_id) => format!("fromId={}", from_id),
+            None => format!("startTime={}&endTime={}", window_start, window_end),
+        };
+        let url = format!(
+            "{}{}?symbol={}&{}&limit={}",
+            rest_base_url,
+            agg_trades_path(market),
+            symbol,
+            range,
+            page_limit
+        );
+        let rows = get_json::<Vec<AggTrade>>(&url).await?;
+        let page_len = rows.len();
+        debug!("Fetched {} aggregate trade(s) with {}", page_len, range);
+
+        let before = trades.len();
+        trades.extend(
+            rows.into_iter()
+                .take_while(|row| row.trade_time <= end_ms)
+                .map(|row| row.into_agg_trade_event(&symbol)),
+        );
+        // Id pages don't stop at `end_ms` on their own.
+        if trades.len() - before < page_len {
+            break;
+        }
+
+        if page_len == page_limit {
+            from_id = trades.last().map(|trade| trade.agg_trade_id + 1);
+        } else if from_id.is_some() || window_end >= end_ms {
+            // A short page means the window (or, paging by id, the whole history) is exhausted.
+            break;
+        } else {
+            window_start = window_end + 1;
+        }
+    }
+
+    Ok(trades)
+}
+
+/// GETs `url` and parses the JSON body. A 429 is retried after the server's
+/// `Retry-After`; a 418 (IP ban) or any other error status is returned.
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, Box<dyn Error>> {
+    let client = proxy::http_client()?;
+    let mut retries = 0;
+
+    loop {
+        let resp = client.get(url).send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp.json().await?);
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RATE_LIMITED_RETRIES {
+            let wait_secs = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .unwrap_or(DEFAULT_RETRY_AFTER_SECS);
+            retries += 1;
+            warn!(
+                "Rate limited (HTTP 429); retry {}/{} in {}s",
+                retries, MAX_RATE_LIMITED_RETRIES, wait_secs
+            );
+            tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+            continue;
+        }
+
+        let error_text = resp.text().await?;
+        return Err(format!("HTTP Error {}: {}", status, error_text).into());
+    }
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,416 @@
+use crate::clock::Clock;
+use crate::deflate::{self, CompressionCounters};
+use crate::metrics::Metrics;
+use crate::models;
+use crate::proxy;
+use crate::reconnect::ErrorClass;
+use crate::sink::{EventSink, FrameSink};
+use crate::stats::{SessionStats, StatsMode};
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use std::error::Error;
+use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval, Instant, Interval};
+use tokio_tungstenite::tungstenite::{self, protocol::Message};
+use url::Url;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+pub const STATS_INTERVAL_SECS: u64 = 5;
+/// Default unsolicited pong period. Server pings are always answered regardless.
+pub const UNSOLICITED_PONG_INTERVAL_SECS: u64 = 180;
+/// Overrides `UNSOLICITED_PONG_INTERVAL_SECS` when no CLI flag is given; `0` disables.
+pub const PONG_INTERVAL_ENV: &str = "BINANCE_PONG_INTERVAL_SECS";
+pub const CLOSE_DRAIN_TIMEOUT_SECS: u64 = 2;
+/// Default no-message threshold after which the session is treated as stale.
+pub const DEFAULT_STALE_TIMEOUT_SECS: u64 = 30;
+const WATCHDOG_CHECK_INTERVAL_SECS: u64 = 1;
+
+/// Boxed so that plain and `permessage-deflate` connections, whose stream types
+/// differ, fit the same session.
+type WsWrite = Pin<Box<dyn Sink<Message, Error = tungstenite::Error> + Send>>;
+type WsRead = Pin<Box<dyn Stream<Item = Result<Message, tungstenite::Error>> + Send>>;
+
+/// A connected public-stream websocket that owns the housekeeping shared by every
+/// bin: answering pings, the periodic stats line, and the unsolicited pong heartbeat.
+/// Callers only see text frames.
+pub struct WsSession {
+    write: WsWrite,
+    read: WsRead,
+    stats: SessionStats,
+    stats_mode: StatsMode,
+    print_stats_interval: Interval,
+    pong_interval: Option<Interval>,
+    watchdog_interval: Interval,
+    stale_timeout: Option<Duration>,
+    /// A liveness ping was sent while idle and nothing has arrived since.
+    liveness_ping_sent: bool,
+    metrics: Option<Metrics>,
+    /// Class of the read error that ended the session, if one did.
+    error_class: Option<ErrorClass>,
+}
+
+impl WsSession {
+    pub async fn connect(ws_url: &str) -> Result<Self, Box<dyn Error>> {
+        let url = Url::parse(ws_url)?;
+        let (write, read, compression) = open(url).await?;
+        info!("WebSocket handshake successful.");
+
+        let stale_timeout = Some(Duration::from_secs(DEFAULT_STALE_TIMEOUT_SECS));
+        let mut stats = SessionStats::new();
+        stats.set_label(stream_label(ws_url));
+        stats.set_stale_timeout(stale_timeout);
+        stats.set_compression(compression);
+        Ok(Self {
+            write,
+            read,
+            stats,
+            stats_mode: StatsMode::default(),
+            print_stats_interval: interval(Duration::from_secs(STATS_INTERVAL_SECS)),
+            pong_interval: pong_interval(UNSOLICITED_PONG_INTERVAL_SECS),
+            watchdog_interval: interval(Duration::from_secs(WATCHDOG_CHECK_INTERVAL_SECS)),
+            stale_timeout,
+            liveness_ping_sent: false,
+            metrics: None,
+            error_class: None,
+        })
+    }
+
+    /// Includes the processing queue's dropped-frame count in the periodic stats line.
+    pub fn with_frame_sink(mut self, sink: Option<&FrameSink>) -> Self {
+        self.stats
+            .set_dropped_frames(sink.map(FrameSink::dropped_counter));
+        self
+    }
+
+    /// Includes `sink`'s dropped-event count in the periodic stats line.
+    pub fn with_event_sink(self, sink: Option<&EventSink>) -> Self {
+        self.with_dropped_events(sink.map(EventSink::dropped_counter))
+    }
+
+    /// Like `with_event_sink`, for when the sink itself has moved into another task.
+    pub fn with_dropped_events(mut self, dropped: Option<Arc<AtomicU64>>) -> Self {
+        self.stats.set_dropped_events(dropped);
+        self
+    }
+
+    /// Chooses between the periodic stats log line (default) and the in-place table.
+    pub fn with_stats_mode(mut self, mode: StatsMode) -> Self {
+        self.stats_mode = mode;
+        self
+    }
+
+    /// Reconnects so far, shown in the stats table; the session itself never reconnects.
+    pub fn with_reconnects(mut self, reconnects: u64) -> Self {
+        self.stats.set_reconnects(reconnects);
+        self
+    }
+
+    /// Sends an unsolicited pong every `secs` seconds; `0` disables the heartbeat.
+    pub fn with_pong_interval(mut self, secs: u64) -> Self {
+        self.pong_interval = pong_interval(secs);
+        self
+    }
+
+    /// Ends the session (as if disconnected) when no text frame arrives for
+    /// `secs` seconds, so callers reconnect instead of waiting forever on a
+    /// silent socket. `0` disables the watchdog.
+    pub fn with_stale_timeout(mut self, secs: u64) -> Self {
+        self.stale_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+        self.stats.set_stale_timeout(self.stale_timeout);
+        self
+    }
+
+    /// Marks whether any streams are subscribed. With none, no messages are
+    /// expected, so instead of reconnecting on silence the watchdog pings the
+    /// server once half the stale timeout passes without frames, and only treats
+    /// the connection as stale when not even the pong comes back in time.
+    pub fn set_idle(&mut self, idle: bool) {
+        self.stats.set_idle(idle);
+    }
+
+    /// Times the stats and the stale watchdog with `clock` instead of the wall clock.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.stats.set_clock(clock);
+        self
+    }
+
+    /// Mirrors the message counter into shared `metrics` for the Prometheus endpoint.
+    pub fn with_metrics(mut self, metrics: Option<Metrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Waits for the next text frame, servicing pings and timers in the meantime.
+    /// Binary frames that decode as UTF-8 are returned as text too.
+    /// Returns `None` once the connection is closed, errors out, or goes stale.
+    ///
+    /// Safe to use inside `tokio::select!`; cancelling it can at worst drop an
+    /// in-flight pong reply.
+    pub async fn next_text(&mut self) -> Result<Option<String>, Box<dyn Error>> {
+        loop {
+            tokio::select! {
+                msg = self.read.next() => {
+                    if let Some(Ok(_)) = &msg {
+                        self.stats.record_frame();
+                        self.liveness_ping_sent = false;
+                    }
+                    match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            self.record_message(&text);
+                            return Ok(Some(text));
+                        }
+                        // Not expected from Binance, but a binary frame carrying
+                        // JSON is handled like the text frame it would have been.
+                        Some(Ok(Message::Binary(payload))) => {
+                            self.stats.record_binary_frame();
+                            match String::from_utf8(payload) {
+                                Ok(text) => {
+                                    self.record_message(&text);
+                                    return Ok(Some(text));
+                                }
+                                Err(e) => debug!(
+                                    "Ignoring {}-byte binary frame that is not UTF-8: {}",
+                                    e.as_bytes().len(),
+                                    e.utf8_error()
+                                ),
+                            }
+                        }
+                        Some(Ok(Message::Ping(payload))) => {
+                            info!("Received Ping, sending Pong.");
+                            self.write.send(Message::Pong(payload)).await?;
+                        }
+                        Some(Ok(Message::Close(frame))) => {
+                            if let Some(cf) = frame {
+                                info!("WebSocket closed: {:?}", cf);
+                            } else {
+                                info!("WebSocket closed without a close frame.");
+                            }
+                            return Ok(None);
+                        }
+                        Some(Err(e)) => {
+                            let class = ErrorClass::of_ws(&e);
+                            error!("WebSocket error ({}): {}", class, e);
+                            self.error_class = Some(class);
+                            return Ok(None);
+                        }
+                        None => {
+                            warn!("WebSocket stream ended.");
+                            return Ok(None);
+                        }
+                        _ => {}
+                    }
+                }
+                _ = self.print_stats_interval.tick() => {
+                    self.print_stats();
+                }
+                _ = optional_tick(&mut self.pong_interval) => {
+                    self.send_unsolicited_pong().await?;
+                }
+                _ = self.watchdog_interval.tick() => {
+                    if let Some(timeout) = self.stale_timeout {
+                        let silent_for = self.stats.watchdog_silence();
+                        if silent_for > timeout {
+                            if self.stats.is_idle() {
+                                warn!(
+                                    "No frames, not even a pong, for {:?} (limit {:?}) with nothing subscribed; treating connection as stale.",
+                                    silent_for, timeout
+                                );
+                            } else {
+                                warn!(
+                                    "No messages for {:?} (limit {:?}); treating stream as stale.",
+                                    silent_for, timeout
+                                );
+                            }
+                            return Ok(None);
+                        }
+                        // One check early: a pong landing just after a tick would
+                        // otherwise leave the ping to the tick that finds it stale.
+                        let ping_after = (timeout / 2).saturating_sub(Duration::from_secs(WATCHDOG_CHECK_INTERVAL_SECS));
+                        if self.stats.is_idle() && !self.liveness_ping_sent && silent_for > ping_after {
+                            debug!("Nothing subscribed and no frames for {:?}; sending a liveness ping.", silent_for);
+                            self.write.send(Message::Ping(Vec::new())).await?;
+                            self.liveness_ping_sent = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Feeds every text frame to `handler` until the connection ends or the
+    /// handler returns `false`.
+    pub async fn run<H>(&mut self, mut handler: H) -> Result<(), Box<dyn Error>>
+    where
+        H: FnMut(&str) -> bool,
+    {
+        while let Some(text) = self.next_text().await? {
+            if !handler(&text) {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn send_text(&mut self, text: String) -> Result<(), Box<dyn Error>> {
+        self.write.send(Message::Text(text)).await?;
+        Ok(())
+    }
+
+    pub async fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.write.send(Message::Close(None)).await?;
+        Ok(())
+    }
+
+    /// Sends a close frame and drains incoming frames until the server echoes the
+    /// close or `CLOSE_DRAIN_TIMEOUT_SECS` elapses.
+    pub async fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
+        self.close().await?;
+
+        let drain = async {
+            while let Some(msg) = self.read.next().await {
+                match msg {
+                    Ok(Message::Close(_)) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+        };
+
+        if tokio::time::timeout(Duration::from_secs(CLOSE_DRAIN_TIMEOUT_SECS), drain)
+            .await
+            .is_err()
+        {
+            warn!(
+                "Server did not acknowledge close within {}s.",
+                CLOSE_DRAIN_TIMEOUT_SECS
+            );
+        } else {
+            info!("WebSocket closed cleanly.");
+        }
+        Ok(())
+    }
+
+    /// Counters for this connection, e.g. to fold into a `RunSummary` when it ends.
+    pub fn stats(&self) -> &SessionStats {
+        &self.stats
+    }
+
+    /// How the read error that ended the session was classified; `None` when it
+    /// ended on a close frame, went stale, or is still running.
+    pub fn error_class(&self) -> Option<ErrorClass> {
+        self.error_class
+    }
+
+    /// Attributes the last message to `stream` for the top-N busiest streams in the stats.
+    pub fn record_stream(&mut self, stream: String) {
+        self.stats.record_stream(stream);
+    }
+
+    fn record_message(&mut self, text: &str) {
+        let time_since_last = self.stats.record_message();
+        if let Some(header) = models::FrameHeader::parse(text) {
+            if let Some(event_time_ms) = header.event_time_ms {
+                self.stats.record_latency(event_time_ms);
+            }
+            if let Some((symbol, trade_id)) = header.trade_id() {
+                self.stats.record_trade_id(symbol, trade_id);
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_message();
+        }
+        debug!("Time since last message: {:?}", time_since_last);
+    }
+
+    /// Logs the stats line now instead of waiting for the next interval, e.g. on exit.
+    pub fn print_stats(&mut self) {
+        self.stats.report(self.stats_mode);
+    }
+
+    async fn send_unsolicited_pong(&mut self) -> Result<(), Box<dyn Error>> {
+        debug!("Sending unsolicited pong heartbeat.");
+        self.write.send(Message::Pong(vec![])).await?;
+        Ok(())
+    }
+}
+
+/// Connects with `proxy::connect_ws`, or with `proxy::connect_ws_deflate` under
+/// `--compress`, in which case the counters come back once the server agrees.
+async fn open(
+    url: Url,
+) -> Result<(WsWrite, WsRead, Option<Arc<CompressionCounters>>), tungstenite::Error> {
+    if !deflate::enabled() {
+        let (ws_stream, _) = proxy::connect_ws(url).await?;
+        let (write, read) = ws_stream.split();
+        return Ok((Box::pin(write), Box::pin(read), None));
+    }
+    let (ws_stream, _) = proxy::connect_ws_deflate(url).await?;
+    let stream = ws_stream.get_ref();
+    let compression = stream.is_negotiated().then(|| stream.counters());
+    let (write, read) = ws_stream.split();
+    Ok((Box::pin(write), Box::pin(read), compression))
+}
+
+/// Resolves the pong interval with precedence: CLI flag > `PONG_INTERVAL_ENV` > default.
+pub fn resolve_pong_interval_secs(cli_value: Option<u64>) -> u64 {
+    if let Some(secs) = cli_value {
+        return secs;
+    }
+
+    match std::env::var(PONG_INTERVAL_ENV) {
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+            Ok(secs) => secs,
+            Err(_) => {
+                warn!(
+                    "Ignoring invalid {}={}; using {}s",
+                    PONG_INTERVAL_ENV, value, UNSOLICITED_PONG_INTERVAL_SECS
+                );
+                UNSOLICITED_PONG_INTERVAL_SECS
+            }
+        },
+        _ => UNSOLICITED_PONG_INTERVAL_SECS,
+    }
+}
+
+/// When a `--duration` run should stop; `0` means run until interrupted.
+pub fn run_deadline(duration_secs: u64) -> Option<Instant> {
+    (duration_secs > 0).then(|| Instant::now() + Duration::from_secs(duration_secs))
+}
+
+/// Completes at `deadline`, or never without one, so it can sit in a `select!`
+/// arm unconditionally.
+pub async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// The stream part of a connect URL (`btcusdt@trade`, `a/b` for combined), used
+/// as the stats table label.
+fn stream_label(ws_url: &str) -> String {
+    if let Some((_, streams)) = ws_url.split_once("streams=") {
+        return streams.to_string();
+    }
+    match ws_url.rsplit_once("/ws/") {
+        Some((_, stream)) => stream.to_string(),
+        None => ws_url.rsplit('/').next().unwrap_or(ws_url).to_string(),
+    }
+}
+
+fn pong_interval(secs: u64) -> Option<Interval> {
+    (secs > 0).then(|| interval(Duration::from_secs(secs)))
+}
+
+async fn optional_tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
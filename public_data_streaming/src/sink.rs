@@ -0,0 +1,146 @@
+use crate::models::{BinanceEvent, BinanceMessage};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// What to discard when the consumer falls behind and the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Keep the backlog, discard the event being sent.
+    DropNewest,
+    /// Evict the oldest buffered event to make room for the new one.
+    DropOldest,
+}
+
+impl DropPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "newest" | "drop-newest" => Some(Self::DropNewest),
+            "oldest" | "drop-oldest" => Some(Self::DropOldest),
+            _ => None,
+        }
+    }
+}
+
+struct Shared<T> {
+    queue: Mutex<VecDeque<T>>,
+    capacity: usize,
+    policy: DropPolicy,
+    notify: Notify,
+    dropped: Arc<AtomicU64>,
+    closed: AtomicBool,
+}
+
+/// Sending half of a bounded channel. Never blocks the websocket loop: when the
+/// buffer is full an item is dropped per `DropPolicy` and counted.
+///
+/// A hand-rolled queue rather than `tokio::sync::mpsc`, since drop-oldest needs
+/// the sender to evict from the buffer.
+pub struct Sink<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Receiving half, typically moved into a dedicated consumer task.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Typed events for consumers such as the SQLite writer.
+pub type EventSink = Sink<BinanceEvent>;
+pub type EventReceiver = Receiver<BinanceEvent>;
+
+/// Raw text frames handed from the socket loop to a processing task.
+pub type FrameSink = Sink<String>;
+pub type FrameReceiver = Receiver<String>;
+
+pub fn event_channel(capacity: usize, policy: DropPolicy) -> (EventSink, EventReceiver) {
+    channel(capacity, policy)
+}
+
+pub fn frame_channel(capacity: usize, policy: DropPolicy) -> (FrameSink, FrameReceiver) {
+    channel(capacity, policy)
+}
+
+fn channel<T>(capacity: usize, policy: DropPolicy) -> (Sink<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity: capacity.max(1),
+        policy,
+        notify: Notify::new(),
+        dropped: Arc::new(AtomicU64::new(0)),
+        closed: AtomicBool::new(false),
+    });
+
+    (
+        Sink {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+impl<T> Sink<T> {
+    pub fn send(&self, item: T) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.len() >= self.shared.capacity {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                match self.shared.policy {
+                    DropPolicy::DropNewest => return,
+                    DropPolicy::DropOldest => {
+                        queue.pop_front();
+                    }
+                }
+            }
+            queue.push_back(item);
+        }
+        self.shared.notify.notify_one();
+    }
+
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Shared handle to the drop counter, e.g. for `WsSession` stats.
+    pub fn dropped_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.shared.dropped)
+    }
+}
+
+impl EventSink {
+    /// Forwards every typed event in `message` (array frames are flattened).
+    pub fn send_message(&self, message: BinanceMessage) {
+        match message {
+            BinanceMessage::Event(event) => self.send(event),
+            BinanceMessage::ArrayEvent(events) => {
+                for event in events {
+                    self.send(event);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<T> Drop for Sink<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.notify.notify_one();
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next item; returns `None` once the sink is dropped and drained.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            if let Some(item) = self.shared.queue.lock().unwrap().pop_front() {
+                return Some(item);
+            }
+            if self.shared.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+}
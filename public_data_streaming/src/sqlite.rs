@@ -0,0 +1,110 @@
+use crate::models::{BinanceEvent, TradeEvent};
+use crate::sink::EventReceiver;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Commit once this many trades are buffered...
+pub const SQLITE_BATCH_SIZE: usize = 500;
+/// ...or after this long, whichever comes first.
+pub const SQLITE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Prices and quantities are stored as TEXT so Decimal values round-trip exactly.
+const CREATE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS trades (
+        trade_time INTEGER NOT NULL,
+        symbol     TEXT    NOT NULL,
+        price      TEXT    NOT NULL,
+        quantity   TEXT    NOT NULL,
+        is_maker   INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS trades_symbol_time ON trades (symbol, trade_time);
+";
+
+/// Opens (or creates) the database and ensures the `trades` table and index exist.
+pub fn open_trade_db(path: impl AsRef<Path>) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(CREATE_SCHEMA)?;
+    Ok(conn)
+}
+
+/// Spawns the writer task: trades from `events` are buffered and inserted in one
+/// transaction per batch, on a blocking thread so the websocket loop never waits
+/// on disk. The task drains and exits once the sending `EventSink` is dropped.
+pub fn spawn_trade_writer(conn: Connection, mut events: EventReceiver) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut conn = Some(conn);
+        let mut batch: Vec<TradeEvent> = Vec::with_capacity(SQLITE_BATCH_SIZE);
+        let mut flush_timer = tokio::time::interval(SQLITE_FLUSH_INTERVAL);
+
+        loop {
+            let closed = tokio::select! {
+                event = events.recv() => match event {
+                    Some(BinanceEvent::Trade(trade)) => {
+                        batch.push(trade);
+                        if batch.len() < SQLITE_BATCH_SIZE {
+                            continue;
+                        }
+                        false
+                    }
+                    Some(_) => continue,
+                    None => true,
+                },
+                _ = flush_timer.tick() => false,
+            };
+
+            if !batch.is_empty() {
+                let Some(owned) = conn.take() else {
+                    return;
+                };
+                let trades = std::mem::take(&mut batch);
+                match tokio::task::spawn_blocking(move || {
+                    let result = insert_trades(&owned, &trades);
+                    (owned, result, trades.len())
+                })
+                .await
+                {
+                    Ok((owned, result, count)) => {
+                        match result {
+                            Ok(()) => debug!("Inserted {} trade(s) into SQLite.", count),
+                            Err(e) => error!("Failed to insert {} trade(s): {}", count, e),
+                        }
+                        conn = Some(owned);
+                    }
+                    Err(e) => {
+                        error!("SQLite writer thread panicked: {}", e);
+                        return;
+                    }
+                }
+            }
+
+            if closed {
+                info!("SQLite trade writer finished.");
+                return;
+            }
+        }
+    })
+}
+
+fn insert_trades(conn: &Connection, trades: &[TradeEvent]) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO trades (trade_time, symbol, price, quantity, is_maker) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for trade in trades {
+            insert.execute(params![
+                trade.trade_time as i64,
+                trade.symbol,
+                trade.price.to_string(),
+                trade.quantity.to_string(),
+                trade.is_buyer_market_maker,
+            ])?;
+        }
+    }
+    tx.commit()
+}
@@ -0,0 +1,106 @@
+//! Explicit connection lifecycle for the websocket clients, so every state
+//! change shows up as one log line and can be observed by health checks.
+
+use std::fmt;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Where a client is in its connect/subscribe/reconnect cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Opening the websocket (TCP, TLS and the HTTP upgrade).
+    Connecting,
+    /// Handshake done; nothing subscribed yet.
+    Connected,
+    /// Initial subscribe sent, waiting for the server to answer it.
+    Subscribing,
+    /// Subscribed and delivering market data.
+    Live,
+    /// Disconnected and waiting out the reconnect delay.
+    Reconnecting,
+    /// Shut down, or the reconnect policy gave up.
+    Closed,
+}
+
+/// Every state, in lifecycle order; `index` is the position in this list.
+pub const CONNECTION_STATES: [ConnectionState; 6] = [
+    ConnectionState::Connecting,
+    ConnectionState::Connected,
+    ConnectionState::Subscribing,
+    ConnectionState::Live,
+    ConnectionState::Reconnecting,
+    ConnectionState::Closed,
+];
+
+impl ConnectionState {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Subscribing => "subscribing",
+            ConnectionState::Live => "live",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Closed => "closed",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        CONNECTION_STATES
+            .iter()
+            .position(|state| *state == self)
+            .unwrap_or(0)
+    }
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Called with `(from, to)` on every transition.
+pub type StateCallback = Box<dyn FnMut(ConnectionState, ConnectionState) + Send>;
+
+/// Holds the current `ConnectionState`, logging each transition and passing it
+/// to the optional callback. Starts `Closed`, i.e. not yet connected.
+pub struct ConnectionStateMachine {
+    state: ConnectionState,
+    on_transition: Option<StateCallback>,
+}
+
+impl ConnectionStateMachine {
+    pub fn new() -> Self {
+        Self {
+            state: ConnectionState::Closed,
+            on_transition: None,
+        }
+    }
+
+    pub fn with_callback(mut self, callback: StateCallback) -> Self {
+        self.on_transition = Some(callback);
+        self
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    /// Moves to `next`; a no-op when already there.
+    pub fn transition(&mut self, next: ConnectionState) {
+        if next == self.state {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.state, next);
+        info!("Connection state: {} → {}", previous, next);
+        if let Some(callback) = &mut self.on_transition {
+            callback(previous, next);
+        }
+    }
+}
+
+impl Default for ConnectionStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
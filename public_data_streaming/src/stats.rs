@@ -0,0 +1,840 @@
+use crate::clock::{Clock, SystemClock};
+use crate::deflate::CompressionCounters;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Busiest streams listed in the periodic stats log line.
+pub const STATS_TOP_STREAMS: usize = 5;
+
+/// Most recent latency samples kept for min/avg/max/p99.
+pub const LATENCY_WINDOW: usize = 1000;
+
+// Stats windows shorter than this (e.g. the final one on exit) don't count toward
+// the peak rate, since a handful of messages in a few milliseconds reads as a spike.
+const MIN_PEAK_WINDOW: Duration = Duration::from_secs(1);
+
+/// How `WsSession` reports its periodic stats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatsMode {
+    /// One info log line per interval; friendly to log files.
+    #[default]
+    Log,
+    /// A single table row rewritten in place on stdout with a carriage return.
+    Table,
+}
+
+impl StatsMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "log" => Some(StatsMode::Log),
+            "table" => Some(StatsMode::Table),
+            _ => None,
+        }
+    }
+}
+
+/// Rolling receive latency (`local now - event time E`) over the last `LATENCY_WINDOW`
+/// events. Negative samples mean the local clock is behind Binance's; they are
+/// clamped to zero and counted separately.
+#[derive(Debug, Default)]
+pub struct LatencyStats {
+    samples_ms: VecDeque<u64>,
+    clamped: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySummary {
+    pub min_ms: u64,
+    pub avg_ms: f64,
+    pub max_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, event_time_ms: u64, now_ms: u64) {
+        let latency_ms = match now_ms.checked_sub(event_time_ms) {
+            Some(latency_ms) => latency_ms,
+            None => {
+                self.clamped += 1;
+                0
+            }
+        };
+        if self.samples_ms.len() == LATENCY_WINDOW {
+            self.samples_ms.pop_front();
+        }
+        self.samples_ms.push_back(latency_ms);
+    }
+
+    /// Samples clamped to zero because the event time was ahead of the local clock.
+    pub fn clamped(&self) -> u64 {
+        self.clamped
+    }
+
+    pub fn summary(&self) -> Option<LatencySummary> {
+        if self.samples_ms.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.samples_ms.iter().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        // Nearest-rank percentile.
+        let p99_index = (sorted.len() * 99).div_ceil(100).saturating_sub(1);
+        Some(LatencySummary {
+            min_ms: sorted[0],
+            avg_ms: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+            max_ms: sorted[sorted.len() - 1],
+            p99_ms: sorted[p99_index],
+        })
+    }
+}
+
+/// Last trade id (`t`) seen per symbol on this connection. Binance numbers each
+/// symbol's trades consecutively, so any other step means trades were dropped or
+/// delivered out of order.
+#[derive(Debug, Default)]
+pub struct TradeIdGaps {
+    last_ids: HashMap<String, u64>,
+    checked: u64,
+    gaps: u64,
+}
+
+impl TradeIdGaps {
+    /// Warns and counts a gap unless `trade_id` directly follows the symbol's last
+    /// one. A late trade doesn't move the last id back, so the trades after it
+    /// aren't flagged as well.
+    pub fn record(&mut self, symbol: &str, trade_id: u64) {
+        self.checked += 1;
+        let Some(last_id) = self.last_ids.get_mut(symbol) else {
+            self.last_ids.insert(symbol.to_string(), trade_id);
+            return;
+        };
+        if trade_id == *last_id + 1 {
+            *last_id = trade_id;
+            return;
+        }
+
+        self.gaps += 1;
+        if trade_id > *last_id {
+            warn!(
+                "Trade id gap for {}: {} -> {} ({} trade(s) missing)",
+                symbol,
+                last_id,
+                trade_id,
+                trade_id - *last_id - 1
+            );
+            *last_id = trade_id;
+        } else {
+            warn!(
+                "Trade id out of order for {}: {} after {}",
+                symbol, trade_id, last_id
+            );
+        }
+    }
+
+    /// Trades checked, including each symbol's first.
+    pub fn checked(&self) -> u64 {
+        self.checked
+    }
+
+    pub fn gaps(&self) -> u64 {
+        self.gaps
+    }
+}
+
+/// Counters behind the periodic stats output, shared by both `StatsMode`s.
+pub struct SessionStats {
+    clock: Arc<dyn Clock>,
+    label: String,
+    start_time: Instant,
+    message_count: usize,
+    /// Binary frames received, whether or not they decoded to text.
+    binary_frames: usize,
+    last_message_time: Instant,
+    /// Last frame of any kind, pings and pongs included.
+    last_frame_time: Instant,
+    /// No streams are subscribed, so silence is expected and liveness is judged
+    /// on frames of any kind instead of messages.
+    idle: bool,
+    /// When the session last left `idle`; the message watchdog runs from here.
+    active_since: Instant,
+    last_gap: Duration,
+    /// Start and message count of the current stats window, for `peak_rate`.
+    window_start: Instant,
+    window_message_count: usize,
+    /// Highest msg/s over any completed stats window.
+    peak_rate: f64,
+    reconnects: u64,
+    stale_timeout: Option<Duration>,
+    dropped_events: Option<Arc<AtomicU64>>,
+    dropped_frames: Option<Arc<AtomicU64>>,
+    /// Set when the connection negotiated `permessage-deflate`.
+    compression: Option<Arc<CompressionCounters>>,
+    stream_counts: HashMap<String, usize>,
+    latency: LatencyStats,
+    trade_ids: TradeIdGaps,
+    header_printed: bool,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Reads the time from `clock` instead of `Instant::now()`.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        Self {
+            clock,
+            label: String::new(),
+            start_time: now,
+            message_count: 0,
+            binary_frames: 0,
+            last_message_time: now,
+            last_frame_time: now,
+            idle: false,
+            active_since: now,
+            last_gap: Duration::ZERO,
+            window_start: now,
+            window_message_count: 0,
+            peak_rate: 0.0,
+            reconnects: 0,
+            stale_timeout: None,
+            dropped_events: None,
+            dropped_frames: None,
+            compression: None,
+            stream_counts: HashMap::new(),
+            latency: LatencyStats::default(),
+            trade_ids: TradeIdGaps::default(),
+            header_printed: false,
+        }
+    }
+
+    /// Switches to `clock`, restarting the session's timers from its current time.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        self.start_time = now;
+        self.last_message_time = now;
+        self.last_frame_time = now;
+        self.active_since = now;
+        self.window_start = now;
+        self.clock = clock;
+    }
+
+    pub fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+
+    pub fn set_reconnects(&mut self, reconnects: u64) {
+        self.reconnects = reconnects;
+    }
+
+    /// Watchdog window used to judge health; `None` when the watchdog is off.
+    pub fn set_stale_timeout(&mut self, stale_timeout: Option<Duration>) {
+        self.stale_timeout = stale_timeout;
+    }
+
+    pub fn set_dropped_events(&mut self, dropped_events: Option<Arc<AtomicU64>>) {
+        self.dropped_events = dropped_events;
+    }
+
+    pub fn set_dropped_frames(&mut self, dropped_frames: Option<Arc<AtomicU64>>) {
+        self.dropped_frames = dropped_frames;
+    }
+
+    pub fn set_compression(&mut self, compression: Option<Arc<CompressionCounters>>) {
+        self.compression = compression;
+    }
+
+    /// Inflated over compressed bytes so far; `None` without `permessage-deflate`
+    /// or before the first compressed message.
+    pub fn compression_ratio(&self) -> Option<f64> {
+        self.compression
+            .as_ref()
+            .and_then(|counters| counters.ratio())
+    }
+
+    /// Marks the session idle (nothing subscribed) or active. Leaving idle restarts
+    /// the message watchdog, so a fresh subscription gets the full window.
+    pub fn set_idle(&mut self, idle: bool) {
+        if self.idle && !idle {
+            self.active_since = self.clock.now();
+        }
+        self.idle = idle;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Notes that a frame of any kind arrived, for the idle liveness check.
+    pub fn record_frame(&mut self) {
+        self.last_frame_time = self.clock.now();
+    }
+
+    /// Counts a message and returns the gap since the previous one.
+    pub fn record_message(&mut self) -> Duration {
+        let now = self.clock.now();
+        self.last_gap = now.duration_since(self.last_message_time);
+        self.last_message_time = now;
+        self.last_frame_time = now;
+        self.message_count += 1;
+        self.window_message_count += 1;
+        self.last_gap
+    }
+
+    pub fn record_binary_frame(&mut self) {
+        self.binary_frames += 1;
+    }
+
+    /// Records receive latency for an event stamped `event_time_ms` by the server.
+    pub fn record_latency(&mut self, event_time_ms: u64) {
+        self.latency.record(event_time_ms, self.clock.now_unix_ms());
+    }
+
+    pub fn latency(&self) -> &LatencyStats {
+        &self.latency
+    }
+
+    /// Checks a `@trade` event's id against the symbol's previous one.
+    pub fn record_trade_id(&mut self, symbol: &str, trade_id: u64) {
+        self.trade_ids.record(symbol, trade_id);
+    }
+
+    pub fn trade_ids(&self) -> &TradeIdGaps {
+        &self.trade_ids
+    }
+
+    pub fn record_stream(&mut self, stream: String) {
+        *self.stream_counts.entry(stream).or_default() += 1;
+    }
+
+    /// The `n` streams with the most messages, busiest first (ties by name).
+    pub fn top_streams(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut streams = self
+            .stream_counts
+            .iter()
+            .map(|(stream, count)| (stream.as_str(), *count))
+            .collect::<Vec<_>>();
+        streams.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        streams.truncate(n);
+        streams
+    }
+
+    /// Time since the last message (or since the session started).
+    pub fn silent_for(&self) -> Duration {
+        self.since(self.last_message_time)
+    }
+
+    /// Time since any frame at all, pings and pongs included.
+    pub fn frame_silence(&self) -> Duration {
+        self.since(self.last_frame_time)
+    }
+
+    /// The silence the stale watchdog judges: since the last frame of any kind
+    /// while idle, otherwise since the last message or leaving idle.
+    pub fn watchdog_silence(&self) -> Duration {
+        if self.idle {
+            self.frame_silence()
+        } else {
+            self.since(self.last_message_time.max(self.active_since))
+        }
+    }
+
+    /// `healthy` while the watchdog's silence is within its window, `stale`
+    /// past it, and `unmonitored` when the watchdog is disabled.
+    pub fn health(&self) -> &'static str {
+        match self.stale_timeout {
+            Some(timeout) if self.watchdog_silence() > timeout => "stale",
+            Some(_) => "healthy",
+            None => "unmonitored",
+        }
+    }
+
+    pub fn message_count(&self) -> usize {
+        self.message_count
+    }
+
+    pub fn messages_per_second(&self) -> f64 {
+        rate(
+            self.message_count,
+            self.since(self.start_time).as_secs_f64(),
+        )
+    }
+
+    pub fn peak_rate(&self) -> f64 {
+        self.peak_rate
+    }
+
+    pub fn report(&mut self, mode: StatsMode) {
+        self.close_window();
+        match mode {
+            StatsMode::Log => self.log_line(),
+            StatsMode::Table => self.table_row(),
+        }
+    }
+
+    fn since(&self, earlier: Instant) -> Duration {
+        self.clock.now().saturating_duration_since(earlier)
+    }
+
+    /// Folds the window since the last report into `peak_rate` and starts a new one.
+    fn close_window(&mut self) {
+        let elapsed = self.since(self.window_start);
+        if elapsed >= MIN_PEAK_WINDOW {
+            let rate = self.window_message_count as f64 / elapsed.as_secs_f64();
+            self.peak_rate = self.peak_rate.max(rate);
+        }
+        self.window_start = self.clock.now();
+        self.window_message_count = 0;
+    }
+
+    fn log_line(&self) {
+        let mut line = format!(
+            "Messages received: {}, Frequency: {:.2} msg/s",
+            self.message_count,
+            self.messages_per_second()
+        );
+        if self.binary_frames > 0 {
+            line.push_str(&format!(", Binary frames: {}", self.binary_frames));
+        }
+        if self.trade_ids.checked() > 0 {
+            line.push_str(&format!(", Trade id gaps: {}", self.trade_ids.gaps()));
+        }
+        if let Some(dropped) = &self.dropped_frames {
+            line.push_str(&format!(
+                ", Dropped frames: {}",
+                dropped.load(Ordering::Relaxed)
+            ));
+        }
+        if let Some(dropped) = &self.dropped_events {
+            line.push_str(&format!(
+                ", Dropped events: {}",
+                dropped.load(Ordering::Relaxed)
+            ));
+        }
+        if let Some(counters) = &self.compression {
+            line.push_str(&format!(
+                ", Compression: {}",
+                compression_summary(counters.compressed_bytes(), counters.inflated_bytes())
+            ));
+        }
+        info!("{}", line);
+
+        let watchdog = self
+            .stale_timeout
+            .map(|timeout| {
+                if self.idle {
+                    format!("{}s, idle: checked by ping", timeout.as_secs())
+                } else {
+                    format!("{}s", timeout.as_secs())
+                }
+            })
+            .unwrap_or_else(|| "off".to_string());
+        info!(
+            "Connection {}: last message {:.1}s ago (watchdog {}), reconnects: {}",
+            self.health(),
+            self.silent_for().as_secs_f64(),
+            watchdog,
+            self.reconnects
+        );
+
+        if let Some(latency) = self.latency.summary() {
+            info!(
+                "Latency (last {} events): min {} ms, avg {:.1} ms, max {} ms, p99 {} ms, clamped: {}",
+                self.latency.samples_ms.len(),
+                latency.min_ms,
+                latency.avg_ms,
+                latency.max_ms,
+                latency.p99_ms,
+                self.latency.clamped
+            );
+        }
+
+        let top = self.top_streams(STATS_TOP_STREAMS);
+        if !top.is_empty() {
+            let elapsed = self.since(self.start_time).as_secs_f64();
+            let summary = top
+                .iter()
+                .map(|(stream, count)| {
+                    format!("{} {} ({:.2}/s)", stream, count, rate(*count, elapsed))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            info!(
+                "Top streams ({} of {}): {}",
+                top.len(),
+                self.stream_counts.len(),
+                summary
+            );
+        }
+    }
+
+    fn table_row(&mut self) {
+        let mut stdout = io::stdout().lock();
+        if !self.header_printed {
+            let _ = writeln!(
+                stdout,
+                "{:<32} {:>10} {:>9} {:>10} {:>10} {:>11} {:>10} {:>8}",
+                "symbol",
+                "messages",
+                "msg/s",
+                "last gap",
+                "p99 lat",
+                "health",
+                "reconnects",
+                "dropped"
+            );
+            self.header_printed = true;
+        }
+
+        // Frames shed by the processing queue plus events shed by the sink.
+        let dropped = [&self.dropped_frames, &self.dropped_events]
+            .into_iter()
+            .flatten()
+            .map(|dropped| dropped.load(Ordering::Relaxed))
+            .reduce(|a, b| a + b)
+            .map(|dropped| dropped.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let p99 = self
+            .latency
+            .summary()
+            .map(|latency| format!("{}ms", latency.p99_ms))
+            .unwrap_or_else(|| "-".to_string());
+        let _ = write!(
+            stdout,
+            "\r{:<32} {:>10} {:>9.2} {:>9.0}ms {:>10} {:>11} {:>10} {:>8}",
+            truncate(&self.label, 32),
+            self.message_count,
+            self.messages_per_second(),
+            self.last_gap.as_secs_f64() * 1000.0,
+            p99,
+            self.health(),
+            self.reconnects,
+            dropped
+        );
+        let _ = stdout.flush();
+    }
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Totals for a whole run across reconnects, logged once when a bin exits.
+/// Each connection's `SessionStats` is folded in when that connection ends.
+#[derive(Debug)]
+pub struct RunSummary {
+    clock: Arc<dyn Clock>,
+    start_time: Instant,
+    message_count: usize,
+    binary_frames: usize,
+    trades_checked: u64,
+    trade_id_gaps: u64,
+    peak_rate: f64,
+    dropped_events: Option<Arc<AtomicU64>>,
+    dropped_frames: Option<Arc<AtomicU64>>,
+    /// `permessage-deflate` payload bytes read and inflated, over every session.
+    compressed_bytes: u64,
+    inflated_bytes: u64,
+}
+
+impl RunSummary {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            start_time: clock.now(),
+            clock,
+            message_count: 0,
+            binary_frames: 0,
+            trades_checked: 0,
+            trade_id_gaps: 0,
+            peak_rate: 0.0,
+            dropped_events: None,
+            dropped_frames: None,
+            compressed_bytes: 0,
+            inflated_bytes: 0,
+        }
+    }
+
+    pub fn add_session(&mut self, stats: &SessionStats) {
+        self.message_count += stats.message_count;
+        self.binary_frames += stats.binary_frames;
+        self.trades_checked += stats.trade_ids.checked();
+        self.trade_id_gaps += stats.trade_ids.gaps();
+        self.peak_rate = self.peak_rate.max(stats.peak_rate);
+        // The drop counters are shared by every session of a run.
+        if stats.dropped_events.is_some() {
+            self.dropped_events = stats.dropped_events.clone();
+        }
+        if stats.dropped_frames.is_some() {
+            self.dropped_frames = stats.dropped_frames.clone();
+        }
+        if let Some(counters) = &stats.compression {
+            self.compressed_bytes += counters.compressed_bytes();
+            self.inflated_bytes += counters.inflated_bytes();
+        }
+    }
+
+    pub fn log(&self, reconnects: u64) {
+        info!("{}", self.line(reconnects));
+    }
+
+    fn line(&self, reconnects: u64) -> String {
+        let runtime = self
+            .clock
+            .now()
+            .saturating_duration_since(self.start_time)
+            .as_secs_f64();
+        let mut line = format!(
+            "Run summary: {} message(s) in {:.1}s, avg {:.2} msg/s, peak {:.2} msg/s, reconnects: {}",
+            self.message_count,
+            runtime,
+            rate(self.message_count, runtime),
+            self.peak_rate,
+            reconnects
+        );
+        if self.binary_frames > 0 {
+            line.push_str(&format!(", binary frames: {}", self.binary_frames));
+        }
+        if self.trades_checked > 0 {
+            line.push_str(&format!(", trade id gaps: {}", self.trade_id_gaps));
+        }
+        if let Some(dropped) = &self.dropped_frames {
+            line.push_str(&format!(
+                ", dropped frames: {}",
+                dropped.load(Ordering::Relaxed)
+            ));
+        }
+        if let Some(dropped) = &self.dropped_events {
+            line.push_str(&format!(
+                ", dropped events: {}",
+                dropped.load(Ordering::Relaxed)
+            ));
+        }
+        if self.compressed_bytes > 0 {
+            line.push_str(&format!(
+                ", compression: {}",
+                compression_summary(self.compressed_bytes, self.inflated_bytes)
+            ));
+        }
+        line
+    }
+}
+
+/// e.g. `4.21x (120.5 KiB read, 507.3 KiB inflated)`.
+fn compression_summary(compressed: u64, inflated: u64) -> String {
+    if compressed == 0 {
+        return "no compressed messages yet".to_string();
+    }
+    format!(
+        "{:.2}x ({:.1} KiB read, {:.1} KiB inflated)",
+        inflated as f64 / compressed as f64,
+        compressed as f64 / 1024.0,
+        inflated as f64 / 1024.0
+    )
+}
+
+impl Default for RunSummary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `count` over `secs`, or `0` when no time has passed (e.g. an immediate exit).
+fn rate(count: usize, secs: f64) -> f64 {
+    if secs > 0.0 {
+        count as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    if value.chars().count() <= max_chars {
+        return value.to_string();
+    }
+    let mut truncated = value.chars().take(max_chars - 1).collect::<String>();
+    truncated.push('~');
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn stats_with_clock() -> (SessionStats, ManualClock) {
+        let clock = ManualClock::new();
+        let mut stats = SessionStats::with_clock(Arc::new(clock.clone()));
+        stats.set_stale_timeout(Some(Duration::from_secs(30)));
+        (stats, clock)
+    }
+
+    #[test]
+    fn idle_session_is_judged_on_any_frame() {
+        let (mut stats, clock) = stats_with_clock();
+        stats.set_idle(true);
+        clock.advance(Duration::from_secs(25));
+        stats.record_frame();
+        clock.advance(Duration::from_secs(25));
+        // 50s without a message, but a pong arrived 25s ago.
+        assert_eq!(stats.silent_for(), Duration::from_secs(50));
+        assert_eq!(stats.health(), "healthy");
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(stats.health(), "stale");
+    }
+
+    #[test]
+    fn leaving_idle_restarts_the_message_watchdog() {
+        let (mut stats, clock) = stats_with_clock();
+        stats.set_idle(true);
+        clock.advance(Duration::from_secs(120));
+        stats.record_frame();
+        stats.set_idle(false);
+        assert_eq!(stats.watchdog_silence(), Duration::ZERO);
+        clock.advance(Duration::from_secs(31));
+        // Pings and pongs don't count once streams are subscribed.
+        stats.record_frame();
+        assert_eq!(stats.health(), "stale");
+    }
+
+    #[test]
+    fn report_rolls_each_window_into_the_peak_rate() {
+        let (mut stats, clock) = stats_with_clock();
+        for _ in 0..10 {
+            clock.advance(Duration::from_millis(200));
+            stats.record_message();
+        }
+        stats.report(StatsMode::Log);
+        assert_eq!(stats.peak_rate(), 5.0);
+
+        // A slower window doesn't lower the peak.
+        clock.advance(Duration::from_secs(4));
+        stats.record_message();
+        stats.report(StatsMode::Log);
+        assert_eq!(stats.peak_rate(), 5.0);
+
+        for _ in 0..30 {
+            clock.advance(Duration::from_millis(100));
+            stats.record_message();
+        }
+        stats.report(StatsMode::Log);
+        assert_eq!(stats.peak_rate(), 10.0);
+        assert_eq!(stats.message_count(), 41);
+        assert_eq!(stats.messages_per_second(), 41.0 / 9.0);
+    }
+
+    #[test]
+    fn windows_shorter_than_a_second_leave_the_peak_alone() {
+        let (mut stats, clock) = stats_with_clock();
+        for _ in 0..50 {
+            clock.advance(Duration::from_millis(10));
+            stats.record_message();
+        }
+        stats.report(StatsMode::Log);
+        assert_eq!(stats.peak_rate(), 0.0);
+
+        // The short window's messages aren't carried into the next one.
+        clock.advance(Duration::from_secs(2));
+        stats.record_message();
+        stats.report(StatsMode::Log);
+        assert_eq!(stats.peak_rate(), 0.5);
+    }
+
+    #[test]
+    fn run_summary_keeps_the_best_session_peak() {
+        let clock = ManualClock::new();
+        let mut summary = RunSummary::with_clock(Arc::new(clock.clone()));
+        for rate in [4, 8, 2] {
+            let mut stats = SessionStats::with_clock(Arc::new(clock.clone()));
+            for _ in 0..rate {
+                clock.advance(Duration::from_millis(1000 / rate));
+                stats.record_message();
+            }
+            stats.report(StatsMode::Log);
+            summary.add_session(&stats);
+        }
+        assert_eq!(summary.peak_rate, 8.0);
+        assert_eq!(summary.message_count, 14);
+    }
+
+    #[test]
+    fn latency_is_measured_against_the_clock() {
+        let clock = ManualClock::starting_at_unix_ms(10_000);
+        let mut stats = SessionStats::with_clock(Arc::new(clock.clone()));
+        stats.record_latency(9_750);
+        clock.advance(Duration::from_millis(500));
+        stats.record_latency(10_300);
+        // An event stamped after the local clock counts as zero.
+        stats.record_latency(10_600);
+
+        let summary = stats.latency().summary().unwrap();
+        assert_eq!((summary.min_ms, summary.max_ms), (0, 250));
+        assert_eq!(stats.latency().clamped(), 1);
+    }
+
+    #[test]
+    fn rates_are_zero_until_time_passes() {
+        let clock = ManualClock::new();
+        let mut stats = SessionStats::with_clock(Arc::new(clock.clone()));
+        let mut summary = RunSummary::with_clock(Arc::new(clock.clone()));
+        stats.record_message();
+        assert_eq!(stats.messages_per_second(), 0.0);
+        stats.report(StatsMode::Log);
+        summary.add_session(&stats);
+        assert!(summary
+            .line(0)
+            .starts_with("Run summary: 1 message(s) in 0.0s, avg 0.00 msg/s"));
+    }
+
+    #[test]
+    fn run_summary_reports_drops_only_for_attached_counters() {
+        let clock = ManualClock::new();
+        let mut summary = RunSummary::with_clock(Arc::new(clock.clone()));
+        summary.add_session(&SessionStats::with_clock(Arc::new(clock.clone())));
+        assert!(!summary.line(0).contains("dropped"));
+
+        let mut stats = SessionStats::with_clock(Arc::new(clock.clone()));
+        stats.set_dropped_frames(Some(Arc::new(AtomicU64::new(3))));
+        summary.add_session(&stats);
+        assert!(summary.line(0).ends_with(", dropped frames: 3"));
+    }
+
+    #[test]
+    fn compression_summary_reports_the_ratio_in_kib() {
+        assert_eq!(compression_summary(0, 0), "no compressed messages yet");
+        assert_eq!(
+            compression_summary(2048, 8704),
+            "4.25x (2.0 KiB read, 8.5 KiB inflated)"
+        );
+    }
+
+    #[test]
+    fn messages_keep_the_watchdog_happy_until_the_timeout_passes() {
+        let (mut stats, clock) = stats_with_clock();
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(stats.record_message(), Duration::from_secs(20));
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(stats.watchdog_silence(), Duration::from_secs(30));
+        assert_eq!(stats.health(), "healthy");
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(stats.health(), "stale");
+
+        assert_eq!(stats.record_message(), Duration::from_millis(30_001));
+        assert_eq!(stats.health(), "healthy");
+        stats.set_stale_timeout(None);
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(stats.health(), "unmonitored");
+    }
+}
@@ -0,0 +1,216 @@
+//! Stream name vocabulary shared by the bins: which `<symbol>@<kind>` names exist
+//! and how to compose them from separate arguments.
+
+use crate::endpoints::Market;
+
+// Stream kinds that take no parameters; klines additionally take `kline_<interval>`.
+pub const EVENT_KINDS: &[&str] = &[
+    "trade",
+    "aggTrade",
+    "bookTicker",
+    "ticker",
+    "miniTicker",
+    "depth",
+    "depth5",
+    "depth10",
+    "depth20",
+    "markPrice",
+    "forceOrder",
+];
+pub const KLINE_INTERVALS: &[&str] = &[
+    "1s", "1m", "3m", "5m", "15m", "30m", "1h", "2h", "4h", "6h", "8h", "12h", "1d", "3d", "1w",
+    "1M",
+];
+// Subset of EVENT_KINDS that only exists on the futures stream hosts.
+pub const FUTURES_ONLY_EVENT_KINDS: &[&str] = &["markPrice", "forceOrder"];
+// Top-N levels of the partial book depth streams (`depth5`, `depth10`, `depth20`).
+pub const DEPTH_LEVELS: &[&str] = &["5", "10", "20"];
+// Optional update speed suffix for the depth kinds, e.g. `depth20@100ms`.
+pub const DEPTH_UPDATE_SPEEDS: &[&str] = &["100ms", "1000ms"];
+// Cadences of the `@depth` diff stream per market; the first is what plain `@depth` delivers.
+pub const SPOT_DEPTH_DIFF_SPEEDS: &[&str] = &["1000ms", "100ms"];
+pub const FUTURES_DEPTH_DIFF_SPEEDS: &[&str] = &["250ms", "500ms", "100ms"];
+
+/// Update speeds `market` offers for the `@depth` diff stream, default first.
+pub fn depth_diff_speeds(market: Market) -> &'static [&'static str] {
+    if market.is_futures() {
+        FUTURES_DEPTH_DIFF_SPEEDS
+    } else {
+        SPOT_DEPTH_DIFF_SPEEDS
+    }
+}
+
+/// `<symbol>@depth` diff stream name at `speed` (the market's default when
+/// `None`), with the speed returned alongside for logging. The default cadence
+/// is the bare `@depth` name; others get an `@<speed>` suffix.
+pub fn depth_diff_stream(
+    symbol: &str,
+    market: Market,
+    speed: Option<&str>,
+) -> Result<(String, &'static str), String> {
+    let speeds = depth_diff_speeds(market);
+    let speed = match speed {
+        None => speeds[0],
+        Some(speed) => speeds
+            .iter()
+            .copied()
+            .find(|allowed| *allowed == speed)
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported depth update speed {} on {}; expected one of: {}",
+                    speed,
+                    market.name(),
+                    speeds.join(", ")
+                )
+            })?,
+    };
+    let stream = if speed == speeds[0] {
+        format!("{}@depth", symbol)
+    } else {
+        format!("{}@depth@{}", symbol, speed)
+    };
+    Ok((stream, speed))
+}
+
+/// Trims and lowercases a symbol such as ` BTCUSDT `, rejecting empty input and
+/// anything but ASCII letters and digits, which would only subscribe to a dead stream.
+pub fn normalize_symbol(symbol: &str) -> Result<String, String> {
+    let trimmed = symbol.trim();
+    if trimmed.is_empty() {
+        return Err("Symbol is empty".to_string());
+    }
+    if !trimmed.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "Invalid symbol {:?}: only letters and digits are allowed, e.g. btcusdt",
+            symbol
+        ));
+    }
+    Ok(trimmed.to_lowercase())
+}
+
+/// Trims and lowercases a full stream name, validating its symbol with
+/// `normalize_symbol`. All-market names such as `!ticker@arr` have no symbol.
+pub fn normalize_stream(stream: &str) -> Result<String, String> {
+    let stream = stream.trim();
+    let Some((symbol, kind)) = stream.split_once('@') else {
+        return Err(format!(
+            "Invalid stream {:?}: expected <symbol>@<kind>, e.g. btcusdt@trade",
+            stream
+        ));
+    };
+    if kind.is_empty() {
+        return Err(format!(
+            "Invalid stream {:?}: missing the kind after @",
+            stream
+        ));
+    }
+    if symbol.starts_with('!') {
+        return Ok(stream.to_lowercase());
+    }
+    let symbol =
+        normalize_symbol(symbol).map_err(|e| format!("Invalid stream {}: {}", stream, e))?;
+    Ok(format!("{}@{}", symbol, kind.to_lowercase()))
+}
+
+/// Validates an event kind such as `aggTrade`, `kline_1m` or `depth20@100ms`,
+/// returning it with the canonical casing.
+pub fn parse_event_kind(value: &str) -> Result<String, String> {
+    if let Some(kind) = EVENT_KINDS
+        .iter()
+        .find(|kind| kind.eq_ignore_ascii_case(value))
+    {
+        return Ok(kind.to_string());
+    }
+
+    if let Some(interval) = value.strip_prefix("kline_") {
+        // Intervals are case-sensitive: `1m` is a minute, `1M` a month.
+        if KLINE_INTERVALS.contains(&interval) {
+            return Ok(value.to_string());
+        }
+        return Err(format!(
+            "Invalid kline interval in {}; expected one of: {}",
+            value,
+            KLINE_INTERVALS.join(", ")
+        ));
+    }
+
+    if let Some((depth, speed)) = value.split_once('@') {
+        if depth.starts_with("depth") && EVENT_KINDS.contains(&depth) {
+            if DEPTH_UPDATE_SPEEDS.contains(&speed) {
+                return Ok(value.to_string());
+            }
+            return Err(format!(
+                "Invalid depth update speed in {}; expected one of: {}",
+                value,
+                DEPTH_UPDATE_SPEEDS.join(", ")
+            ));
+        }
+    }
+
+    Err(format!(
+        "Unknown event kind: {} (supported: {}, kline_<interval>)",
+        value,
+        EVENT_KINDS.join(", ")
+    ))
+}
+
+/// Composes a stream name from separate words, e.g. `btcusdt kline 1m` ->
+/// `btcusdt@kline_1m` and `btcusdt depth 20 100ms` -> `btcusdt@depth20@100ms`.
+pub fn compose_stream(symbol: &str, kind: &str, args: &[&str]) -> Result<String, String> {
+    let symbol = normalize_symbol(symbol)?;
+    let kind = match (kind.to_ascii_lowercase().as_str(), args) {
+        ("kline", [interval]) => format!("kline_{}", interval),
+        ("kline", _) => {
+            return Err(format!(
+                "kline takes exactly one interval, e.g. `{} kline 1m`; intervals: {}",
+                symbol,
+                KLINE_INTERVALS.join(", ")
+            ));
+        }
+        ("depth", args) => compose_depth_kind(args)?,
+        (_, []) => kind.to_string(),
+        (_, extra) => {
+            return Err(format!(
+                "{} takes no arguments (got: {}); only kline and depth are parameterized",
+                kind,
+                extra.join(" ")
+            ));
+        }
+    };
+    Ok(format!("{}@{}", symbol, parse_event_kind(&kind)?))
+}
+
+/// `[levels] [speed]` -> `depth`, `depth20`, `depth@100ms` or `depth20@100ms`.
+fn compose_depth_kind(args: &[&str]) -> Result<String, String> {
+    let (levels, speed) = match args {
+        [] => (None, None),
+        [speed] if speed.ends_with("ms") => (None, Some(*speed)),
+        [levels] => (Some(*levels), None),
+        [levels, speed] => (Some(*levels), Some(*speed)),
+        _ => return Err("depth takes at most [levels] [speed], e.g. `depth 20 100ms`".to_string()),
+    };
+
+    let mut kind = "depth".to_string();
+    if let Some(levels) = levels {
+        if !DEPTH_LEVELS.contains(&levels) {
+            return Err(format!(
+                "Invalid depth levels {}; expected one of: {}",
+                levels,
+                DEPTH_LEVELS.join(", ")
+            ));
+        }
+        kind.push_str(levels);
+    }
+    if let Some(speed) = speed {
+        if !DEPTH_UPDATE_SPEEDS.contains(&speed) {
+            return Err(format!(
+                "Invalid depth update speed {}; expected one of: {}",
+                speed,
+                DEPTH_UPDATE_SPEEDS.join(", ")
+            ));
+        }
+        kind.push('@');
+        kind.push_str(speed);
+    }
+    Ok(kind)
+}
@@ -0,0 +1,96 @@
+//! `--validate`: reports which `BinanceMessage`/`BinanceEvent` variant every
+//! frame matched, and for frames that fell through to `Other` (or failed to
+//! parse at all) the deserialization error of the variant they were meant for.
+
+use crate::models::{BinanceEvent, BinanceMessage, BookTickerEvent, PartialDepthEvent};
+use serde_json::Value;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Validates one raw frame, unwrapping a combined-stream envelope first.
+pub fn log_frame(frame: &str) {
+    let value = match serde_json::from_str::<Value>(frame) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Validate: frame is not JSON ({}): {}", e, frame);
+            return;
+        }
+    };
+
+    match (
+        value.get("stream").and_then(Value::as_str),
+        value.get("data"),
+    ) {
+        (Some(stream), Some(data)) => log_payload(Some(stream), data),
+        _ => log_payload(None, &value),
+    }
+}
+
+/// Validates an already unwrapped payload; `stream` only labels the log line.
+pub fn log_payload(stream: Option<&str>, payload: &Value) {
+    let label = stream
+        .map(|stream| format!(" {}", stream))
+        .unwrap_or_default();
+    match serde_json::from_value::<BinanceMessage>(payload.clone()) {
+        Ok(BinanceMessage::Other(value)) => {
+            warn!(
+                "Validate{}: unmatched → Other ({}): {}",
+                label,
+                diagnose(&value),
+                value
+            );
+        }
+        Ok(message) => info!("Validate{}: matched {}", label, message_name(&message)),
+        Err(e) => warn!("Validate{}: failed to parse ({}): {}", label, e, payload),
+    }
+}
+
+fn message_name(message: &BinanceMessage) -> String {
+    match message {
+        BinanceMessage::Event(event) => format!("Event({})", event.name()),
+        BinanceMessage::ArrayEvent(events) => format!(
+            "ArrayEvent({} x {})",
+            events.len(),
+            events.first().map_or("empty", BinanceEvent::name)
+        ),
+        BinanceMessage::BookTicker(_) => "BookTicker".to_string(),
+        BinanceMessage::PartialDepth(_) => "PartialDepth".to_string(),
+        BinanceMessage::ErrorResponse { .. } => "ErrorResponse".to_string(),
+        BinanceMessage::SubscriptionResponse { .. } => "SubscriptionResponse".to_string(),
+        BinanceMessage::Other(_) => "Other".to_string(),
+    }
+}
+
+/// Why `value` matched none of the typed variants. Tagged objects are checked
+/// against `BinanceEvent` (naming an unknown `e` or the failing field); untagged
+/// objects against each shape-matched variant.
+fn diagnose(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            if let Some(tag) = map.get("e") {
+                return match serde_json::from_value::<BinanceEvent>(value.clone()) {
+                    Ok(event) => format!("parses as {} on its own", event.name()),
+                    Err(e) => format!("event {}: {}", tag, e),
+                };
+            }
+            let book_ticker = serde_json::from_value::<BookTickerEvent>(value.clone()).err();
+            let partial_depth = serde_json::from_value::<PartialDepthEvent>(value.clone()).err();
+            format!(
+                "no \"e\" tag; as BookTicker: {}; as PartialDepth: {}",
+                book_ticker.map_or("ok".to_string(), |e| e.to_string()),
+                partial_depth.map_or("ok".to_string(), |e| e.to_string())
+            )
+        }
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .find_map(|(index, item)| {
+                serde_json::from_value::<BinanceEvent>(item.clone())
+                    .err()
+                    .map(|e| format!("array element {}: {}", index, e))
+            })
+            .unwrap_or_else(|| "array of events".to_string()),
+        _ => "not an object or array".to_string(),
+    }
+}
@@ -0,0 +1,90 @@
+//! Session-long traded volume and volume-weighted average price per symbol,
+//! accumulated from the trade stream in exact decimals.
+
+use crate::models::TradeEvent;
+use crate::session::STATS_INTERVAL_SECS;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, warn};
+
+/// Running totals for one symbol.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolVolume {
+    /// Base asset quantity traded.
+    pub volume: Decimal,
+    /// Sum of price × quantity, i.e. quote asset traded.
+    pub notional: Decimal,
+    pub trades: u64,
+}
+
+impl SymbolVolume {
+    /// `None` until some quantity has traded.
+    pub fn vwap(&self) -> Option<Decimal> {
+        (!self.volume.is_zero()).then(|| self.notional / self.volume)
+    }
+}
+
+/// Accumulates `SymbolVolume` per symbol and logs the totals once per stats interval.
+#[derive(Debug)]
+pub struct VolumeTracker {
+    symbols: BTreeMap<String, SymbolVolume>,
+    report_interval: Duration,
+    last_report: Instant,
+}
+
+impl VolumeTracker {
+    pub fn new() -> Self {
+        Self {
+            symbols: BTreeMap::new(),
+            report_interval: Duration::from_secs(STATS_INTERVAL_SECS),
+            last_report: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, trade: &TradeEvent) {
+        let entry = self.symbols.entry(trade.symbol.clone()).or_default();
+        entry.volume += trade.quantity;
+        entry.notional += trade.price * trade.quantity;
+        entry.trades += 1;
+    }
+
+    /// Logs the totals if a stats interval has passed since the last report.
+    /// Called from the trade path, so a silent stream simply skips reports.
+    pub fn log_if_due(&mut self) {
+        if self.last_report.elapsed() >= self.report_interval {
+            self.log();
+        }
+    }
+
+    pub fn log(&mut self) {
+        self.last_report = Instant::now();
+        for (symbol, totals) in &self.symbols {
+            info!(
+                "VWAP - Symbol: {}, VWAP: {}, Volume: {}, Notional: {}, Trades: {}",
+                symbol,
+                totals.vwap().map_or("n/a".to_string(), |vwap| vwap
+                    .round_dp(8)
+                    .normalize()
+                    .to_string()),
+                totals.volume,
+                totals.notional,
+                totals.trades
+            );
+        }
+    }
+
+    /// Forgets every symbol's totals and starts a new accumulation window.
+    pub fn reset(&mut self) {
+        self.symbols.clear();
+        self.last_report = Instant::now();
+    }
+}
+
+impl Default for VolumeTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,181 @@
+//! Connects `WsSession` with compression offered (`--compress`) to local servers
+//! that accept, ignore or refuse `permessage-deflate`.
+
+use futures::{SinkExt, StreamExt};
+use public_data_streaming::deflate;
+use public_data_streaming::session::WsSession;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str =
+    r#"{"e":"trade","E":1,"s":"ETHUSDT","t":42,"p":"3000.50","q":"0.25","T":1,"m":false}"#;
+/// `TRADE` as zlib's raw deflate sends it after a sync flush, minus the
+/// `00 00 ff ff` tail that permessage-deflate drops.
+const TRADE_COMPRESSED: &[u8] = &[
+    0xaa, 0x56, 0x4a, 0x55, 0xb2, 0x52, 0x2a, 0x29, 0x4a, 0x4c, 0x49, 0x55, 0xd2, 0x51, 0x72, 0x55,
+    0xb2, 0x32, 0xd4, 0x51, 0x2a, 0x06, 0x8a, 0xb8, 0x86, 0x78, 0x84, 0x06, 0xbb, 0x84, 0x00, 0xc5,
+    0x4a, 0x94, 0xac, 0x4c, 0x8c, 0x74, 0x94, 0x0a, 0x80, 0x82, 0xc6, 0x06, 0x06, 0x06, 0x7a, 0xa6,
+    0x06, 0x40, 0xc1, 0x42, 0x20, 0xcf, 0x40, 0xcf, 0xc8, 0x14, 0xc8, 0x0c, 0x01, 0xeb, 0xc9, 0x55,
+    0xb2, 0x4a, 0x4b, 0xcc, 0x29, 0x4e, 0xad, 0x05, 0x00,
+];
+/// The same message from the same compressor: a back-reference into `TRADE`.
+const TRADE_AGAIN_COMPRESSED: &[u8] = &[0xaa, 0xa6, 0xb6, 0x81, 0x00, 0x00];
+const CLOSE_NORMAL: [u8; 2] = [0x03, 0xe8];
+
+/// Reads the client's handshake request, up to the blank line.
+async fn read_request(socket: &mut TcpStream) -> String {
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        request.push(socket.read_u8().await.unwrap());
+    }
+    String::from_utf8(request).unwrap()
+}
+
+/// A 101 response for `request`, accepting `extension` when given.
+fn accept(request: &str, extension: Option<&str>) -> String {
+    let key = request
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("sec-websocket-key")
+                .then(|| value.trim())
+        })
+        .unwrap();
+    let extension = extension
+        .map(|extension| format!("Sec-WebSocket-Extensions: {}\r\n", extension))
+        .unwrap_or_default();
+    format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n{}\r\n",
+        derive_accept_key(key.as_bytes()),
+        extension
+    )
+}
+
+/// An unmasked server frame; `first_byte` carries FIN, RSV1 and the opcode.
+fn frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(payload.len() < 126);
+    let mut frame = vec![first_byte, payload.len() as u8];
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Runs a session to the server's close and returns the text frames it saw.
+async fn collect_texts(url: &str) -> (Vec<String>, Option<f64>) {
+    deflate::init(true);
+    let mut session = WsSession::connect(url).await.unwrap();
+    let mut texts = Vec::new();
+    let run = session.run(|text| {
+        texts.push(text.to_string());
+        true
+    });
+    tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .expect("session didn't end on close")
+        .unwrap();
+    (texts, session.stats().compression_ratio())
+}
+
+/// Compressed messages, one split over two frames around a ping, come out as
+/// plain text, and the ratio covers only the compressed bytes.
+#[tokio::test]
+async fn accepted_offer_inflates_messages_and_tracks_the_ratio() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let offer = Arc::new(Mutex::new(String::new()));
+    let seen_offer = offer.clone();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut socket).await;
+        *seen_offer.lock().unwrap() = request.clone();
+        let response = accept(
+            &request,
+            Some("permessage-deflate; client_no_context_takeover"),
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+
+        let (first, rest) = TRADE_AGAIN_COMPRESSED.split_at(3);
+        let mut frames = frame(0xc1, TRADE_COMPRESSED);
+        frames.extend(frame(0x41, first));
+        frames.extend(frame(0x89, b"heartbeat"));
+        frames.extend(frame(0x80, rest));
+        frames.extend(frame(0x81, b"{\"result\":null,\"id\":1}"));
+        frames.extend(frame(0x88, &CLOSE_NORMAL));
+        socket.write_all(&frames).await.unwrap();
+        let mut rest = Vec::new();
+        let _ = socket.read_to_end(&mut rest).await;
+    });
+
+    let (texts, ratio) = collect_texts(&url).await;
+    assert!(
+        offer
+            .lock()
+            .unwrap()
+            .to_ascii_lowercase()
+            .contains("sec-websocket-extensions: permessage-deflate"),
+        "{}",
+        offer.lock().unwrap()
+    );
+    assert_eq!(texts, [TRADE, TRADE, "{\"result\":null,\"id\":1}"]);
+    let compressed = TRADE_COMPRESSED.len() + TRADE_AGAIN_COMPRESSED.len();
+    let expected = (2 * TRADE.len()) as f64 / compressed as f64;
+    assert!((ratio.unwrap() - expected).abs() < 1e-9, "{:?}", ratio);
+}
+
+/// A server that ignores the offer is read as usual, with no ratio to report.
+#[tokio::test]
+async fn ignored_offer_reads_plain_frames() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+        ws.close(None).await.unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+
+    let (texts, ratio) = collect_texts(&url).await;
+    assert_eq!(texts, [TRADE]);
+    assert_eq!(ratio, None);
+}
+
+/// A server that fails the handshake over the offer gets a second one without it.
+#[tokio::test]
+async fn refused_offer_is_retried_uncompressed() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            seen.lock().unwrap().push(request.clone());
+            if request.contains("permessage-deflate") {
+                let refusal = "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n";
+                socket.write_all(refusal.as_bytes()).await.unwrap();
+                continue;
+            }
+            socket
+                .write_all(accept(&request, None).as_bytes())
+                .await
+                .unwrap();
+            let mut frames = frame(0x81, TRADE.as_bytes());
+            frames.extend(frame(0x88, &CLOSE_NORMAL));
+            socket.write_all(&frames).await.unwrap();
+            let mut rest = Vec::new();
+            let _ = socket.read_to_end(&mut rest).await;
+        }
+    });
+
+    let (texts, ratio) = collect_texts(&url).await;
+    assert_eq!(texts, [TRADE]);
+    assert_eq!(ratio, None);
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(!requests[1].contains("permessage-deflate"));
+}
@@ -0,0 +1,327 @@
+//! Runs the `dynamic_subscriptions` bin against a local websocket server that
+//! answers SUBSCRIBE/UNSUBSCRIBE requests, feeding commands through stdin.
+
+use futures::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::process::Child;
+use tokio_tungstenite::tungstenite::Message;
+
+impl ServerLog {
+    /// The connection each request naming `stream` arrived on.
+    fn connections_for(&self, stream: &str) -> Vec<u64> {
+        self.requests
+            .iter()
+            .filter(|(_, request)| {
+                request["params"]
+                    .as_array()
+                    .is_some_and(|params| params.iter().any(|param| param == stream))
+            })
+            .map(|(k, _)| *k)
+            .collect()
+    }
+}
+
+/// What the server saw, in order: `(connection, request)` for every text frame,
+/// plus the pings it answered.
+#[derive(Debug, Default)]
+struct ServerLog {
+    connections: u64,
+    requests: Vec<(u64, Value)>,
+    pings: usize,
+}
+
+/// How the server answers the `n`-th request (from 1) on connection `k` (from 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reply {
+    Ack,
+    /// Answer with an error, as Binance does for an unknown stream.
+    Reject,
+    /// Close the socket without answering.
+    Close,
+}
+
+/// Acks requests per `reply` and, while `trades` is set, sends a `@trade` event
+/// every 50ms for each subscribed trade stream.
+async fn spawn_server(
+    reply: fn(u64, usize) -> Reply,
+    trades: bool,
+) -> (String, Arc<Mutex<ServerLog>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let log = Arc::new(Mutex::new(ServerLog::default()));
+    let server_log = log.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                continue;
+            };
+            let k = {
+                let mut log = server_log.lock().unwrap();
+                log.connections += 1;
+                log.connections
+            };
+            let log = server_log.clone();
+            tokio::spawn(async move {
+                let mut subscribed = Vec::<String>::new();
+                let mut received = 0;
+                let mut trade_id = 0;
+                let mut tick = tokio::time::interval(Duration::from_millis(50));
+                loop {
+                    tokio::select! {
+                        msg = ws.next() => match msg {
+                            Some(Ok(Message::Text(text))) => {
+                                let request: Value = serde_json::from_str(&text).unwrap();
+                                log.lock().unwrap().requests.push((k, request.clone()));
+                                received += 1;
+                                match reply(k, received) {
+                                    Reply::Ack => {}
+                                    Reply::Reject => {
+                                        let error = format!(
+                                            r#"{{"error":{{"code":2,"msg":"Invalid request"}},"id":{}}}"#,
+                                            request["id"]
+                                        );
+                                        if ws.send(Message::Text(error)).await.is_err() {
+                                            break;
+                                        }
+                                        continue;
+                                    }
+                                    Reply::Close => {
+                                        let _ = ws.close(None).await;
+                                        break;
+                                    }
+                                }
+                                let params = request["params"].as_array().cloned().unwrap_or_default();
+                                let streams = params.iter().filter_map(Value::as_str).map(str::to_string);
+                                match request["method"].as_str() {
+                                    Some("SUBSCRIBE") => subscribed.extend(streams),
+                                    Some("UNSUBSCRIBE") => {
+                                        let streams = streams.collect::<Vec<_>>();
+                                        subscribed.retain(|s| !streams.contains(s));
+                                    }
+                                    _ => {}
+                                }
+                                let ack = format!(r#"{{"result":null,"id":{}}}"#, request["id"]);
+                                if ws.send(Message::Text(ack)).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Some(Ok(Message::Ping(_))) => log.lock().unwrap().pings += 1,
+                            Some(Ok(_)) => {}
+                            _ => break,
+                        },
+                        _ = tick.tick(), if trades => {
+                            for stream in subscribed.iter().filter(|s| s.ends_with("@trade")) {
+                                trade_id += 1;
+                                let symbol = stream.trim_end_matches("@trade").to_uppercase();
+                                let trade = format!(
+                                    r#"{{"e":"trade","E":1,"s":"{}","t":{},"p":"100.0","q":"1.0","T":1,"m":true}}"#,
+                                    symbol, trade_id
+                                );
+                                if ws.send(Message::Text(trade)).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+    (url, log)
+}
+
+struct Run {
+    child: Child,
+    log_file: PathBuf,
+}
+
+impl Run {
+    fn start(name: &str, ws_url: &str, args: &[&str]) -> Self {
+        let log_file = std::env::temp_dir().join(format!(
+            "dynamic_subscriptions_{}_{}.log",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&log_file);
+        let child = tokio::process::Command::new(env!("CARGO_BIN_EXE_dynamic_subscriptions"))
+            .args(["--ws-url", ws_url, "--reconnect", "fixed:1"])
+            .args(args)
+            .arg("--log-file")
+            .arg(&log_file)
+            .env("LOG_LEVEL", "info")
+            .env_remove("RUST_LOG")
+            .env_remove("LOG_FORMAT")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        Self { child, log_file }
+    }
+
+    async fn command(&mut self, line: &str) {
+        let stdin = self.child.stdin.as_mut().unwrap();
+        stdin
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .unwrap();
+        stdin.flush().await.unwrap();
+    }
+
+    /// Waits for the bin to exit (it is run with `--duration`) and returns its log.
+    async fn finish(mut self) -> String {
+        let status = self.child.wait().await.unwrap();
+        assert!(
+            status.success(),
+            "dynamic_subscriptions exited with {}",
+            status
+        );
+        let log = std::fs::read_to_string(&self.log_file).unwrap();
+        let _ = std::fs::remove_file(&self.log_file);
+        log
+    }
+}
+
+/// With every stream removed there is nothing to hear, so the watchdog must not
+/// reconnect on silence; it pings instead, and the answered pings keep it alive.
+#[tokio::test]
+async fn empty_subscription_set_is_kept_alive_by_pings() {
+    let (ws_url, server) = spawn_server(|_, _| Reply::Ack, false).await;
+    let mut run = Run::start(
+        "idle",
+        &ws_url,
+        &["--stale-timeout", "2", "--duration", "7"],
+    );
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    run.command("delsub ethusdt@trade").await;
+    let log = run.finish().await;
+
+    let server = server.lock().unwrap();
+    assert_eq!(server.connections, 1, "reconnected:\n{}", log);
+    assert!(server.pings >= 2, "{} pings", server.pings);
+    assert!(!log.contains("stale"), "{}", log);
+}
+
+/// A socket lost right after a SUBSCRIBE goes out: that stream is covered by the
+/// next connection's initial subscribe, and an `addsub` typed while disconnected
+/// is queued and sent exactly once after it.
+#[tokio::test]
+async fn commands_during_a_disconnect_are_replayed_once() {
+    let (ws_url, server) = spawn_server(
+        |k, n| {
+            if k == 1 && n == 2 {
+                Reply::Close
+            } else {
+                Reply::Ack
+            }
+        },
+        false,
+    )
+    .await;
+    let mut run = Run::start("replay", &ws_url, &["--duration", "4"]);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    run.command("addsub btcusdt@trade").await;
+    // The reconnect delay is 1s, so this lands while disconnected.
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    run.command("addsub solusdt@trade").await;
+    let log = run.finish().await;
+
+    assert!(
+        log.contains("Disconnected; queued Subscribe(\"solusdt@trade\")"),
+        "{}",
+        log
+    );
+    assert!(log.contains("Replaying 1 command(s)"), "{}", log);
+    let server = server.lock().unwrap();
+    assert_eq!(server.connections, 2);
+    assert_eq!(server.connections_for("btcusdt@trade"), vec![1, 2]);
+    assert_eq!(server.connections_for("solusdt@trade"), vec![2]);
+
+    let resubscribe = &server
+        .requests
+        .iter()
+        .find(|(k, _)| *k == 2)
+        .expect("no requests after reconnecting")
+        .1;
+    let mut params = resubscribe["params"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(Value::as_str)
+        .collect::<Vec<_>>();
+    params.sort();
+    assert_eq!(resubscribe["method"], "SUBSCRIBE");
+    assert_eq!(params, ["btcusdt@trade", "ethusdt@trade"]);
+}
+
+/// Shards without streams stay disconnected: one connection for the one initial
+/// stream, a second once `addsub` gives another shard work, closed again by `delsub`.
+#[tokio::test]
+async fn shards_only_connect_while_they_have_streams() {
+    let (ws_url, server) = spawn_server(|_, _| Reply::Ack, false).await;
+    let mut run = Run::start(
+        "shards_lazy",
+        &ws_url,
+        &["--shards", "3", "--duration", "3"],
+    );
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    assert_eq!(server.lock().unwrap().connections, 1);
+
+    run.command("addsub btcusdt@trade").await;
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    assert_eq!(server.lock().unwrap().connections, 2);
+
+    run.command("delsub btcusdt@trade").await;
+    let log = run.finish().await;
+
+    assert_eq!(server.lock().unwrap().connections, 2, "{}", log);
+    assert!(
+        log.contains("No streams left on this connection; closing it"),
+        "{}",
+        log
+    );
+    assert!(!log.contains("reconnect attempt"), "{}", log);
+}
+
+/// A subscribe the server rejects gives its shard's slot back: the next stream goes
+/// to that (now empty) shard rather than doubling up on the first one.
+#[tokio::test]
+async fn rejected_subscribe_frees_its_shard_slot() {
+    let (ws_url, server) = spawn_server(
+        |k, n| {
+            if k == 2 && n == 1 {
+                Reply::Reject
+            } else {
+                Reply::Ack
+            }
+        },
+        false,
+    )
+    .await;
+    let mut run = Run::start(
+        "shards_reject",
+        &ws_url,
+        &["--shards", "2", "--duration", "3"],
+    );
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    run.command("addsub nosuchpair@trade").await;
+    tokio::time::sleep(Duration::from_millis(800)).await;
+    run.command("addsub btcusdt@trade").await;
+    let log = run.finish().await;
+
+    assert!(
+        log.contains("Shard 1 couldn't subscribe to nosuchpair@trade"),
+        "{}",
+        log
+    );
+    let server = server.lock().unwrap();
+    assert_eq!(server.connections_for("nosuchpair@trade"), vec![2]);
+    assert_eq!(server.connections_for("btcusdt@trade"), vec![3]);
+}
@@ -0,0 +1,172 @@
+//! Runs the `order_book` bin against a local depth-diff websocket and a local
+//! REST snapshot endpoint, then checks what it fetched and logged.
+
+use futures::{SinkExt, StreamExt};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Response, Server};
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Serves `GET` depth snapshots; the n-th request (from 1) gets `last_update_id(n)`.
+/// Returns the base URL and the request counter.
+fn spawn_snapshot_server(last_update_id: fn(usize) -> u64) -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let make_service = make_service_fn(move |_| {
+        let counter = counter.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |_request| {
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                let id = last_update_id(n);
+                let body = format!(
+                    r#"{{"lastUpdateId":{},"bids":[["10","1"]],"asks":[["5000","1"]]}}"#,
+                    id
+                );
+                async move { Ok::<_, Infallible>(Response::new(Body::from(body))) }
+            }))
+        }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    (url, requests)
+}
+
+/// Streams spot depth diffs every 20ms. Connection `k` (from 1) starts at update id
+/// `first_id(k)`, bids at price `bid(k)`, and closes after `close_after(k)` diffs.
+struct DepthFeed {
+    first_id: fn(u64) -> u64,
+    bid: fn(u64) -> u64,
+    close_after: fn(u64) -> Option<u64>,
+}
+
+async fn spawn_depth_server(feed: DepthFeed) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connections = 0;
+        while let Ok((stream, _)) = listener.accept().await {
+            connections += 1;
+            let k = connections;
+            let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                continue;
+            };
+            let mut tick = tokio::time::interval(Duration::from_millis(20));
+            let mut next = (feed.first_id)(k);
+            let mut sent = 0;
+            loop {
+                tokio::select! {
+                    msg = ws.next() => {
+                        if !matches!(msg, Some(Ok(Message::Ping(_) | Message::Pong(_)))) {
+                            break;
+                        }
+                    }
+                    _ = tick.tick() => {
+                        if (feed.close_after)(k) == Some(sent) {
+                            let _ = ws.close(None).await;
+                            break;
+                        }
+                        let diff = format!(
+                            r#"{{"e":"depthUpdate","E":1,"s":"BTCUSDT","U":{},"u":{},"b":[["{}","1"]],"a":[]}}"#,
+                            next,
+                            next + 4,
+                            (feed.bid)(k)
+                        );
+                        next += 5;
+                        sent += 1;
+                        if ws.send(Message::Text(diff)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    });
+    url
+}
+
+/// Runs the bin for `duration_secs` and returns its log.
+async fn run_order_book(name: &str, ws_url: &str, rest_url: &str, duration_secs: u64) -> String {
+    let log_file: PathBuf =
+        std::env::temp_dir().join(format!("order_book_{}_{}.log", name, std::process::id()));
+    let _ = std::fs::remove_file(&log_file);
+    let status = tokio::process::Command::new(env!("CARGO_BIN_EXE_order_book"))
+        .args(["--ws-url", ws_url, "--rest-url", rest_url])
+        .args(["--duration", &duration_secs.to_string()])
+        .arg("--log-file")
+        .arg(&log_file)
+        .env("LOG_LEVEL", "info")
+        .env_remove("RUST_LOG")
+        .env_remove("LOG_FORMAT")
+        .env("NO_PROXY", "127.0.0.1")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success(), "order_book exited with {}", status);
+    let log = std::fs::read_to_string(&log_file).unwrap();
+    let _ = std::fs::remove_file(&log_file);
+    log
+}
+
+/// A snapshot that never lines up with the diffs is refetched with a growing delay,
+/// one request at a time, instead of on every one of the ~100 diffs that arrive.
+#[tokio::test]
+async fn stale_snapshots_are_retried_with_backoff() {
+    let (rest_url, requests) = spawn_snapshot_server(|_| 1);
+    let ws_url = spawn_depth_server(DepthFeed {
+        first_id: |_| 100,
+        bid: |_| 100,
+        close_after: |_| None,
+    })
+    .await;
+
+    let log = run_order_book("backoff", &ws_url, &rest_url, 2).await;
+
+    // 250ms, 500ms, 1s: four requests fit in two seconds, a fifth at most on a slow start.
+    let requests = requests.load(Ordering::SeqCst);
+    assert!(
+        (2..=5).contains(&requests),
+        "{} snapshot requests",
+        requests
+    );
+    assert!(!log.contains("Order book synced"));
+    assert!(log.contains("Fetching another depth snapshot in 500ms"));
+}
+
+/// When the socket drops partway, the book is rebuilt from a second snapshot and
+/// none of the first connection's diffs (the only ones bidding 150) leak into it.
+#[tokio::test]
+async fn reconnect_rebuilds_the_book_from_a_new_snapshot() {
+    let (rest_url, requests) = spawn_snapshot_server(|n| if n == 1 { 1 } else { 1001 });
+    let ws_url = spawn_depth_server(DepthFeed {
+        first_id: |k| 1 + (k - 1) * 1000,
+        bid: |k| if k == 1 { 150 } else { 120 },
+        close_after: |k| (k == 1).then_some(10),
+    })
+    .await;
+
+    let log = run_order_book("reconnect", &ws_url, &rest_url, 4).await;
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert!(log.contains("Discarding the order book"));
+    let (before, after) = log
+        .split_once("Fetched depth snapshot lastUpdateId=1001")
+        .expect("no second snapshot");
+    assert!(before.contains("Bid: 150"));
+    assert!(after.contains("Order book synced"));
+    assert!(after.contains("Bid: 120"));
+    assert!(!after.contains("Bid: 150"));
+    for line in after.lines().filter(|line| line.contains("Top of book")) {
+        let id: u64 = line.rsplit("Update ID: ").next().unwrap().parse().unwrap();
+        assert!(id >= 1001, "stale update id in {}", line);
+    }
+}
@@ -0,0 +1,226 @@
+//! Drives `WsSession`, and the `dynamic_subscriptions` bin built on it, against a
+//! local websocket server that sends trade and depth frames, pings, then closes.
+
+use futures::{SinkExt, StreamExt};
+use public_data_streaming::clock::ManualClock;
+use public_data_streaming::handler::{self, TradeHandler};
+use public_data_streaming::models::{BinanceEvent, BinanceMessage, TradeEvent};
+use public_data_streaming::session::WsSession;
+use serde_json::Value;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const TRADE: &str =
+    r#"{"e":"trade","E":1,"s":"ETHUSDT","t":42,"p":"3000.50","q":"0.25","T":1,"m":false}"#;
+const DEPTH: &str =
+    r#"{"e":"depthUpdate","E":2,"s":"ETHUSDT","U":10,"u":12,"b":[["2999.00","1.5"]],"a":[]}"#;
+const PING_PAYLOAD: &[u8] = b"heartbeat";
+
+/// What the server received, per connection (from 1).
+#[derive(Debug, Default)]
+struct ServerLog {
+    connections: u64,
+    subscribes: Vec<(u64, Value)>,
+    pongs: Vec<(u64, Vec<u8>)>,
+}
+
+/// Sends a ping, `TRADE` and `DEPTH` on connect (after acking the SUBSCRIBE when
+/// `wait_for_subscribe` is set), then closes connection 1; later ones stay open.
+async fn spawn_server(wait_for_subscribe: bool) -> (String, Arc<Mutex<ServerLog>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let log = Arc::new(Mutex::new(ServerLog::default()));
+    let server_log = log.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                continue;
+            };
+            let k = {
+                let mut log = server_log.lock().unwrap();
+                log.connections += 1;
+                log.connections
+            };
+            let log = server_log.clone();
+            tokio::spawn(async move {
+                if wait_for_subscribe {
+                    let Some(Ok(Message::Text(text))) = ws.next().await else {
+                        return;
+                    };
+                    let request: Value = serde_json::from_str(&text).unwrap();
+                    let ack = format!(r#"{{"result":null,"id":{}}}"#, request["id"]);
+                    log.lock().unwrap().subscribes.push((k, request));
+                    ws.send(Message::Text(ack)).await.unwrap();
+                }
+                ws.send(Message::Ping(PING_PAYLOAD.to_vec())).await.unwrap();
+                ws.send(Message::Text(TRADE.to_string())).await.unwrap();
+                ws.send(Message::Text(DEPTH.to_string())).await.unwrap();
+                // Unsolicited keepalive pongs are empty; only replies are recorded.
+                while let Some(Ok(msg)) = ws.next().await {
+                    if let Message::Pong(payload) = msg {
+                        if !payload.is_empty() {
+                            log.lock().unwrap().pongs.push((k, payload));
+                            // Close only after the reply, so the close can't overtake it.
+                            if k == 1 {
+                                let _ = ws.close(None).await;
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    });
+    (url, log)
+}
+
+/// What the handler was given, as display strings.
+#[derive(Default)]
+struct Recorder {
+    trades: Vec<String>,
+    events: Vec<String>,
+    other: Vec<Value>,
+}
+
+impl TradeHandler for Recorder {
+    fn on_trade(&mut self, trade: &TradeEvent) {
+        self.trades.push(format!(
+            "{} #{} {} x {}",
+            trade.symbol, trade.trade_id, trade.price, trade.quantity
+        ));
+    }
+
+    fn on_event(&mut self, event: &BinanceEvent) {
+        let summary = match event {
+            BinanceEvent::DepthUpdate(depth) => format!(
+                "depth {} {}..{} bid {}",
+                depth.symbol, depth.first_update_id, depth.final_update_id, depth.bids[0].0
+            ),
+            other => format!("{:?}", other),
+        };
+        self.events.push(summary);
+    }
+
+    fn on_other(&mut self, value: &Value) {
+        self.other.push(value.clone());
+    }
+}
+
+/// The session hands the handler the trade and depth frames, answers the ping
+/// with its payload, and `run` returns cleanly once the server closes.
+#[tokio::test]
+async fn session_parses_frames_answers_pings_and_ends_on_close() {
+    let (url, server) = spawn_server(false).await;
+    let mut session = WsSession::connect(&url).await.unwrap();
+    let mut recorder = Recorder::default();
+
+    let run = session.run(|text| {
+        let message: BinanceMessage = serde_json::from_str(text).unwrap();
+        handler::dispatch(&mut recorder, message);
+        true
+    });
+    tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .expect("session didn't end on close")
+        .unwrap();
+
+    assert_eq!(recorder.trades, ["ETHUSDT #42 3000.50 x 0.25"]);
+    assert_eq!(recorder.events, ["depth ETHUSDT 10..12 bid 2999.00"]);
+    assert!(recorder.other.is_empty());
+    assert_eq!(
+        server.lock().unwrap().pongs,
+        vec![(1, PING_PAYLOAD.to_vec())]
+    );
+}
+
+/// The stale watchdog runs off the session's clock: a silent server ends the
+/// session as soon as the clock passes the timeout, without waiting it out.
+#[tokio::test]
+async fn silent_connection_goes_stale_once_the_clock_passes_the_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+
+    let clock = ManualClock::new();
+    let mut session = WsSession::connect(&url)
+        .await
+        .unwrap()
+        .with_stale_timeout(30)
+        .with_clock(Arc::new(clock.clone()));
+
+    clock.advance(Duration::from_secs(30));
+    let early = tokio::time::timeout(Duration::from_millis(1500), session.next_text()).await;
+    assert!(early.is_err(), "went stale at exactly the timeout");
+
+    clock.advance(Duration::from_secs(1));
+    let text = tokio::time::timeout(Duration::from_secs(3), session.next_text())
+        .await
+        .expect("watchdog didn't fire")
+        .unwrap();
+    assert_eq!(text, None);
+    assert_eq!(session.stats().health(), "stale");
+    assert_eq!(session.error_class(), None);
+}
+
+/// When the server closes, the bin reconnects, subscribes again, and keeps
+/// parsing trades and answering pings on the new connection.
+#[tokio::test]
+async fn dynamic_client_resubscribes_after_the_server_closes() {
+    let (ws_url, server) = spawn_server(true).await;
+    let log_file =
+        std::env::temp_dir().join(format!("session_resubscribe_{}.log", std::process::id()));
+    let _ = std::fs::remove_file(&log_file);
+    let status = tokio::process::Command::new(env!("CARGO_BIN_EXE_dynamic_subscriptions"))
+        .args([
+            "--ws-url",
+            &ws_url,
+            "--reconnect",
+            "fixed:1",
+            "--duration",
+            "3",
+        ])
+        .env("LOG_FILE", &log_file)
+        .env("LOG_LEVEL", "info")
+        .env_remove("RUST_LOG")
+        .env_remove("LOG_FORMAT")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await
+        .unwrap();
+    let log = std::fs::read_to_string(&log_file).unwrap();
+    let _ = std::fs::remove_file(&log_file);
+    assert!(
+        status.success(),
+        "dynamic_subscriptions exited with {}",
+        status
+    );
+
+    let server = server.lock().unwrap();
+    assert_eq!(server.connections, 2, "{}", log);
+    for (k, (connection, request)) in server.subscribes.iter().enumerate() {
+        assert_eq!(*connection, k as u64 + 1);
+        assert_eq!(request["method"], "SUBSCRIBE");
+        assert_eq!(request["params"], serde_json::json!(["ethusdt@trade"]));
+    }
+    assert_eq!(server.subscribes.len(), 2);
+    assert_eq!(
+        server.pongs,
+        vec![(1, PING_PAYLOAD.to_vec()), (2, PING_PAYLOAD.to_vec())]
+    );
+
+    assert!(log.contains("reconnect attempt 1"), "{}", log);
+    let trades = log
+        .matches("Trade - Symbol: ETHUSDT, Price: 3000.50, Quantity: 0.25")
+        .count();
+    assert_eq!(trades, 2, "{}", log);
+    assert!(log.contains("Run duration elapsed"), "{}", log);
+}
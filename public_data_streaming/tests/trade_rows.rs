@@ -0,0 +1,72 @@
+//! Replays a capture through `fixed_url_stream --format csv|json` and checks that
+//! stdout carries exactly the trade rows, with the log on stderr.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const TRADES: [(u64, &str); 3] = [(1, "0.00100000"), (2, "2.50000000"), (3, "0.50000000")];
+
+fn capture(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("trade_rows_{}_{}.jsonl", name, std::process::id()));
+    let lines = TRADES
+        .iter()
+        .map(|(id, qty)| {
+            format!(
+                r#"{{"e":"trade","E":{id},"s":"BTCUSDT","t":{id},"p":"64000.10","q":"{qty}","T":{id},"m":true}}"#
+            )
+        })
+        .collect::<Vec<_>>();
+    std::fs::write(&path, lines.join("\n")).unwrap();
+    path
+}
+
+fn replay(name: &str, args: &[&str], log_level: &str) -> Output {
+    let capture = capture(name);
+    let log_file =
+        std::env::temp_dir().join(format!("trade_rows_{}_{}.log", name, std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_fixed_url_stream"))
+        .arg("--replay")
+        .arg(&capture)
+        .args(args)
+        .arg("--log-file")
+        .arg(&log_file)
+        .env("LOG_LEVEL", log_level)
+        .env_remove("RUST_LOG")
+        .env_remove("LOG_FORMAT")
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(capture);
+    let _ = std::fs::remove_file(log_file);
+    assert!(output.status.success());
+    output
+}
+
+#[test]
+fn csv_rows_ignore_the_log_level_but_honour_min_qty() {
+    let output = replay("csv", &["--format", "csv", "--min-qty", "0.5"], "error");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "kind,symbol,id,price,quantity,buyer_maker,trade_time\n\
+         trade,BTCUSDT,2,64000.10,2.50000000,true,2\n\
+         trade,BTCUSDT,3,64000.10,0.50000000,true,3\n"
+    );
+}
+
+#[test]
+fn json_rows_keep_stdout_to_themselves() {
+    let output = replay("json", &["--format", "json"], "info");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(rows.len(), TRADES.len());
+    for (row, (id, qty)) in rows.iter().zip(TRADES) {
+        let row: serde_json::Value = serde_json::from_str(row).unwrap();
+        assert_eq!(row["id"], id);
+        assert_eq!(row["quantity"], qty);
+    }
+
+    // The info log still runs, on stderr.
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("INFO"));
+    assert!(!stderr.contains("\"kind\""));
+}